@@ -0,0 +1,88 @@
+use criterion::{Criterion, criterion_group, criterion_main, Throughput};
+
+use wslpath::convert::{Converter, PathSeparators};
+use wslpath::convert::line_sep::LineSep;
+use wslpath::convert::{win_to_wsl, wsl_to_win};
+
+/// About as many paths as indexing a large monorepo produces.
+const NUM_PATHS: usize = 2_000_000;
+
+const SEPS: PathSeparators<LineSep, LineSep> = PathSeparators {
+    input: LineSep::LF,
+    output: LineSep::LF,
+};
+
+/// [`NUM_PATHS`] newline-separated paths under [`root`] that look like a source tree,
+/// with the occasional name that has a char that needs encoding.
+fn paths(root: &str, sep: char) -> Vec<u8> {
+    let mut paths = String::new();
+    for i in 0..NUM_PATHS {
+        let name = match i % 1000 {
+            0 => "weird:name",
+            _ => "file",
+        };
+        let path = [
+            root,
+            "src",
+            "monorepo",
+            &format!("crate_{}", i % 97),
+            "src",
+            &format!("module_{}", i % 13),
+            &format!("{}_{}.rs", name, i),
+        ].join(&sep.to_string());
+        paths.push_str(&path);
+        paths.push('\n');
+    }
+    paths.into_bytes()
+}
+
+/// The simplest conversion to compare against:
+/// go through every byte, replacing [`from`] with [`to`] and checking for [`illegal`],
+/// without converting any prefixes or encoding anything.
+fn baseline(paths: &[u8], from: u8, to: u8, illegal: u8) -> Result<Vec<u8>, usize> {
+    let mut buf = Vec::with_capacity(paths.len());
+    for (i, c) in paths.iter().copied().enumerate() {
+        match c {
+            _ if c == illegal => return Err(i),
+            _ if c == from => buf.push(to),
+            _ => buf.push(c),
+        }
+    }
+    Ok(buf)
+}
+
+fn win_to_wsl(c: &mut Criterion) {
+    let converter = win_to_wsl::Converter::new(win_to_wsl::Options {
+        convert_root_loop: false,
+        distro: Some("bench".into()),
+        ..Default::default()
+    }).unwrap();
+    let paths = paths(r"C:\Users\dev", '\\');
+    let mut group = c.benchmark_group("win_to_wsl");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(paths.len() as u64));
+    group.bench_function("convert_all", |b| b.iter(|| converter.convert_all(&paths, &SEPS)));
+    group.bench_function("baseline", |b| b.iter(|| baseline(&paths, b'\\', b'/', b'/')));
+    group.finish();
+}
+
+fn wsl_to_win(c: &mut Criterion) {
+    let converter = wsl_to_win::Converter::new(wsl_to_win::Options {
+        sep: Default::default(),
+        base_directory: None,
+        canonicalize: false,
+        distro: Some("bench".into()),
+        keep_relative: false,
+        canonicalize_cache: None,
+    }).unwrap();
+    let paths = paths("/home/dev", '/');
+    let mut group = c.benchmark_group("wsl_to_win");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(paths.len() as u64));
+    group.bench_function("convert_all", |b| b.iter(|| converter.convert_all(&paths, &SEPS)));
+    group.bench_function("baseline", |b| b.iter(|| baseline(&paths, b'/', b'\\', 0)));
+    group.finish();
+}
+
+criterion_group!(benches, win_to_wsl, wsl_to_win);
+criterion_main!(benches);
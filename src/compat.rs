@@ -0,0 +1,176 @@
+use std::env;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+use print_bytes::print_bytes;
+
+use wslpath::convert::{Converter, win_to_wsl, wsl_to_win};
+use wslpath::convert::path_sep::WindowsPathSep;
+
+const USAGE: &str = "\
+wslpath: Invalid argument
+Usage:
+    -a    force result to absolute path format
+    -u    translate from a Windows path to a WSL path (default)
+    -w    translate from a WSL path to a Windows path
+    -m    translate from a WSL path to a Windows path, with '/' instead of '\\'
+
+EX: wslpath 'c:\\users'
+";
+
+/// The names the extended CLI's subcommands and top-level flags are parsed as.
+const SUBCOMMANDS: &[&str] = &["win", "wsl", "env", "exec", "filter", "wsl-version", "help", "-h", "--help", "-V", "--version"];
+
+enum Direction {
+    ToWsl,
+    ToWin(WindowsPathSep),
+}
+
+struct Args {
+    direction: Direction,
+    absolute: bool,
+    path: OsString,
+}
+
+/// Whether to act like the system `wslpath`,
+/// either explicitly with a leading `--compat`,
+/// or when invoked as `wslpath` without one of our subcommands.
+/// If `--compat` was passed, it's removed from [`args`].
+pub fn is_compat(args: &mut Vec<OsString>) -> bool {
+    if args.get(1).map(OsString::as_os_str) == Some(OsStr::new("--compat")) {
+        args.remove(1);
+        return true;
+    }
+    let invoked_as_wslpath = args
+        .first()
+        .and_then(|it| Path::new(it).file_name())
+        == Some(OsStr::new("wslpath"));
+    let has_subcommand = args
+        .get(1)
+        .and_then(|it| it.to_str())
+        .is_some_and(|it| SUBCOMMANDS.contains(&it));
+    invoked_as_wslpath && !has_subcommand
+}
+
+/// Parse `wslpath [-a] [-u|-w|-m] <path>` like getopt would,
+/// allowing combined flags like `-wa` and the last direction flag to win.
+fn parse(args: &[OsString]) -> Option<Args> {
+    let mut direction = Direction::ToWsl;
+    let mut absolute = false;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let bytes = arg.as_bytes();
+        match bytes {
+            b"--" => {
+                path = Some(args.next()?.clone());
+                break;
+            }
+            [b'-', flags @ ..] if !flags.is_empty() => {
+                for flag in flags {
+                    match flag {
+                        b'a' => absolute = true,
+                        b'u' => direction = Direction::ToWsl,
+                        b'w' => direction = Direction::ToWin(WindowsPathSep::BackSlash),
+                        b'm' => direction = Direction::ToWin(WindowsPathSep::Slash),
+                        _ => return None,
+                    }
+                }
+            }
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return None,
+        }
+    }
+    // exactly one path
+    if args.next().is_some() {
+        return None;
+    }
+    Some(Args {
+        direction,
+        absolute,
+        path: path?,
+    })
+}
+
+/// The system wslpath just reports the underlying error, like `No such file or directory`,
+/// without the ` (os error 2)` that [`io::Error`] adds after it.
+fn root_cause(error: &(dyn Error + 'static)) -> String {
+    let mut error = error;
+    while let Some(source) = error.source() {
+        error = source;
+    }
+    let message = error.to_string();
+    let code = error.downcast_ref::<io::Error>().and_then(io::Error::raw_os_error);
+    match code.and_then(|code| message.strip_suffix(&format!(" (os error {})", code))) {
+        Some(message) => message.to_string(),
+        None => message,
+    }
+}
+
+fn convert<C: Converter>(options: C::Options, path: &OsStr) -> Result<OsString, String> {
+    let converter = C::new(options).map_err(|e| root_cause(&e))?;
+    converter.convert(path).map_err(|e| root_cause(&e))
+}
+
+fn to_wsl(path: &OsStr, absolute: bool) -> Result<OsString, String> {
+    // the system wslpath accepts both separators
+    let path = path
+        .as_bytes()
+        .iter()
+        .map(|c| if *c == b'\\' { b'/' } else { *c })
+        .collect::<Vec<_>>();
+    let options = win_to_wsl::Options {
+        sep: WindowsPathSep::Slash,
+        keep_relative: true,
+        ..Default::default()
+    };
+    let converted = convert::<win_to_wsl::Converter>(options, OsStr::from_bytes(&path))?;
+    if absolute && Path::new(&converted).is_relative() {
+        let cwd = env::current_dir().map_err(|e| root_cause(&e))?;
+        return Ok(cwd.join(converted).into_os_string());
+    }
+    Ok(converted)
+}
+
+fn to_win(path: &OsStr, absolute: bool, sep: WindowsPathSep) -> Result<OsString, String> {
+    let options = wsl_to_win::Options {
+        sep,
+        base_directory: Some(env::current_dir().map_err(|e| root_cause(&e))?),
+        canonicalize: true,
+        distro: None,
+        keep_relative: !absolute,
+        // only one path is converted
+        canonicalize_cache: None,
+    };
+    convert::<wsl_to_win::Converter>(options, path)
+}
+
+/// Run like the system `wslpath`, returning the exit code.
+pub fn run(args: &[OsString]) -> i32 {
+    let Args { direction, absolute, path } = match parse(args.get(1..).unwrap_or_default()) {
+        Some(args) => args,
+        None => {
+            eprint!("{}", USAGE);
+            return 1;
+        }
+    };
+    let converted = match direction {
+        Direction::ToWsl => to_wsl(&path, absolute),
+        Direction::ToWin(sep) => to_win(&path, absolute, sep),
+    };
+    match converted {
+        Ok(converted) => {
+            let mut line = converted.into_vec();
+            line.push(b'\n');
+            print_bytes(OsStr::from_bytes(&line));
+            0
+        }
+        Err(e) => {
+            eprintln!("wslpath: {}: {}", Path::new(&path).display(), e);
+            1
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::{io, iter};
+use std::future::Future;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::task::JoinHandle;
+
+use crate::convert::{BufferSizeBlocks, BulkConversion, ConvertFileError, Converter, InputPathSeparator, OneConvertError, OutputPathSeparator, PathSeparators, Position, unterminated_path_error};
+use crate::convert::eof::EofPolicy;
+use crate::convert::mmap::MMAP_CHUNK_LEN;
+
+/// Like [`Converter::convert_file`], but opening and reading [`path`] asynchronously.
+pub async fn convert_file<C, InputSep, OutputSep, P>(
+    converter: Arc<C>,
+    path: P,
+    seps: Arc<PathSeparators<InputSep, OutputSep>>,
+    buffer_size_blocks: BufferSizeBlocks,
+) -> Result<ConversionStream<C, InputSep, OutputSep, File>, ConvertFileError>
+    where C: Converter + Send + Sync + 'static,
+          InputSep: InputPathSeparator + Send + Sync + 'static,
+          OutputSep: OutputPathSeparator + Send + Sync + 'static,
+          P: AsRef<Path> {
+    let file = File::open(path).await?;
+    let metadata = file.metadata().await?;
+    if metadata.is_dir() {
+        return Err(ConvertFileError::IsADirectory);
+    }
+    // there's no mmap here, so don't read a huge file all at once
+    let buf_len = buffer_size_blocks.buf_len(&metadata).min(MMAP_CHUNK_LEN);
+    Ok(convert_reader(converter, file, seps, buf_len))
+}
+
+/// Like [`Converter::convert_reader`], but for an [`AsyncRead`].
+pub fn convert_reader<C, InputSep, OutputSep, R>(
+    converter: Arc<C>,
+    reader: R,
+    seps: Arc<PathSeparators<InputSep, OutputSep>>,
+    buf_len: usize,
+) -> ConversionStream<C, InputSep, OutputSep, R>
+    where C: Converter + Send + Sync + 'static,
+          InputSep: InputPathSeparator + Send + Sync + 'static,
+          OutputSep: OutputPathSeparator + Send + Sync + 'static,
+          R: AsyncRead + Unpin {
+    ConversionStream {
+        converter,
+        separators: seps,
+        reader,
+        read_len: buf_len,
+        buf: Vec::new(),
+        position: Position::default(),
+        eof_policy: EofPolicy::default(),
+        state: State::Reading,
+    }
+}
+
+enum State<C: Converter> {
+    Reading,
+    /// Converting a chunk on the blocking thread pool,
+    /// since conversion can make blocking syscalls or even spawn processes.
+    Converting {
+        task: JoinHandle<(Vec<u8>, BulkConversion<C>)>,
+        is_end: bool,
+    },
+    Done,
+}
+
+/// The async counterpart of [`ConversionIterator`](crate::convert::ConversionIterator),
+/// yielding the same chunks of converted paths.
+pub struct ConversionStream<C, InputSep, OutputSep, R>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator,
+          R: AsyncRead + Unpin {
+    converter: Arc<C>,
+    separators: Arc<PathSeparators<InputSep, OutputSep>>,
+    reader: R,
+    read_len: usize,
+    buf: Vec<u8>,
+    /// Where the start of [`buf`] is in the whole input.
+    position: Position,
+    eof_policy: EofPolicy,
+    state: State<C>,
+}
+
+impl<C, InputSep, OutputSep, R> ConversionStream<C, InputSep, OutputSep, R>
+    where C: Converter + Send + Sync + 'static,
+          InputSep: InputPathSeparator + Send + Sync + 'static,
+          OutputSep: OutputPathSeparator + Send + Sync + 'static,
+          R: AsyncRead + Unpin {
+    pub fn buf_len(&self) -> usize {
+        self.read_len
+    }
+    
+    /// Set what to do with a final path that isn't ended by a separator.
+    pub fn with_eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.eof_policy = eof_policy;
+        self
+    }
+    
+    /// Convert [`buf`] off of the runtime.
+    fn spawn_convert(&mut self, is_end: bool) {
+        let converter = self.converter.clone();
+        let separators = self.separators.clone();
+        let mut buf = std::mem::take(&mut self.buf);
+        let position = self.position;
+        let task = tokio::task::spawn_blocking(move || {
+            let paths = buf.as_slice();
+            let mut converted = match is_end {
+                true => converter.convert_end_from(paths, &separators, position),
+                false => converter.convert_all_from(paths, &separators, position),
+            };
+            // remove everything except remainder
+            buf.splice(..converted.remainder_index, iter::empty::<u8>());
+            converted.remainder_index = 0;
+            (buf, converted)
+        });
+        self.state = State::Converting {
+            task,
+            is_end,
+        };
+    }
+    
+    /// Handle whatever's left in [`buf`] once the reader is at EOF.
+    fn end(&mut self) -> Option<io::Result<BulkConversion<C>>> {
+        self.state = State::Done;
+        if self.buf.is_empty() {
+            return None;
+        }
+        match self.eof_policy {
+            EofPolicy::Convert => {
+                self.spawn_convert(true);
+                None
+            }
+            EofPolicy::Error => {
+                let e = unterminated_path_error(&self.buf, self.position);
+                self.buf.clear();
+                Some(Err(e))
+            }
+            EofPolicy::Ignore => {
+                self.buf.clear();
+                None
+            }
+        }
+    }
+    
+    /// Convert everything, writing the converted paths to [`writer`]
+    /// and passing the error for each path that fails to convert to [`on_error`].
+    /// Returns the number of paths attempted.
+    pub async fn write_to<W, F>(mut self, mut writer: W, mut on_error: F) -> io::Result<usize>
+        where W: AsyncWrite + Unpin,
+              F: FnMut(OneConvertError<C::Error>) {
+        let mut num_paths = 0;
+        while let Some(converted) = std::future::poll_fn(|cx| Pin::new(&mut self).poll_next(cx)).await {
+            let converted = converted?;
+            writer.write_all(converted.paths.as_bytes()).await?;
+            num_paths += converted.num_paths;
+            converted.errors.into_iter().for_each(&mut on_error);
+        }
+        writer.flush().await?;
+        Ok(num_paths)
+    }
+    
+    /// Read the converted paths instead of streaming each chunk,
+    /// passing the error for each path that fails to convert to [`on_error`].
+    pub fn into_async_reader<F>(self, on_error: F) -> AsyncConversionReader<C, InputSep, OutputSep, R, F>
+        where F: FnMut(OneConvertError<C::Error>) + Unpin {
+        AsyncConversionReader {
+            stream: self,
+            on_error,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<C, InputSep, OutputSep, R> Stream for ConversionStream<C, InputSep, OutputSep, R>
+    where C: Converter + Send + Sync + 'static,
+          InputSep: InputPathSeparator + Send + Sync + 'static,
+          OutputSep: OutputPathSeparator + Send + Sync + 'static,
+          R: AsyncRead + Unpin {
+    type Item = io::Result<BulkConversion<C>>;
+    
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                State::Done => return Poll::Ready(None),
+                State::Converting { task, is_end } => {
+                    let is_end = *is_end;
+                    let (buf, converted) = match Pin::new(task).poll(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Err(e)) => {
+                            this.state = State::Done;
+                            return Poll::Ready(Some(Err(io::Error::other(e))));
+                        }
+                        Poll::Ready(Ok(done)) => done,
+                    };
+                    this.buf = buf;
+                    this.position = converted.next;
+                    this.state = match is_end {
+                        true => State::Done,
+                        false => State::Reading,
+                    };
+                    return Poll::Ready(Some(Ok(converted)));
+                }
+                State::Reading => {
+                    let start = this.buf.len();
+                    this.buf.resize(start + this.read_len, 0);
+                    let mut read_buf = ReadBuf::new(&mut this.buf[start..]);
+                    let result = Pin::new(&mut this.reader).poll_read(cx, &mut read_buf);
+                    let bytes_read = read_buf.filled().len();
+                    // only keep bytes actually read into buf
+                    this.buf.truncate(start + bytes_read);
+                    match result {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                        Poll::Ready(Ok(())) => {}
+                    }
+                    if bytes_read == 0 {
+                        if let Some(end) = this.end() {
+                            return Poll::Ready(Some(end));
+                        }
+                    } else {
+                        this.spawn_convert(false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An [`AsyncRead`] of converted paths, created by [`ConversionStream::into_async_reader`].
+pub struct AsyncConversionReader<C, InputSep, OutputSep, R, F>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator,
+          R: AsyncRead + Unpin,
+          F: FnMut(OneConvertError<C::Error>) + Unpin {
+    stream: ConversionStream<C, InputSep, OutputSep, R>,
+    on_error: F,
+    /// The current converted chunk.
+    buf: Vec<u8>,
+    /// How much of [`buf`] has been read.
+    pos: usize,
+}
+
+impl<C, InputSep, OutputSep, R, F> AsyncRead for AsyncConversionReader<C, InputSep, OutputSep, R, F>
+    where C: Converter + Send + Sync + 'static,
+          InputSep: InputPathSeparator + Send + Sync + 'static,
+          OutputSep: OutputPathSeparator + Send + Sync + 'static,
+          R: AsyncRead + Unpin,
+          F: FnMut(OneConvertError<C::Error>) + Unpin {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        // a chunk can be empty if it had no complete paths, so keep going until one isn't
+        while this.pos == this.buf.len() {
+            let converted = match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Ready(Some(converted)) => converted?,
+            };
+            converted.errors.into_iter().for_each(&mut this.on_error);
+            this.buf = converted.paths.into_vec();
+            this.pos = 0;
+        }
+        let available = &this.buf[this.pos..];
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        this.pos += n;
+        Poll::Ready(Ok(()))
+    }
+}
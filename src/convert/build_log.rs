@@ -0,0 +1,349 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, Write};
+use std::ops::Range;
+
+use crate::convert::{text, Converter};
+use crate::convert::path_sep::WindowsPathSep;
+use crate::convert::text::{find_paths, TextOptions, TextPaths, TextSummary};
+
+/// How to follow the directory changes in a build log, and find the paths in it.
+#[derive(Clone, Debug)]
+pub struct BuildLogOptions {
+    /// How to find the absolute Windows paths in the log,
+    /// and the separator the converter expects in them,
+    /// since make prints `C:/src` and MSBuild prints `C:\src`.
+    pub text: TextOptions,
+    /// The directory, Windows or Linux, that paths are relative to before the log changes directories,
+    /// or none to leave those paths as is.
+    /// It isn't used for MSBuild project contexts, whose paths are only relative to their own projects.
+    pub base_dir: Option<Vec<u8>>,
+}
+
+impl BuildLogOptions {
+    pub fn new(sep: WindowsPathSep) -> Self {
+        Self {
+            text: TextOptions {
+                sep,
+                ..TextOptions::new(TextPaths::Windows)
+            },
+            base_dir: None,
+        }
+    }
+}
+
+/// An absolute directory, with its separators normalized and no trailing one unless it's the root.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Dir {
+    path: Vec<u8>,
+    /// The length of the root, like `C:\`, `\\server\share\`, or `/`, which `..` doesn't go above.
+    root_len: usize,
+    windows: bool,
+}
+
+fn is_sep(c: u8) -> bool {
+    c == b'/' || c == b'\\'
+}
+
+impl Dir {
+    /// Parse the absolute directory [`path`], which is Windows if it starts with a drive or `\\`,
+    /// using [`sep`] for Windows paths.
+    fn parse(path: &[u8], sep: WindowsPathSep) -> Option<Self> {
+        let sep = sep.value();
+        let (root, rest, windows) = match path {
+            [drive, b':', c, rest @ ..] if drive.is_ascii_alphabetic() && is_sep(*c) => {
+                (vec![*drive, b':', sep], rest, true)
+            }
+            [b'\\', b'\\', rest @ ..] => {
+                let mut parts = rest.splitn(3, |c| is_sep(*c));
+                let server = parts.next().filter(|it| !it.is_empty())?;
+                let share = parts.next().filter(|it| !it.is_empty())?;
+                let root = [&[sep, sep], server, &[sep], share, &[sep]].concat();
+                (root, parts.next().unwrap_or_default(), true)
+            }
+            [b'/', rest @ ..] => (b"/".to_vec(), rest, false),
+            _ => return None,
+        };
+        let dir = Self {
+            root_len: root.len(),
+            path: root,
+            windows,
+        };
+        Some(dir.join(rest))
+    }
+    
+    fn sep(&self) -> u8 {
+        self.path[self.root_len - 1]
+    }
+    
+    /// Join [`relative`], which can use either separator, folding any `.` and `..` components.
+    fn join(&self, relative: &[u8]) -> Self {
+        let sep = self.sep();
+        let mut path = self.path.clone();
+        for component in relative.split(|c| is_sep(*c)) {
+            match component {
+                b"" | b"." => {}
+                b".." => {
+                    let parent = path[self.root_len..]
+                        .iter()
+                        .rposition(|c| *c == sep)
+                        .map_or(self.root_len, |i| self.root_len + i);
+                    path.truncate(parent);
+                }
+                name => {
+                    if path.len() > self.root_len {
+                        path.push(sep);
+                    }
+                    path.extend_from_slice(name);
+                }
+            }
+        }
+        Self {
+            path,
+            root_len: self.root_len,
+            windows: self.windows,
+        }
+    }
+}
+
+/// The `N>` prefix MSBuild puts before each line when building in parallel,
+/// which is the context ID of the project the line is from, not the node building it,
+/// and the rest of the line after it.
+fn split_context_id(line: &[u8]) -> (Option<u32>, &[u8]) {
+    let start = line.iter().position(|c| *c != b' ').unwrap_or(line.len());
+    let digits = line[start..].iter().take_while(|c| c.is_ascii_digit()).count();
+    let context_id = std::str::from_utf8(&line[start..start + digits]).ok().and_then(|it| it.parse().ok());
+    match (context_id, line.get(start + digits)) {
+        (Some(context_id), Some(b'>')) => (Some(context_id), &line[start + digits + 1..]),
+        _ => (None, line),
+    }
+}
+
+/// How a line of a build log changes the directory.
+#[derive(Debug, Eq, PartialEq)]
+enum Marker<'a> {
+    /// make's `make[1]: Entering directory '/src'`.
+    Enter(&'a [u8]),
+    /// make's `make[1]: Leaving directory '/src'`.
+    Leave,
+    /// ninja's `ninja: Entering directory `build'`, which isn't left again.
+    Change(&'a [u8]),
+    /// MSBuild's `Project "C:\src\a.vcxproj" on node 1 (default targets).`,
+    /// or `Project "C:\a.sln" (1) is building "C:\src\a.vcxproj" (2) on node 1 (default targets).`,
+    /// which enters the project's directory, for the project's context ID, like the `(2)`, if there is one.
+    /// Newer MSBuild versions print it as `(2:2)`, which is the context ID and then the build request's.
+    Project {
+        path: &'a [u8],
+        context_id: Option<u32>,
+    },
+    /// MSBuild's `Done Building Project "C:\src\a.vcxproj" (default targets).`
+    DoneProject,
+}
+
+/// The part of [`line`] after [`prefix`] up to [`close`], like a quoted directory.
+fn between<'a>(line: &'a [u8], prefix: &[u8], close: u8) -> Option<(&'a [u8], &'a [u8])> {
+    let start = line.windows(prefix.len()).position(|w| w == prefix)? + prefix.len();
+    let len = line[start..].iter().position(|c| *c == close)?;
+    Some((&line[start..start + len], &line[start + len + 1..]))
+}
+
+impl<'a> Marker<'a> {
+    fn parse(line: &'a [u8]) -> Option<Self> {
+        let line = line.trim_ascii_start();
+        if line.starts_with(b"Done Building Project \"") {
+            return Some(Marker::DoneProject);
+        }
+        if line.starts_with(b"Project \"") {
+            return match between(line, b"is building \"", b'"') {
+                Some((project, rest)) => {
+                    let context_id = between(rest, b"(", b')').and_then(|(ids, _)| {
+                        let context_id = ids.split(|c| *c == b':').next()?;
+                        std::str::from_utf8(context_id).ok()?.parse().ok()
+                    });
+                    Some(Marker::Project {
+                        path: project,
+                        context_id,
+                    })
+                }
+                None => Some(Marker::Project {
+                    path: between(line, b"\"", b'"')?.0,
+                    context_id: None,
+                }),
+            };
+        }
+        // make quotes like 'dir' or `dir', and ninja like `dir'
+        let quoted = |line: &'a [u8], prefix: &[u8]| {
+            let start = line.windows(prefix.len()).position(|w| w == prefix)? + prefix.len();
+            let dir = line[start..].strip_prefix(b"'").or_else(|| line[start..].strip_prefix(b"`"))?;
+            dir.strip_suffix(b"'")
+        };
+        if line.starts_with(b"ninja: ") {
+            return quoted(line, b": Entering directory ").map(Marker::Change);
+        }
+        if let Some(dir) = quoted(line, b": Entering directory ") {
+            return Some(Marker::Enter(dir));
+        }
+        quoted(line, b": Leaving directory ").map(|_| Marker::Leave)
+    }
+}
+
+/// The range of a relative path followed by a `:line` or `(line,column)` location starting at [`start`] in [`line`],
+/// like `src\a.c(12,5)` or `../src/a.c:12:5`.
+/// It has to have a `.` or separator and a letter, so that things like `note:` or `1.2:3` aren't paths.
+fn relative_path_at(line: &[u8], start: usize) -> Option<Range<usize>> {
+    let len = line[start..]
+        .iter()
+        .position(|c| c.is_ascii_whitespace() || b"\"'`<>|:(".contains(c))?;
+    let path = &line[start..start + len];
+    let location = &line[start + len..];
+    let has_location = match location {
+        [b':', c, ..] => c.is_ascii_digit(),
+        [b'(', rest @ ..] => {
+            let digits = rest.iter().take_while(|c| c.is_ascii_digit() || **c == b',').count();
+            digits > 0 && rest.get(digits) == Some(&b')')
+        }
+        _ => false,
+    };
+    let is_relative = !matches!(path.first(), None | Some(b'/' | b'\\' | b'-'));
+    let looks_like_path = path.iter().any(|c| *c == b'.' || is_sep(*c)) && path.iter().any(u8::is_ascii_alphabetic);
+    Some(start..start + len).filter(|_| has_location && is_relative && looks_like_path)
+}
+
+/// The ranges of the relative paths with locations in [`line`], at the start of words.
+fn find_relative_paths(line: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < line.len() {
+        let is_boundary = i == 0 || line[i - 1].is_ascii_whitespace() || b"\"'`".contains(&line[i - 1]);
+        match relative_path_at(line, i).filter(|_| is_boundary) {
+            Some(range) => {
+                i = range.end;
+                ranges.push(range);
+            }
+            None => i += 1,
+        }
+    }
+    ranges
+}
+
+/// Converts the paths in a build log from Windows to WSL, like [`convert_line`](crate::convert::text::convert_line),
+/// but also resolving relative `file:line` and `file(line)` paths against the directory the build was in.
+///
+/// That directory is followed from make's `Entering directory` and `Leaving directory` lines,
+/// ninja's `Entering directory` line, and MSBuild's project headers,
+/// with a separate stack for each MSBuild project context when it builds in parallel.
+pub struct BuildLog<C> {
+    converter: C,
+    options: BuildLogOptions,
+    base_dir: Option<Dir>,
+    /// The directories entered for each MSBuild project context ID, where `None` is one we couldn't parse.
+    stacks: HashMap<Option<u32>, Vec<Option<Dir>>>,
+    summary: TextSummary,
+}
+
+impl<C: Converter> BuildLog<C> {
+    pub fn new(converter: C, options: BuildLogOptions) -> Self {
+        let base_dir = options.base_dir.as_deref().and_then(|dir| Dir::parse(dir, options.text.sep));
+        Self {
+            converter,
+            options,
+            base_dir,
+            stacks: HashMap::new(),
+            summary: TextSummary::default(),
+        }
+    }
+    
+    pub fn converter(&self) -> &C {
+        &self.converter
+    }
+    
+    /// How many paths were found so far, and how many couldn't be converted or resolved.
+    pub fn summary(&self) -> TextSummary {
+        self.summary
+    }
+    
+    fn current_dir(&self, context_id: Option<u32>) -> Option<&Dir> {
+        match (self.stacks.get(&context_id).and_then(|stack| stack.last()), context_id) {
+            (Some(dir), _) => dir.as_ref(),
+            // an MSBuild context we haven't seen a project for, so we can't know its directory
+            (None, Some(_)) => None,
+            (None, None) => self.base_dir.as_ref(),
+        }
+    }
+    
+    /// Parse [`dir`], resolving it against the current directory of [`context_id`] if it's relative.
+    fn resolve_dir(&self, dir: &[u8], context_id: Option<u32>) -> Option<Dir> {
+        Dir::parse(dir, self.options.text.sep).or_else(|| Some(self.current_dir(context_id)?.join(dir)))
+    }
+    
+    fn follow(&mut self, marker: Marker<'_>, context_id: Option<u32>) {
+        let (dir, context_id, replace) = match marker {
+            Marker::Enter(dir) => (self.resolve_dir(dir, context_id), context_id, false),
+            Marker::Change(dir) => (self.resolve_dir(dir, context_id), context_id, true),
+            // the built project's own context, if it has one
+            Marker::Project { path, context_id: project_context_id } => {
+                let context_id = project_context_id.filter(|_| context_id.is_some()).or(context_id);
+                let dir = Dir::parse(path, self.options.text.sep).map(|project| project.join(b".."));
+                (dir, context_id, false)
+            }
+            Marker::Leave | Marker::DoneProject => {
+                self.stacks.entry(context_id).or_default().pop();
+                return;
+            }
+        };
+        let stack = self.stacks.entry(context_id).or_default();
+        if replace {
+            stack.pop();
+        }
+        stack.push(dir);
+    }
+    
+    /// Convert [`path`] with [`converter`] if it's a Windows path.
+    fn convert_path(&self, path: Vec<u8>, windows: bool) -> Option<Vec<u8>> {
+        match windows {
+            true => self.converter.convert_cow(&path).ok().map(Cow::into_owned),
+            false => Some(path),
+        }
+    }
+    
+    /// Convert the paths in [`line`], without its line ending,
+    /// after following any directory change in it.
+    pub fn convert_line<'a>(&mut self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let (context_id, rest) = split_context_id(line);
+        if let Some(marker) = Marker::parse(rest) {
+            self.follow(marker, context_id);
+        }
+        let offset = line.len() - rest.len();
+        let in_line = |range: Range<usize>| offset + range.start..offset + range.end;
+        let absolute = find_paths(rest, &self.options.text).into_iter().map(in_line).collect::<Vec<_>>();
+        let mut ranges = absolute.clone();
+        ranges.extend(find_relative_paths(rest).into_iter().map(in_line));
+        ranges.sort_by_key(|range| range.start);
+        let mut summary = TextSummary::default();
+        let converted = text::replace_ranges(line, ranges, |range| {
+            summary.found += 1;
+            let path = &line[range.clone()];
+            let path = match absolute.contains(&range) {
+                true => self.convert_path(self.options.text.normalize_seps(path).into_owned(), true),
+                false => self
+                    .current_dir(context_id)
+                    .map(|dir| dir.join(path))
+                    .and_then(|dir| self.convert_path(dir.path, dir.windows)),
+            };
+            if path.is_none() {
+                summary.failed += 1;
+            }
+            path.map(Cow::Owned)
+        });
+        self.summary.found += summary.found;
+        self.summary.failed += summary.failed;
+        converted
+    }
+    
+    /// Convert each line read from [`reader`], writing the lines to [`writer`],
+    /// like [`convert_text`](crate::convert::text::convert_text).
+    pub fn convert_log<R: BufRead, W: Write>(&mut self, reader: R, writer: W) -> io::Result<()> {
+        text::filter_lines(reader, writer, |line| self.convert_line(line))
+    }
+}
@@ -0,0 +1,53 @@
+use crate::util::enum_arg::EnumArg;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::str::FromStr;
+
+/// What to do with a final path that isn't ended by a separator,
+/// like the last line of a file without a trailing newline.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EofPolicy {
+    /// Convert it like any other path.
+    Convert,
+    /// Report it as an [`io::ErrorKind::UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) error.
+    Error,
+    /// Silently drop it.
+    Ignore,
+}
+
+impl EnumArg for EofPolicy {
+    fn variants() -> &'static [Self] {
+        use EofPolicy::*;
+        &[Convert, Error, Ignore]
+    }
+    
+    fn displays(&self) -> &'static [&'static str] {
+        use EofPolicy::*;
+        match self {
+            Convert => &["convert"],
+            Error => &["error"],
+            Ignore => &["ignore"],
+        }
+    }
+}
+
+impl Display for EofPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        EnumArg::fmt(self, f)
+    }
+}
+
+impl FromStr for EofPolicy {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EnumArg::from_str(s)
+    }
+}
+
+impl Default for EofPolicy {
+    fn default() -> Self {
+        use EofPolicy::*;
+        Convert
+    }
+}
@@ -1,10 +1,13 @@
+use memchr::{memchr, memchr2, memrchr, memrchr2};
+
 use crate::convert::{InputPathSeparator, OutputPathSeparator};
 use crate::util::enum_arg::EnumArg;
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 use std::fmt;
 use std::str::FromStr;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum LineSep {
     Null,
     LF,
@@ -70,6 +73,34 @@ impl InputPathSeparator for LineSep {
             CRLF => c == b'\r' || c == b'\n',
         }
     }
+    
+    fn is_line_end(&self, c: u8) -> bool {
+        use LineSep::*;
+        match self {
+            Null => c == 0,
+            LF | CRLF => c == b'\n',
+        }
+    }
+    
+    fn find(&self, paths: &[u8]) -> Option<Range<usize>> {
+        use LineSep::*;
+        let i = match self {
+            Null => memchr(0, paths),
+            LF => memchr(b'\n', paths),
+            CRLF => memchr2(b'\r', b'\n', paths),
+        }?;
+        Some(i..i + 1)
+    }
+    
+    fn rfind(&self, paths: &[u8]) -> Option<Range<usize>> {
+        use LineSep::*;
+        let i = match self {
+            Null => memrchr(0, paths),
+            LF => memrchr(b'\n', paths),
+            CRLF => memrchr2(b'\r', b'\n', paths),
+        }?;
+        Some(i..i + 1)
+    }
 }
 
 impl OutputPathSeparator for LineSep {
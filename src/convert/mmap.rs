@@ -0,0 +1,304 @@
+use std::fs::{File, Metadata};
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+use memmap2::{Advice, Mmap, MmapOptions, UncheckedAdvice};
+
+use crate::convert::{BulkConversion, ConversionIterator, Converter, InputPathSeparator, OutputPathSeparator, unterminated_path_error};
+use crate::convert::eof::EofPolicy;
+
+/// How large a regular file has to be before it's worth mapping instead of reading,
+/// so it doesn't have to fit in memory, when mapping is turned on, like with `--mmap`.
+/// It's off by default, since truncating a mapped file while it's being converted raises `SIGBUS`.
+/// See [`BufferSizeBlocks::mmap_min_len`](crate::convert::BufferSizeBlocks::mmap_min_len).
+pub const MMAP_MIN_LEN: u64 = 16 << 20;
+
+/// How much of a mapped file to convert at a time.
+pub const MMAP_CHUNK_LEN: usize = 4 << 20;
+
+/// Where [`ConversionIterator`] gets its input from.
+pub(crate) enum Source<R> {
+    Read(R),
+    Mmap(MappedFile),
+}
+
+/// A file mapped read-only, which works since conversion never modifies its input.
+pub(crate) struct MappedFile {
+    map: Mmap,
+    /// Kept so its offset, which it shares with the fd it was duplicated from,
+    /// can follow what's been converted, like it would if the file were read.
+    file: File,
+    /// How much to convert at a time, unless a path is longer than that.
+    chunk_len: usize,
+    /// Where the next chunk starts.
+    pos: usize,
+    /// Everything before this has been converted and its pages released.
+    released: usize,
+}
+
+impl MappedFile {
+    /// Map the rest of [`file`] from its current offset if it's a regular file of at least [`min_len`],
+    /// or else return [`None`] so that it's read instead, like for pipes and ttys.
+    pub(crate) fn new(mut file: File, metadata: &Metadata, min_len: u64) -> Option<Self> {
+        // there's nothing to map in an empty file
+        if !metadata.is_file() || metadata.len() < min_len.max(1) {
+            return None;
+        }
+        let pos = file.stream_position().ok()? as usize;
+        // Safety: like any mmap, if another process modifies the file while we're converting it,
+        // we'll see a mix of old and new data or get a SIGBUS if it's truncated,
+        // which is why mapping is opt-in and documented on `Converter::convert_fd`.
+        let map = unsafe { MmapOptions::new().map(&file) }.ok()?;
+        let _ = map.advise(Advice::Sequential);
+        Some(Self {
+            map,
+            file,
+            chunk_len: MMAP_CHUNK_LEN,
+            pos,
+            released: 0,
+        })
+    }
+    
+    /// The next chunk of at most [`len`] bytes, and whether it's the end of the file.
+    /// Any partial path at the end of it is left as the remainder when it's converted,
+    /// so that separators are always found from the start of a path.
+    fn next_chunk(&self, len: usize) -> Option<(&[u8], bool)> {
+        let map_len = self.map.len();
+        if self.pos >= map_len {
+            return None;
+        }
+        let end = self.pos.saturating_add(len).min(map_len);
+        Some((&self.map[self.pos..end], end == map_len))
+    }
+    
+    /// Mark the next [`n`] bytes as converted, releasing the pages they're in
+    /// and moving the file's offset past them.
+    fn consume(&mut self, n: usize) {
+        self.pos += n;
+        let _ = self.file.seek(SeekFrom::Start(self.pos as u64));
+        // Safety: the map is read-only and file-backed,
+        // so dropped pages are just reread from the file if they're used again,
+        // which only happens for the start of the next chunk sharing a page.
+        let _ = unsafe {
+            self.map.unchecked_advise_range(UncheckedAdvice::DontNeed, self.released, self.pos - self.released)
+        };
+        self.released = self.pos;
+    }
+}
+
+impl<'a, C, InputSep, OutputSep, R> ConversionIterator<'a, C, InputSep, OutputSep, R>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator,
+          R: Read {
+    /// [`Iterator::next`] for a [`MappedFile`],
+    /// which converts each chunk directly from the map instead of reading it into [`buf`].
+    pub(crate) fn next_mapped(&mut self) -> Option<io::Result<BulkConversion<C>>> {
+        let file = match &mut self.source {
+            Source::Mmap(file) => file,
+            Source::Read(_) => return None,
+        };
+        let mut chunk_len = file.chunk_len;
+        let (chunk, converted) = loop {
+            let (chunk, is_end) = match file.next_chunk(chunk_len) {
+                None => {
+                    self.done = true;
+                    return None;
+                }
+                Some(next) => next,
+            };
+            let converted = match (is_end, self.eof_policy) {
+                (true, EofPolicy::Convert) => self.converter.convert_end_from(chunk, self.separators, self.position),
+                _ => (self.convert_chunk)(self.converter, chunk, self.separators, self.position),
+            };
+            if converted.remainder_index == 0 && !is_end {
+                // a path longer than a whole chunk
+                chunk_len = chunk_len.saturating_mul(2);
+                continue;
+            }
+            break (chunk, converted);
+        };
+        if converted.remainder_index == 0 {
+            // only an unterminated path is left
+            self.done = true;
+            let result = match self.eof_policy {
+                EofPolicy::Error => Some(Err(unterminated_path_error(chunk, self.position))),
+                _ => None,
+            };
+            // it's been read, even if it's not converted
+            let len = chunk.len();
+            file.consume(len);
+            return result;
+        }
+        file.consume(converted.remainder_index);
+        self.position = converted.next;
+        Some(Ok(converted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    
+    use crate::convert::{BufferSizeBlocks, PathSeparators};
+    use crate::convert::line_sep::LineSep;
+    use crate::convert::win_to_wsl;
+    
+    use super::*;
+    
+    fn converter() -> win_to_wsl::Converter {
+        let options = win_to_wsl::Options {
+            convert_root_loop: false,
+            distro: Some("test".into()),
+            ..Default::default()
+        };
+        win_to_wsl::Converter::new(options).unwrap()
+    }
+    
+    const SEPS: PathSeparators<LineSep, LineSep> = PathSeparators {
+        input: LineSep::LF,
+        output: LineSep::LF,
+    };
+    
+    fn file(contents: &[u8]) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(contents).unwrap();
+        file.rewind().unwrap();
+        file
+    }
+    
+    /// Map any non-empty file.
+    fn mapped() -> BufferSizeBlocks {
+        BufferSizeBlocks {
+            mmap_min_len: 0,
+            ..Default::default()
+        }
+    }
+    
+    /// Convert [`input`] from a map converted [`chunk_len`] bytes at a time,
+    /// returning the output and error lines of each chunk, and the final error, if any.
+    fn convert(input: &[u8], chunk_len: usize, eof_policy: EofPolicy) -> (Vec<(String, Vec<usize>)>, Option<io::Error>) {
+        let converter = converter();
+        let file = file(input);
+        let mut converted = converter
+            .convert_fd(&file, &SEPS, mapped())
+            .unwrap()
+            .with_eof_policy(eof_policy);
+        match &mut converted.source {
+            Source::Mmap(file) => file.chunk_len = chunk_len,
+            Source::Read(_) => panic!("not mapped"),
+        }
+        let mut chunks = Vec::new();
+        for chunk in converted {
+            match chunk {
+                Ok(chunk) => chunks.push((
+                    chunk.paths.into_string().unwrap(),
+                    chunk.errors.iter().map(|e| e.position().line).collect(),
+                )),
+                Err(e) => return (chunks, Some(e)),
+            }
+        }
+        (chunks, None)
+    }
+    
+    fn output(chunks: &[(String, Vec<usize>)]) -> String {
+        chunks.iter().map(|(output, _)| output.as_str()).collect()
+    }
+    
+    #[test]
+    fn min_len() {
+        let converter = converter();
+        let file = file(b"C:\\a\n");
+        let converted = converter.convert_fd(&file, &SEPS, BufferSizeBlocks::default()).unwrap();
+        assert!(matches!(converted.source, Source::Read(_)));
+        let converted = converter.convert_fd(&file, &SEPS, mapped()).unwrap();
+        assert!(matches!(converted.source, Source::Mmap(_)));
+        // nothing to map
+        let file = self::file(b"");
+        let converted = converter.convert_fd(&file, &SEPS, mapped()).unwrap();
+        assert!(matches!(converted.source, Source::Read(_)));
+    }
+    
+    #[test]
+    fn chunks() {
+        let input = b"C:\\a\nbad\nD:\\bb\nC:\\a\\very\\long\\path\\longer\\than\\a\\chunk\nE:\\\n";
+        let (chunks, error) = convert(input, 8, EofPolicy::Error);
+        assert!(error.is_none());
+        // each chunk only has whole paths, and a path longer than a chunk gets a bigger one
+        assert_eq!(chunks, vec![
+            ("/mnt/c/a\n".to_owned(), vec![]),
+            ("\n".to_owned(), vec![2]),
+            ("/mnt/d/bb\n".to_owned(), vec![]),
+            ("/mnt/c/a/very/long/path/longer/than/a/chunk\n/mnt/e/\n".to_owned(), vec![]),
+        ]);
+        // one chunk for the whole thing
+        let (chunks, _) = convert(input, 1 << 10, EofPolicy::Error);
+        assert_eq!(chunks.len(), 1);
+    }
+    
+    #[test]
+    fn unterminated_tail() {
+        let input = b"C:\\a\nbad\nD:\\b";
+        let (chunks, error) = convert(input, 6, EofPolicy::Convert);
+        assert!(error.is_none());
+        assert_eq!(output(&chunks), "/mnt/c/a\n\n/mnt/d/b\n");
+        
+        let (chunks, error) = convert(input, 6, EofPolicy::Ignore);
+        assert!(error.is_none());
+        assert_eq!(output(&chunks), "/mnt/c/a\n\n");
+        
+        let (chunks, error) = convert(input, 6, EofPolicy::Error);
+        assert_eq!(error.unwrap().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(output(&chunks), "/mnt/c/a\n\n");
+        
+        // just an unterminated path, in one chunk
+        let (chunks, error) = convert(b"D:\\b", 64, EofPolicy::Convert);
+        assert!(error.is_none());
+        assert_eq!(output(&chunks), "/mnt/d/b\n");
+    }
+    
+    #[test]
+    fn offset() {
+        let converter = converter();
+        let file = file(b"C:\\a\nD:\\b\nE:\\c");
+        // only the rest of the file from its offset is converted
+        (&file).seek(SeekFrom::Start(5)).unwrap();
+        let mut converted = converter.convert_fd(&file, &SEPS, mapped()).unwrap();
+        match &mut converted.source {
+            Source::Mmap(file) => file.chunk_len = 5,
+            Source::Read(_) => panic!("not mapped"),
+        }
+        let chunk = converted.next().unwrap().unwrap();
+        assert_eq!(chunk.paths.into_string().unwrap(), "/mnt/d/b\n");
+        // the offset follows what's been converted
+        let mut rest = Vec::new();
+        (&file).read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"E:\\c");
+        (&file).seek(SeekFrom::Start(10)).unwrap();
+        assert_eq!(converted.map(|chunk| chunk.unwrap().paths.into_string().unwrap()).collect::<String>(), "/mnt/e/c\n");
+        // and it's at the end once it's done
+        assert_eq!((&file).stream_position().unwrap(), 14);
+        assert_eq!((&file).read(&mut [0]).unwrap(), 0);
+    }
+    
+    #[test]
+    fn release() {
+        let mut input = Vec::new();
+        for i in 0..1000 {
+            input.extend_from_slice(format!("C:\\{}\n", i).as_bytes());
+        }
+        let file = file(&input);
+        let mut mapped = MappedFile::new(file.try_clone().unwrap(), &file.metadata().unwrap(), 0).unwrap();
+        let mut consumed = 0;
+        while let Some((chunk, _)) = mapped.next_chunk(100) {
+            let n = chunk.len().min(37);
+            mapped.consume(n);
+            consumed += n;
+            // everything converted so far is released
+            assert_eq!((mapped.pos, mapped.released), (consumed, consumed));
+        }
+        assert_eq!(consumed, input.len());
+        // released pages are just reread from the file
+        assert_eq!(&mapped.map[..], input.as_slice());
+    }
+}
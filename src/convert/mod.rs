@@ -2,29 +2,96 @@ use std::{fmt, io, iter};
 use std::cmp::{max, min};
 use std::ffi::{OsStr, OsString};
 use std::fmt::{Debug, Display, Formatter};
-use std::fs::File;
+use std::fs::{File, Metadata};
 use std::io::Read;
-use std::os::unix::ffi::OsStringExt;
+use std::ops::Range;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::os::unix::fs::MetadataExt;
-use std::path::Path;
+use std::os::unix::io::AsFd;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
 
 use thiserror::Error;
 
+use crate::convert::eof::EofPolicy;
+use crate::convert::mmap::{MappedFile, Source};
+use crate::convert::sink::ConversionSink;
+
 pub mod win_to_wsl;
 pub mod wsl_to_win;
-mod wsl;
+pub mod wsl;
 mod windows_file_name_char;
+mod scan;
 pub mod path_sep;
 pub mod line_sep;
+pub mod separator;
+pub mod path_list;
+pub mod wslenv;
+pub mod text;
+pub mod build_log;
+pub mod sink;
+pub mod eof;
+pub mod stream;
+pub mod mmap;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+#[cfg(feature = "async")]
+pub mod async_stream;
 
 pub trait InputPathSeparator {
+    /// Whether [`c`] is a separator, or part of one for a multi-byte separator.
     fn matches(&self, c: u8) -> bool;
+    
+    /// Whether [`c`] ends a line, for counting line numbers.
+    /// This is usually the same as [`matches`](InputPathSeparator::matches),
+    /// but not when a multi-byte separator like `\r\n` is matched byte-by-byte.
+    fn is_line_end(&self, c: u8) -> bool {
+        self.matches(c)
+    }
+    
+    /// How many lines the separator [`sep`] ends,
+    /// i.e., how many of its bytes [`is_line_end`](InputPathSeparator::is_line_end).
+    /// Override it when a separator ends a line as a whole, not byte-by-byte.
+    fn lines_ended(&self, sep: &[u8]) -> usize {
+        sep.iter().filter(|c| self.is_line_end(**c)).count()
+    }
+    
+    /// The span of the first separator in [`paths`].
+    /// This checks each byte with [`matches`](InputPathSeparator::matches),
+    /// so override it when there's a faster search, like [`memchr`],
+    /// or when a separator is more than one byte.
+    fn find(&self, paths: &[u8]) -> Option<Range<usize>> {
+        let i = paths.iter().position(|c| self.matches(*c))?;
+        Some(i..i + 1)
+    }
+    
+    /// The span of the last separator in [`paths`], like [`find`](InputPathSeparator::find).
+    fn rfind(&self, paths: &[u8]) -> Option<Range<usize>> {
+        let i = paths.iter().rposition(|c| self.matches(*c))?;
+        Some(i..i + 1)
+    }
 }
 
 impl<T: InputPathSeparator + ?Sized> InputPathSeparator for &T {
     fn matches(&self, c: u8) -> bool {
         T::matches(self, c)
     }
+    
+    fn is_line_end(&self, c: u8) -> bool {
+        T::is_line_end(self, c)
+    }
+    
+    fn lines_ended(&self, sep: &[u8]) -> usize {
+        T::lines_ended(self, sep)
+    }
+    
+    fn find(&self, paths: &[u8]) -> Option<Range<usize>> {
+        T::find(self, paths)
+    }
+    
+    fn rfind(&self, paths: &[u8]) -> Option<Range<usize>> {
+        T::rfind(self, paths)
+    }
 }
 
 pub trait OutputPathSeparator {
@@ -54,18 +121,77 @@ pub struct PathSeparators<InputSep, OutputSep>
 //     source: C::Error,
 // }
 
+/// Where a path is in the whole input,
+/// which may be converted in multiple chunks.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Position {
+    /// The number of paths before this one.
+    pub index: usize,
+    /// The 1-based line number.
+    pub line: usize,
+    /// The byte offset.
+    pub offset: usize,
+}
+
+impl Position {
+    /// Where [`relative`], a position within a chunk starting here, is in the whole input.
+    pub fn then(&self, relative: Position) -> Position {
+        Position {
+            index: self.index + relative.index,
+            line: self.line + relative.line - 1,
+            offset: self.offset + relative.offset,
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            line: 1,
+            offset: 0,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub struct OneConvertError<E: std::error::Error + 'static> {
-    index: usize,
+    position: Position,
     path: OsString,
     source: E,
 }
 
+impl<E: std::error::Error + 'static> OneConvertError<E> {
+    /// The index of the path among all the paths converted together.
+    pub fn index(&self) -> usize {
+        self.position.index
+    }
+    
+    /// Where the path is in the whole input.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+    
+    /// The path that failed to convert.
+    pub fn path(&self) -> &OsStr {
+        &self.path
+    }
+    
+    pub fn error(&self) -> &E {
+        &self.source
+    }
+    
+    pub fn into_error(self) -> E {
+        self.source
+    }
+}
+
 impl<E: std::error::Error + 'static> Display for OneConvertError<E> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "error converting {:#?} at index {}: {:#?}",
+        write!(f, "error converting {:?} at line {}, byte {}: {}",
                self.path,
-               self.index,
+               self.position.line,
+               self.position.offset,
                self.source,
         )?;
         Ok(())
@@ -75,7 +201,12 @@ impl<E: std::error::Error + 'static> Display for OneConvertError<E> {
 pub struct BulkConversion<C: Converter> {
     pub paths: OsString,
     pub remainder_index: usize,
+    /// The number of paths attempted, including those in [`errors`](BulkConversion::errors).
+    pub num_paths: usize,
     pub errors: Vec<OneConvertError<C::Error>>,
+    /// Where the remainder starts in the whole input,
+    /// i.e., where the next chunk should continue from.
+    pub next: Position,
 }
 
 // I would #[derive(Default)] this,
@@ -85,32 +216,84 @@ impl<C: Converter> Default for BulkConversion<C> {
         Self {
             paths: OsString::default(),
             remainder_index: usize::default(),
+            num_paths: usize::default(),
             errors: Vec::default(),
+            next: Position::default(),
         }
     }
 }
 
+/// Structured details about why a single path failed to convert,
+/// beyond its message.
+pub trait ConvertErrorInfo {
+    /// A short, stable name for the kind of error, like `IllegalFileNameChar`.
+    fn kind(&self) -> &'static str;
+    
+    /// The byte index into the path where the error is, if there's a specific one.
+    fn index(&self) -> Option<usize> {
+        None
+    }
+}
+
 pub trait Converter where Self: Sized {
     type Options;
     type OptionsError: std::error::Error + Send + Sync + 'static;
-    type Error: std::error::Error + Send + Sync + 'static;
+    type Error: std::error::Error + ConvertErrorInfo + Send + Sync + 'static;
     
     fn new(options: Self::Options) -> Result<Self, Self::OptionsError>;
     
     /// Lower-level version of [`convert`].
-    /// Takes a [`&mut [u8]`] directly to avoid any copies and allow slices.
+    /// Takes a [`&[u8]`] directly to avoid any copies and allow slices.
     /// Outputs into a [`Vec<u8>`] to avoid extra copies on return.
     /// This is useful for minimizing overhead on bulk conversions.
-    fn convert_into_buf(&self, path: &mut [u8], buf: &mut Vec<u8>) -> Result<(), Self::Error>;
+    /// [`path`] is never modified, so it can be borrowed or read-only, like an mmap.
+    fn convert_slice_into_buf(&self, path: &[u8], buf: &mut Vec<u8>) -> Result<(), Self::Error>;
+    
+    /// Same as [`convert_slice_into_buf`], which it predates.
+    /// [`path`] isn't actually modified.
+    fn convert_into_buf(&self, path: &mut [u8], buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        self.convert_slice_into_buf(path, buf)
+    }
+    
+    /// Whether converting [`path`] would return it as is,
+    /// so that it can be borrowed instead of converted into a new buffer.
+    /// This is only a fast path for [`convert_cow`], so `false` is always correct.
+    fn is_unchanged(&self, _path: &[u8]) -> bool {
+        false
+    }
+    
+    /// Convert [`path`], borrowing it if it doesn't need to change.
+    fn convert_cow<'p>(&self, path: &'p [u8]) -> Result<Cow<'p, [u8]>, Self::Error> {
+        if self.is_unchanged(path) {
+            return Ok(Cow::Borrowed(path));
+        }
+        let mut buf = Vec::new();
+        self.convert_slice_into_buf(path, &mut buf)?;
+        Ok(Cow::Owned(buf))
+    }
+    
+    /// Like [`convert_cow`], but for an [`OsStr`].
+    fn convert_os_str<'p>(&self, path: &'p OsStr) -> Result<Cow<'p, OsStr>, Self::Error> {
+        let path = match self.convert_cow(path.as_bytes())? {
+            Cow::Borrowed(path) => Cow::Borrowed(OsStr::from_bytes(path)),
+            Cow::Owned(path) => Cow::Owned(OsString::from_vec(path)),
+        };
+        Ok(path)
+    }
+    
+    /// Like [`convert_cow`], but for a [`Path`].
+    fn convert_path<'p>(&self, path: &'p Path) -> Result<Cow<'p, Path>, Self::Error> {
+        let path = match self.convert_os_str(path.as_os_str())? {
+            Cow::Borrowed(path) => Cow::Borrowed(Path::new(path)),
+            Cow::Owned(path) => Cow::Owned(PathBuf::from(path)),
+        };
+        Ok(path)
+    }
     
     /// Convert an absolute path.
     fn convert<S: AsRef<OsStr> + ?Sized>(&self, path: &S) -> Result<OsString, Self::Error> {
-        let mut path = path
-            .as_ref()
-            .to_os_string()
-            .into_vec();
         let mut buf = Vec::new();
-        self.convert_into_buf(path.as_mut_slice(), &mut buf)?;
+        self.convert_slice_into_buf(path.as_ref().as_bytes(), &mut buf)?;
         Ok(buf)
             .map(OsString::from_vec)
     }
@@ -119,62 +302,70 @@ pub trait Converter where Self: Sized {
     /// If the end of [`paths`] does not end in an [`input_sep`],
     /// then that part is returned in the remainder field.
     /// The output paths are separated by [`output_sep`], including a trailing separator.
-    fn convert_all<'a, InputSep, OutputSep>(
-        &self, paths: &'a mut [u8],
+    fn convert_all<InputSep, OutputSep>(
+        &self, paths: &[u8],
         seps: &PathSeparators<InputSep, OutputSep>,
     ) -> BulkConversion<Self>
         where InputSep: InputPathSeparator,
               OutputSep: OutputPathSeparator {
-        let empty = BulkConversion::default();
-        
-        // if paths is empty, do nothing
-        let last_byte = match paths.last() {
-            // empty slice
-            None => return empty,
-            Some(c) => *c,
-        };
-        
-        // if paths doesn't end in an input_sep,
-        // truncate paths from the end until it does end in an input_sep
-        // also store the remainder to return
-        let remainder_index = if !seps.input.matches(last_byte) {
-            let i = match paths.iter()
-                .rposition(|c| seps.input.matches(*c)) {
-                None => return empty,
-                Some(i) => i,
-            };
-            i + 1
-        } else {
-            paths.len()
+        self.convert_all_from(paths, seps, Position::default())
+    }
+    
+    /// Like [`convert_all`], but for a chunk of a larger input starting at [`start`],
+    /// so that errors are reported with their positions in the whole input.
+    fn convert_all_from<InputSep, OutputSep>(
+        &self, paths: &[u8],
+        seps: &PathSeparators<InputSep, OutputSep>,
+        start: Position,
+    ) -> BulkConversion<Self>
+        where InputSep: InputPathSeparator,
+              OutputSep: OutputPathSeparator {
+        convert_chunk(self, paths, seps, start, false)
+    }
+    
+    /// Like [`convert_all_from`], but for the last chunk of the input,
+    /// so a final path that isn't ended by an [`input_sep`] is converted, too,
+    /// instead of being left in the remainder.
+    fn convert_end_from<InputSep, OutputSep>(
+        &self, paths: &[u8],
+        seps: &PathSeparators<InputSep, OutputSep>,
+        start: Position,
+    ) -> BulkConversion<Self>
+        where InputSep: InputPathSeparator,
+              OutputSep: OutputPathSeparator {
+        convert_chunk(self, paths, seps, start, true)
+    }
+    
+    /// Convert each of [`paths`], interpreted as multiple paths ended by [`input_sep`],
+    /// passing each one's span in [`paths`] and its result to [`sink`], in order.
+    /// Unlike [`convert_all`], which output belongs to which input is kept.
+    /// Returns the index of the unterminated remainder at the end of [`paths`], like [`convert_all`].
+    fn convert_each<InputSep, Sink>(&self, paths: &[u8], input_sep: InputSep, sink: &mut Sink) -> usize
+        where InputSep: InputPathSeparator,
+              Sink: ConversionSink<Self::Error> {
+        let remainder_index = match remainder_index(paths, &input_sep) {
+            None => return 0,
+            Some(i) => i,
         };
-        let paths = &mut paths[..remainder_index];
-        
-        // at most one more allocation
-        let mut buf = Vec::with_capacity(paths.len());
-        let mut errors = Vec::new();
-        paths
-            .split_mut(|c| seps.input.matches(*c))
-            .filter(|it| !it.is_empty())
-            .enumerate()
-            .for_each(|(index, path)| {
-                let result = self.convert_into_buf(path, &mut buf);
-                if let Err(source) = result {
-                    errors.push(OneConvertError {
-                        index,
-                        path: OsString::from_vec(path.into()),
-                        source,
-                    });
-                }
-                seps.output.write_to_buf(&mut buf);
-            });
-        let paths = OsString::from_vec(buf);
-        BulkConversion {
-            paths,
-            remainder_index,
-            errors,
+        let paths = &paths[..remainder_index];
+        let mut buf = Vec::new();
+        let mut start = 0;
+        while let Some(sep) = input_sep.find(&paths[start..]) {
+            let span = start..start + sep.start;
+            start += sep.end;
+            if span.is_empty() {
+                continue;
+            }
+            buf.clear();
+            let output = self
+                .convert_slice_into_buf(&paths[span.clone()], &mut buf)
+                .map(|_| buf.as_slice());
+            sink.accept(span, output);
         }
+        remainder_index
     }
     
+    /// Convert the paths in the file at [`path`], like [`convert_fd`](Converter::convert_fd).
     fn convert_file<'a, P, InputSep, OutputSep>(
         &'a self,
         path: P,
@@ -184,10 +375,174 @@ pub trait Converter where Self: Sized {
         where P: AsRef<Path>,
               InputSep: InputPathSeparator,
               OutputSep: OutputPathSeparator {
-        ConversionIterator::new(self, path.as_ref(), seps, buffer_size_blocks)
+        let file = File::open(path)?;
+        self.convert_fd(file, seps, buffer_size_blocks)
+    }
+    
+    /// Like [`convert_file`], but for an already open file descriptor, like stdin or a pipe.
+    /// The buffer size is still determined from its metadata.
+    ///
+    /// A regular file of at least [`BufferSizeBlocks::mmap_min_len`] is mapped instead of read.
+    /// If it's truncated while it's being converted, like by log rotation or an editor saving it,
+    /// the process gets a `SIGBUS`, so only lower it from [`u64::MAX`] for files that won't be.
+    /// Either way, [`reader`]'s offset is left after what's been read or converted so far,
+    /// which is the end of the file once the iterator's done.
+    fn convert_fd<'a, R, InputSep, OutputSep>(
+        &'a self,
+        reader: R,
+        seps: &'a PathSeparators<InputSep, OutputSep>,
+        buffer_size_blocks: BufferSizeBlocks,
+    ) -> Result<ConversionIterator<'a, Self, InputSep, OutputSep, R>, ConvertFileError>
+        where R: Read + AsFd,
+              InputSep: InputPathSeparator,
+              OutputSep: OutputPathSeparator {
+        // a duplicate fd shares the open file, including its offset,
+        // so it's as good as the original for its metadata and mapping it
+        let file = File::from(reader.as_fd().try_clone_to_owned()?);
+        let metadata = file.metadata()?;
+        if metadata.is_dir() {
+            return Err(ConvertFileError::IsADirectory);
+        }
+        let mapped = MappedFile::new(file, &metadata, buffer_size_blocks.mmap_min_len);
+        let buf_len = buffer_size_blocks.buf_len(&metadata);
+        let mut converted = self.convert_reader(reader, seps, buf_len);
+        if let Some(mapped) = mapped {
+            converted.source = Source::Mmap(mapped);
+        }
+        Ok(converted)
+    }
+    
+    /// Convert everything read from [`reader`], reading [`buf_len`] bytes at a time.
+    /// Use [`BufferSizeBlocks::DEFAULT_BLOCK_SIZE`] blocks
+    /// if there's no better size for [`reader`].
+    fn convert_reader<'a, R, InputSep, OutputSep>(
+        &'a self,
+        reader: R,
+        seps: &'a PathSeparators<InputSep, OutputSep>,
+        buf_len: usize,
+    ) -> ConversionIterator<'a, Self, InputSep, OutputSep, R>
+        where R: Read,
+              InputSep: InputPathSeparator,
+              OutputSep: OutputPathSeparator {
+        ConversionIterator::new(self, reader, seps, buf_len)
     }
 }
 
+/// Implements [`Converter::convert_all_from`] and [`Converter::convert_end_from`].
+/// If [`is_end`], the end of [`paths`] also ends a path.
+fn convert_chunk<C, InputSep, OutputSep>(
+    converter: &C,
+    paths: &[u8],
+    seps: &PathSeparators<InputSep, OutputSep>,
+    start: Position,
+    is_end: bool,
+) -> BulkConversion<C>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator {
+    let remainder_index = match is_end {
+        true => paths.len(),
+        false => remainder_index(paths, &seps.input).unwrap_or(0),
+    };
+    if remainder_index == 0 {
+        return BulkConversion {
+            next: start,
+            ..Default::default()
+        };
+    }
+    let paths = &paths[..remainder_index];
+    
+    // at most one more allocation
+    let mut buf = Vec::with_capacity(paths.len());
+    let mut errors = Vec::new();
+    let mut num_paths = 0;
+    let mut position = start;
+    let mut path_start = 0;
+    loop {
+        // an unterminated path at the end is ended by the end, too
+        let sep = seps.input
+            .find(&paths[path_start..])
+            .map(|sep| path_start + sep.start..path_start + sep.end);
+        let path_end = sep.as_ref().map_or(paths.len(), |sep| sep.start);
+        let path = &paths[path_start..path_end];
+        if !path.is_empty() {
+            num_paths += 1;
+            let path_output_start = buf.len();
+            let result = converter.convert_slice_into_buf(path, &mut buf);
+            if let Err(source) = result {
+                // a failed path is output as an empty path, not a partially converted one
+                buf.truncate(path_output_start);
+                errors.push(OneConvertError {
+                    position: Position {
+                        offset: start.offset + path_start,
+                        ..position
+                    },
+                    path: OsString::from_vec(path.into()),
+                    source,
+                });
+            }
+            seps.output.write_to_buf(&mut buf);
+            position.index += 1;
+        }
+        let sep = match sep {
+            None => break,
+            Some(sep) => sep,
+        };
+        position.line += seps.input.lines_ended(&paths[sep.clone()]);
+        path_start = sep.end;
+    }
+    position.offset = start.offset + remainder_index;
+    let paths = OsString::from_vec(buf);
+    BulkConversion {
+        paths,
+        remainder_index,
+        num_paths,
+        errors,
+        next: position,
+    }
+}
+
+/// The error for [`EofPolicy::Error`] when the input ends with the unterminated [`path`].
+fn unterminated_path_error(path: &[u8], position: Position) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!(
+            "unterminated path {:?} at line {}, byte {}",
+            OsStr::from_bytes(path),
+            position.line,
+            position.offset,
+        ),
+    )
+}
+
+/// The length of the part of [`paths`] that ends in an [`input_sep`],
+/// i.e., the index of the unterminated remainder,
+/// or [`None`] if there's no complete path.
+fn remainder_index<InputSep: InputPathSeparator>(paths: &[u8], input_sep: &InputSep) -> Option<usize> {
+    // if paths doesn't end in an input_sep,
+    // truncate paths from the end until it does end in an input_sep
+    let last = input_sep.rfind(paths)?;
+    if last.len() == 1 {
+        return Some(last.end);
+    }
+    // a multi-byte separator can overlap itself, like `::` in `:::`,
+    // so the last one has to be found from the start, like when converting
+    Some(sep_boundary(paths, input_sep, paths.len()))
+}
+
+/// The end of the first separator in [`paths`] found from the start that ends at or after [`min`],
+/// or the end of the last one if there's none.
+pub(crate) fn sep_boundary<InputSep: InputPathSeparator>(paths: &[u8], input_sep: &InputSep, min: usize) -> usize {
+    let mut end = 0;
+    while let Some(sep) = input_sep.find(&paths[end..]) {
+        end += sep.end;
+        if end >= min {
+            break;
+        }
+    }
+    end
+}
+
 #[derive(Error, Debug)]
 pub enum ConvertFileError {
     #[error(transparent)]
@@ -196,9 +551,32 @@ pub enum ConvertFileError {
     IsADirectory,
 }
 
+#[derive(Copy, Clone, Debug)]
 pub struct BufferSizeBlocks {
     pub min: u64,
     pub max: u64,
+    /// Regular files at least this long are mapped instead of read,
+    /// or never with [`u64::MAX`], which is the default.
+    /// [`MMAP_MIN_LEN`](crate::convert::mmap::MMAP_MIN_LEN) is a good length if they won't be truncated while they're converted.
+    pub mmap_min_len: u64,
+}
+
+impl BufferSizeBlocks {
+    /// A typical block size, for readers without any metadata.
+    pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+    
+    /// How many bytes to read at a time from a file with this [`metadata`].
+    /// Regular files are read all at once if they fit in [`max`](BufferSizeBlocks::max) blocks,
+    /// while anything else, like a pipe, is read [`min`](BufferSizeBlocks::min) blocks at a time.
+    pub fn buf_len(&self, metadata: &Metadata) -> usize {
+        let file_type = metadata.file_type();
+        let block_size = metadata.blksize();
+        (if file_type.is_file() || file_type.is_symlink() {
+            min(metadata.len(), self.max.saturating_mul(block_size))
+        } else {
+            max(1, self.min) * block_size
+        }) as usize
+    }
 }
 
 impl Default for BufferSizeBlocks {
@@ -206,54 +584,89 @@ impl Default for BufferSizeBlocks {
         Self {
             min: 16,
             max: u64::MAX,
+            // mapping is opt-in, since a mapped file being truncated raises SIGBUS
+            mmap_min_len: u64::MAX,
         }
     }
 }
 
-pub struct ConversionIterator<'a, C, InputSep, OutputSep>
+/// How [`ConversionIterator`] converts each chunk,
+/// normally [`Converter::convert_all_from`].
+pub type ConvertChunkFn<C, InputSep, OutputSep> = fn(
+    &C, &[u8], &PathSeparators<InputSep, OutputSep>, Position,
+) -> BulkConversion<C>;
+
+pub struct ConversionIterator<'a, C, InputSep, OutputSep, R = File>
     where C: Converter,
           InputSep: InputPathSeparator,
-          OutputSep: OutputPathSeparator {
+          OutputSep: OutputPathSeparator,
+          R: Read {
     converter: &'a C,
     separators: &'a PathSeparators<InputSep, OutputSep>,
-    file: File,
-    file_buf_len: usize,
+    source: Source<R>,
+    read_len: usize,
     buf: Vec<u8>,
+    /// Where the start of [`buf`] is in the whole input.
+    position: Position,
+    eof_policy: EofPolicy,
+    done: bool,
+    convert_chunk: ConvertChunkFn<C, InputSep, OutputSep>,
 }
 
-impl<'a, C, InputSep, OutputSep> ConversionIterator<'a, C, InputSep, OutputSep>
+impl<'a, C, InputSep, OutputSep, R> ConversionIterator<'a, C, InputSep, OutputSep, R>
     where C: Converter,
           InputSep: InputPathSeparator,
-          OutputSep: OutputPathSeparator {
+          OutputSep: OutputPathSeparator,
+          R: Read {
     fn new(converter: &'a C,
-           path: &Path,
+           reader: R,
            separators: &'a PathSeparators<InputSep, OutputSep>,
-           buffer_size_blocks: BufferSizeBlocks,
-    ) -> Result<Self, ConvertFileError> {
-        let file = File::open(path)?;
-        let metadata = file.metadata()?;
-        let file_type = metadata.file_type();
-        if file_type.is_dir() {
-            return Err(ConvertFileError::IsADirectory);
-        }
-        let block_size = metadata.blksize();
-        let file_buf_len = if file_type.is_file() || file_type.is_symlink() {
-            min(metadata.len(), buffer_size_blocks.max.saturating_mul(block_size))
-        } else {
-            max(1, buffer_size_blocks.min) * block_size
-        } as usize;
-        let this = Self {
+           read_len: usize,
+    ) -> Self {
+        Self {
             converter,
             separators,
-            file,
-            file_buf_len,
+            source: Source::Read(reader),
+            read_len,
             buf: Vec::new(),
-        };
-        Ok(this)
+            position: Position::default(),
+            eof_policy: EofPolicy::default(),
+            done: false,
+            convert_chunk: C::convert_all_from,
+        }
     }
     
     pub fn buf_len(&self) -> usize {
-        self.file_buf_len
+        self.read_len
+    }
+    
+    /// Set what to do with a final path that isn't ended by a separator.
+    pub fn with_eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.eof_policy = eof_policy;
+        self
+    }
+    
+    /// Handle whatever's left in [`buf`] once the reader is at EOF.
+    fn end(&mut self) -> Option<io::Result<BulkConversion<C>>> {
+        self.done = true;
+        if self.buf.is_empty() {
+            return None;
+        }
+        let end = match self.eof_policy {
+            EofPolicy::Convert => {
+                let paths = self.buf.as_slice();
+                let converted = self.converter.convert_end_from(paths, self.separators, self.position);
+                self.position = converted.next;
+                Ok(converted)
+            }
+            EofPolicy::Error => Err(unterminated_path_error(&self.buf, self.position)),
+            EofPolicy::Ignore => {
+                self.buf.clear();
+                return None;
+            }
+        };
+        self.buf.clear();
+        Some(end)
     }
     
     // fn read(&mut self) -> io::Result<&mut [u8]> {
@@ -261,28 +674,30 @@ impl<'a, C, InputSep, OutputSep> ConversionIterator<'a, C, InputSep, OutputSep>
     // }
 }
 
-impl<'a, C, InputSep, OutputSep> Iterator for ConversionIterator<'a, C, InputSep, OutputSep>
+impl<'a, C, InputSep, OutputSep, R> Iterator for ConversionIterator<'a, C, InputSep, OutputSep, R>
     where C: Converter,
           InputSep: InputPathSeparator,
-          OutputSep: OutputPathSeparator {
+          OutputSep: OutputPathSeparator,
+          R: Read {
     type Item = io::Result<BulkConversion<C>>;
     
     fn next(&mut self) -> Option<Self::Item> {
-        let start = self.buf.len();
-        let len = self.file_buf_len;
-        self.buf.reserve(len);
-        unsafe {
-            // I could zero out this uninitialized memory instead,
-            // but that'd just be a waste
-            // since the kernel will fill it when I read the file
-            // I just need to make sure I don't read beyond
-            self.buf.set_len(start + len);
+        if self.done {
+            return None;
         }
+        let reader = match &mut self.source {
+            Source::Read(reader) => reader,
+            Source::Mmap(_) => return self.next_mapped(),
+        };
+        let start = self.buf.len();
+        let len = self.read_len;
+        // zero-fill rather than hand uninitialized memory to an arbitrary reader
+        self.buf.resize(start + len, 0);
         let read_buf = &mut self.buf[start..start + len];
-        match self.file.read(read_buf) {
-            Err(e) => return Some(Err({
+        match reader.read(read_buf) {
+            Err(e) => Some(Err({
                 // assume no bytes were read
-                self.buf.truncate(len);
+                self.buf.truncate(start);
                 e
             })),
             Ok(bytes_read) => {
@@ -290,10 +705,11 @@ impl<'a, C, InputSep, OutputSep> Iterator for ConversionIterator<'a, C, InputSep
                 // don't return any uninitialized memory
                 self.buf.truncate(start + bytes_read);
                 if bytes_read == 0 {
-                    return None;
+                    return self.end();
                 }
-                let paths = self.buf.as_mut_slice();
-                let mut converted = self.converter.convert_all(paths, self.separators);
+                let paths = self.buf.as_slice();
+                let mut converted = (self.convert_chunk)(self.converter, paths, self.separators, self.position);
+                self.position = converted.next;
                 // remove everything except remainder
                 self.buf.splice(..converted.remainder_index, iter::empty::<u8>());
                 converted.remainder_index = 0;
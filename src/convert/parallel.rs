@@ -0,0 +1,146 @@
+use std::ffi::OsString;
+use std::io::Read;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+use rayon::prelude::*;
+
+use crate::convert::{BulkConversion, ConversionIterator, Converter, InputPathSeparator, OutputPathSeparator, PathSeparators, Position, remainder_index, sep_boundary};
+
+/// Don't bother splitting chunks smaller than this,
+/// since converting them is faster than handing them to another thread.
+const MIN_CHUNK_LEN: usize = 16 * 1024;
+
+/// Split [`paths`] into about [`n`] chunks, each ending in an [`input_sep`].
+fn split_chunks<'a, InputSep: InputPathSeparator>(
+    mut paths: &'a [u8],
+    input_sep: &InputSep,
+    n: usize,
+) -> Vec<&'a [u8]> {
+    let chunk_len = (paths.len() / n.max(1)).max(MIN_CHUNK_LEN);
+    let mut chunks = Vec::with_capacity(n);
+    while paths.len() > chunk_len {
+        // end the chunk at the first separator at or after chunk_len
+        let end = match input_sep.find(&paths[chunk_len..]) {
+            None => break,
+            Some(sep) if sep.len() == 1 => chunk_len + sep.end,
+            // a multi-byte separator can overlap itself, so it has to be found from the start
+            Some(_) => sep_boundary(paths, input_sep, chunk_len + 1),
+        };
+        let (chunk, rest) = paths.split_at(end);
+        chunks.push(chunk);
+        paths = rest;
+    }
+    if !paths.is_empty() {
+        chunks.push(paths);
+    }
+    chunks
+}
+
+/// Like [`Converter::convert_all_from`], but splitting [`paths`] on separator boundaries
+/// and converting the chunks in parallel on the current rayon thread pool.
+/// The output paths and errors are still in the original order.
+pub fn convert_all_from<C, InputSep, OutputSep>(
+    converter: &C,
+    paths: &[u8],
+    seps: &PathSeparators<InputSep, OutputSep>,
+    start: Position,
+) -> BulkConversion<C>
+    where C: Converter + Sync,
+          InputSep: InputPathSeparator + Sync,
+          OutputSep: OutputPathSeparator + Sync {
+    let remainder_index = match remainder_index(paths, &seps.input) {
+        None => return BulkConversion {
+            next: start,
+            ..Default::default()
+        },
+        Some(i) => i,
+    };
+    let chunks = split_chunks(&paths[..remainder_index], &seps.input, rayon::current_num_threads() * 4);
+    if chunks.len() <= 1 {
+        return converter.convert_all_from(paths, seps, start);
+    }
+    // positions are relative to each chunk until we know where each chunk starts
+    let converted = chunks
+        .into_par_iter()
+        .map(|chunk| converter.convert_all_from(chunk, seps, Position::default()))
+        .collect::<Vec<_>>();
+    
+    let mut buf = Vec::with_capacity(converted.iter().map(|it| it.paths.len()).sum());
+    let mut num_paths = 0;
+    let mut errors = Vec::new();
+    let mut next = start;
+    for chunk in converted {
+        buf.extend_from_slice(chunk.paths.as_bytes());
+        num_paths += chunk.num_paths;
+        errors.extend(chunk.errors.into_iter().map(|mut error| {
+            error.position = next.then(error.position);
+            error
+        }));
+        next = next.then(chunk.next);
+    }
+    BulkConversion {
+        paths: OsString::from_vec(buf),
+        remainder_index,
+        num_paths,
+        errors,
+        next,
+    }
+}
+
+impl<'a, C, InputSep, OutputSep, R> ConversionIterator<'a, C, InputSep, OutputSep, R>
+    where C: Converter + Sync,
+          InputSep: InputPathSeparator + Sync,
+          OutputSep: OutputPathSeparator + Sync,
+          R: Read {
+    /// Convert each chunk in parallel with [`convert_all_from`].
+    pub fn parallel(mut self) -> Self {
+        self.convert_chunk = convert_all_from;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::line_sep::LineSep;
+    use crate::convert::separator::SepStr;
+    
+    use super::*;
+    
+    /// [`n`] paths ended by [`sep`].
+    fn paths(n: usize, sep: &str) -> Vec<u8> {
+        (0..n).flat_map(|i| format!("C:\\{}{}", i, sep).into_bytes()).collect()
+    }
+    
+    #[test]
+    fn chunks_end_in_seps() {
+        let paths = paths(20_000, "\n");
+        let chunks = split_chunks(&paths, &LineSep::LF, 8);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() >= MIN_CHUNK_LEN && chunk.ends_with(b"\n")));
+        assert_eq!(chunks.concat(), paths);
+    }
+    
+    #[test]
+    fn small_input_is_one_chunk() {
+        let paths = paths(10, "\n");
+        assert_eq!(split_chunks(&paths, &LineSep::LF, 8), vec![paths.as_slice()]);
+        assert!(split_chunks(b"", &LineSep::LF, 8).is_empty());
+    }
+    
+    #[test]
+    fn overlapping_multi_byte_sep() {
+        // `:::` ends a path with `::` and starts the next one with `:`,
+        // so a chunk can't end in the middle of it
+        let sep = SepStr::new("::").unwrap();
+        let mut paths = paths(20_000, ":::");
+        // what's split only goes up to the remainder, which is the last `:`
+        paths.pop();
+        let chunks = split_chunks(&paths, &sep, 8);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert_eq!(sep_boundary(chunk, &sep, chunk.len()), chunk.len());
+            assert!(chunk.ends_with(b"::") && !chunk.ends_with(b":::"));
+        }
+        assert_eq!(chunks.concat(), paths);
+    }
+}
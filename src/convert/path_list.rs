@@ -0,0 +1,444 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::ops::Range;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use thiserror::Error;
+
+use crate::convert::{ConvertErrorInfo, Converter};
+use crate::util::enum_arg::EnumArg;
+
+/// How the entries of a path list like `PATH` are separated.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ListSyntax {
+    /// Separated by `:`, which can't be escaped.
+    Unix,
+    /// Separated by `;`, where `"` quotes an entry containing `;`.
+    Windows,
+}
+
+impl ListSyntax {
+    pub fn sep(&self) -> u8 {
+        use ListSyntax::*;
+        match self {
+            Unix => b':',
+            Windows => b';',
+        }
+    }
+    
+    /// Split [`list`] into its entries, removing any quotes.
+    pub fn split<'a>(&self, list: &'a [u8]) -> Vec<Cow<'a, [u8]>> {
+        self.split_spans(list).into_iter().map(|(_, entry)| entry).collect()
+    }
+    
+    /// [`split`](Self::split), along with the span of each entry in [`list`], including any quotes.
+    pub fn split_spans<'a>(&self, list: &'a [u8]) -> Vec<(Range<usize>, Cow<'a, [u8]>)> {
+        let sep = self.sep();
+        if let ListSyntax::Unix = self {
+            let mut start = 0;
+            return list
+                .split(|c| *c == sep)
+                .map(|entry| {
+                    let span = start..start + entry.len();
+                    start = span.end + 1;
+                    (span, Cow::Borrowed(entry))
+                })
+                .collect();
+        }
+        let mut entries = Vec::new();
+        let mut start = 0;
+        // only allocate for entries that have quotes
+        let mut unquoted: Option<Vec<u8>> = None;
+        let mut quoted = false;
+        for (i, c) in list.iter().enumerate() {
+            match *c {
+                b'"' => {
+                    quoted = !quoted;
+                    unquoted.get_or_insert_with(|| list[start..i].to_vec());
+                }
+                c if c == sep && !quoted => {
+                    entries.push((start..i, match unquoted.take() {
+                        None => Cow::Borrowed(&list[start..i]),
+                        Some(entry) => Cow::Owned(entry),
+                    }));
+                    start = i + 1;
+                }
+                c => if let Some(entry) = &mut unquoted {
+                    entry.push(c);
+                },
+            }
+        }
+        entries.push((start..list.len(), match unquoted {
+            None => Cow::Borrowed(&list[start..]),
+            Some(entry) => Cow::Owned(entry),
+        }));
+        entries
+    }
+    
+    /// The index in [`raw`], an entry as it's written in a list,
+    /// of the byte at [`index`] in the entry once its quotes are removed.
+    fn raw_index(&self, raw: &[u8], index: usize) -> usize {
+        if let ListSyntax::Unix = self {
+            return index;
+        }
+        raw.iter()
+            .enumerate()
+            .filter(|(_, c)| **c != b'"')
+            .map(|(i, _)| i)
+            .nth(index)
+            .unwrap_or(raw.len())
+    }
+    
+    /// Append [`entry`] to [`list`], after a separator if it's not the first entry,
+    /// quoting it if it has to be.
+    /// Returns whether [`entry`] could be written at all.
+    fn push(&self, list: &mut Vec<u8>, entry: &[u8], first: bool) -> bool {
+        use ListSyntax::*;
+        let needs_quotes = entry.contains(&self.sep());
+        match self {
+            Unix if needs_quotes => return false,
+            Windows if entry.contains(&b'"') => return false,
+            _ => {}
+        }
+        if !first {
+            list.push(self.sep());
+        }
+        if needs_quotes {
+            list.push(b'"');
+            list.extend_from_slice(entry);
+            list.push(b'"');
+        } else {
+            list.extend_from_slice(entry);
+        }
+        true
+    }
+}
+
+impl EnumArg for ListSyntax {
+    fn variants() -> &'static [Self] {
+        use ListSyntax::*;
+        &[Unix, Windows]
+    }
+    
+    fn displays(&self) -> &'static [&'static str] {
+        use ListSyntax::*;
+        match self {
+            Unix => &["unix"],
+            Windows => &["windows"],
+        }
+    }
+}
+
+impl Display for ListSyntax {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        EnumArg::fmt(self, f)
+    }
+}
+
+impl FromStr for ListSyntax {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EnumArg::from_str(s)
+    }
+}
+
+/// What to do with an empty entry in a path list,
+/// which means the current directory in a Unix `PATH`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EmptyEntryPolicy {
+    /// Keep it as an empty entry.
+    Keep,
+    /// Leave it out.
+    Drop,
+}
+
+impl EnumArg for EmptyEntryPolicy {
+    fn variants() -> &'static [Self] {
+        use EmptyEntryPolicy::*;
+        &[Keep, Drop]
+    }
+    
+    fn displays(&self) -> &'static [&'static str] {
+        use EmptyEntryPolicy::*;
+        match self {
+            Keep => &["keep"],
+            Drop => &["drop"],
+        }
+    }
+}
+
+impl Display for EmptyEntryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        EnumArg::fmt(self, f)
+    }
+}
+
+impl FromStr for EmptyEntryPolicy {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EnumArg::from_str(s)
+    }
+}
+
+impl Default for EmptyEntryPolicy {
+    fn default() -> Self {
+        use EmptyEntryPolicy::*;
+        Keep
+    }
+}
+
+/// What to do with an entry in a path list that can't be converted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FailedEntryPolicy {
+    /// Keep it unconverted,
+    /// failing to convert the whole list if it can't even be written in the output list.
+    Keep,
+    /// Leave it out.
+    Drop,
+    /// Fail to convert the whole list.
+    Error,
+}
+
+impl EnumArg for FailedEntryPolicy {
+    fn variants() -> &'static [Self] {
+        use FailedEntryPolicy::*;
+        &[Keep, Drop, Error]
+    }
+    
+    fn displays(&self) -> &'static [&'static str] {
+        use FailedEntryPolicy::*;
+        match self {
+            Keep => &["keep"],
+            Drop => &["drop"],
+            Error => &["error"],
+        }
+    }
+}
+
+impl Display for FailedEntryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        EnumArg::fmt(self, f)
+    }
+}
+
+impl FromStr for FailedEntryPolicy {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EnumArg::from_str(s)
+    }
+}
+
+impl Default for FailedEntryPolicy {
+    fn default() -> Self {
+        use FailedEntryPolicy::*;
+        Error
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ListOptions {
+    pub input: ListSyntax,
+    pub output: ListSyntax,
+    pub empty: EmptyEntryPolicy,
+    pub failed: FailedEntryPolicy,
+}
+
+impl ListOptions {
+    /// The default policies for converting from [`input`] to [`output`] lists.
+    pub fn new(input: ListSyntax, output: ListSyntax) -> Self {
+        Self {
+            input,
+            output,
+            empty: Default::default(),
+            failed: Default::default(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ListEntryError<E: std::error::Error + 'static> {
+    #[error(transparent)]
+    Convert(E),
+    #[error("can't be written in a {0} path list")]
+    Unwritable(ListSyntax),
+}
+
+#[derive(Error, Debug)]
+#[error("error converting entry {index} {entry:?} of path list: {source}")]
+pub struct ListError<E: std::error::Error + 'static> {
+    index: usize,
+    /// The byte index in the list of the error in the entry, or of the entry itself.
+    list_index: usize,
+    entry: OsString,
+    source: ListEntryError<E>,
+}
+
+impl<E: std::error::Error + 'static> ListError<E> {
+    /// The index of the entry in the list.
+    pub fn entry_index(&self) -> usize {
+        self.index
+    }
+    
+    pub fn entry(&self) -> &OsStr {
+        self.entry.as_os_str()
+    }
+    
+    pub fn error(&self) -> &ListEntryError<E> {
+        &self.source
+    }
+}
+
+impl<E: std::error::Error + ConvertErrorInfo + 'static> ConvertErrorInfo for ListError<E> {
+    fn kind(&self) -> &'static str {
+        match &self.source {
+            ListEntryError::Convert(e) => e.kind(),
+            ListEntryError::Unwritable(_) => "Unwritable",
+        }
+    }
+    
+    fn index(&self) -> Option<usize> {
+        Some(self.list_index)
+    }
+}
+
+/// A converted path list,
+/// and the errors for the entries that were kept or dropped because they couldn't be converted.
+#[derive(Debug)]
+pub struct ConvertedList<E: std::error::Error + 'static> {
+    pub list: OsString,
+    pub errors: Vec<ListError<E>>,
+}
+
+/// Convert each entry of the path [`list`] with [`converter`],
+/// and join them back together in the output syntax.
+/// Fails on the first entry that can't be converted if that's the [`FailedEntryPolicy`].
+pub fn convert_list<C: Converter>(
+    converter: &C,
+    list: &[u8],
+    options: &ListOptions,
+) -> Result<ConvertedList<C::Error>, ListError<C::Error>> {
+    let mut output = Vec::with_capacity(list.len());
+    let mut errors = Vec::new();
+    let mut first = true;
+    for (index, (span, entry)) in options.input.split_spans(list).into_iter().enumerate() {
+        if entry.is_empty() {
+            if let EmptyEntryPolicy::Keep = options.empty {
+                options.output.push(&mut output, &entry, first);
+                first = false;
+            }
+            continue;
+        }
+        let error = |source: ListEntryError<C::Error>| {
+            let index_in_entry = match &source {
+                ListEntryError::Convert(e) => e.index().unwrap_or(0),
+                ListEntryError::Unwritable(_) => 0,
+            };
+            ListError {
+                index,
+                list_index: span.start + options.input.raw_index(&list[span.clone()], index_in_entry),
+                entry: OsString::from_vec(entry.to_vec()),
+                source,
+            }
+        };
+        let converted = converter.convert_cow(&entry).map_err(ListEntryError::Convert);
+        let converted = converted.and_then(|converted| {
+            match options.output.push(&mut output, &converted, first) {
+                true => Ok(()),
+                false => Err(ListEntryError::Unwritable(options.output)),
+            }
+        });
+        let source = match converted {
+            Ok(()) => {
+                first = false;
+                continue;
+            }
+            Err(source) => source,
+        };
+        match options.failed {
+            FailedEntryPolicy::Error => return Err(error(source)),
+            FailedEntryPolicy::Drop => {}
+            FailedEntryPolicy::Keep => match options.output.push(&mut output, &entry, first) {
+                true => first = false,
+                // keeping it can't mean silently dropping it
+                false => return Err(error(ListEntryError::Unwritable(options.output))),
+            },
+        }
+        errors.push(error(source));
+    }
+    Ok(ConvertedList {
+        list: OsString::from_vec(output),
+        errors,
+    })
+}
+
+pub struct PathListOptions<O> {
+    /// The options for converting each entry.
+    pub converter: O,
+    pub list: ListOptions,
+}
+
+/// A [`Converter`] that converts whole path lists with another [`Converter`],
+/// so that path lists can be converted anywhere paths can.
+///
+/// A [`Converter`] can only fail a whole list, so the errors of the entries that were kept or dropped
+/// under [`FailedEntryPolicy::Keep`] or [`FailedEntryPolicy::Drop`] are kept for [`take_entry_errors`](Self::take_entry_errors).
+pub struct PathListConverter<C: Converter> {
+    converter: C,
+    options: ListOptions,
+    entry_errors: Mutex<Vec<Vec<ListError<C::Error>>>>,
+}
+
+impl<C: Converter> PathListConverter<C> {
+    pub fn from_converter(converter: C, options: ListOptions) -> Self {
+        Self {
+            converter,
+            options,
+            entry_errors: Default::default(),
+        }
+    }
+    
+    /// The errors of the entries that were kept or dropped instead of failing their list,
+    /// for each list converted since the last call that had any.
+    pub fn take_entry_errors(&self) -> Vec<Vec<ListError<C::Error>>> {
+        let mut entry_errors = self.entry_errors.lock().unwrap_or_else(PoisonError::into_inner);
+        std::mem::take(&mut *entry_errors)
+    }
+    
+    pub fn converter(&self) -> &C {
+        &self.converter
+    }
+    
+    pub fn into_converter(self) -> C {
+        self.converter
+    }
+}
+
+impl<C: Converter> Converter for PathListConverter<C> {
+    type Options = PathListOptions<C::Options>;
+    type OptionsError = C::OptionsError;
+    type Error = ListError<C::Error>;
+    
+    fn new(options: Self::Options) -> Result<Self, Self::OptionsError> {
+        let converter = C::new(options.converter)?;
+        Ok(Self::from_converter(converter, options.list))
+    }
+    
+    fn convert_slice_into_buf(&self, list: &[u8], buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        let converted = convert_list(&self.converter, list, &self.options)?;
+        buf.extend_from_slice(converted.list.as_bytes());
+        if !converted.errors.is_empty() {
+            self.entry_errors
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(converted.errors);
+        }
+        Ok(())
+    }
+}
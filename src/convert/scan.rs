@@ -0,0 +1,94 @@
+//! Finding the few rare bytes in a path that need any work,
+//! so that everything in between can be copied as is.
+
+/// How many bytes are checked at once.
+const LANES: usize = 16;
+
+/// An iterator over the indices of the special bytes in a path, in order.
+/// Control chars (`< 0x20`) are always special, plus any bytes in [`special`].
+/// This looks at [`LANES`] bytes at a time, using SIMD where possible,
+/// since special bytes are rare in most paths.
+pub struct Specials<'a, const N: usize> {
+    bytes: &'a [u8],
+    special: [u8; N],
+    /// The start of the current chunk in [`bytes`].
+    chunk_start: usize,
+    /// The special bytes left in the current chunk, one bit per byte.
+    mask: u32,
+}
+
+impl<'a, const N: usize> Specials<'a, N> {
+    pub fn new(bytes: &'a [u8], special: [u8; N]) -> Self {
+        let mask = chunk_mask(&bytes[..bytes.len().min(LANES)], &special);
+        Self {
+            bytes,
+            special,
+            chunk_start: 0,
+            mask,
+        }
+    }
+}
+
+impl<'a, const N: usize> Iterator for Specials<'a, N> {
+    type Item = usize;
+    
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.mask == 0 {
+            self.chunk_start += LANES;
+            if self.chunk_start >= self.bytes.len() {
+                return None;
+            }
+            let chunk = &self.bytes[self.chunk_start..];
+            self.mask = chunk_mask(&chunk[..chunk.len().min(LANES)], &self.special);
+        }
+        let i = self.mask.trailing_zeros() as usize;
+        // clear the lowest bit
+        self.mask &= self.mask - 1;
+        Some(self.chunk_start + i)
+    }
+}
+
+/// The special bytes in [`chunk`] as a bitmask,
+/// where [`chunk`] is at most [`LANES`] long.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn chunk_mask<const N: usize>(chunk: &[u8], special: &[u8; N]) -> u32 {
+    use std::arch::x86_64::*;
+    
+    let mut padded = [0; LANES];
+    let bytes = match chunk.len() {
+        LANES => chunk,
+        len => {
+            padded[..len].copy_from_slice(chunk);
+            &padded
+        }
+    };
+    // SSE2 is always available on x86_64, and bytes is exactly LANES long
+    let mask = unsafe {
+        let bytes = _mm_loadu_si128(bytes.as_ptr() as *const __m128i);
+        // there's no unsigned <, but c <= max is the same as min(c, max) == c
+        let max_low = _mm_set1_epi8((b' ' - 1) as i8);
+        let mut found = _mm_cmpeq_epi8(_mm_min_epu8(bytes, max_low), bytes);
+        for c in special {
+            found = _mm_or_si128(found, _mm_cmpeq_epi8(bytes, _mm_set1_epi8(*c as i8)));
+        }
+        _mm_movemask_epi8(found) as u32
+    };
+    // the padding is all nulls, which are control chars, so ignore them
+    match chunk.len() {
+        LANES => mask,
+        len => mask & ((1 << len) - 1),
+    }
+}
+
+/// The special bytes in [`chunk`] as a bitmask,
+/// where [`chunk`] is at most [`LANES`] long.
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+fn chunk_mask<const N: usize>(chunk: &[u8], special: &[u8; N]) -> u32 {
+    chunk.iter()
+        .enumerate()
+        .filter(|(_, c)| **c < b' ' || special.contains(*c))
+        .fold(0, |mask, (i, _)| mask | (1 << i))
+}
@@ -0,0 +1,302 @@
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use memchr::{memchr, memrchr};
+use memchr::memmem;
+
+use crate::convert::{InputPathSeparator, OutputPathSeparator};
+use crate::convert::line_sep::LineSep;
+
+/// A single-byte separator.
+impl InputPathSeparator for u8 {
+    fn matches(&self, c: u8) -> bool {
+        c == *self
+    }
+    
+    fn find(&self, paths: &[u8]) -> Option<Range<usize>> {
+        let i = memchr(*self, paths)?;
+        Some(i..i + 1)
+    }
+    
+    fn rfind(&self, paths: &[u8]) -> Option<Range<usize>> {
+        let i = memrchr(*self, paths)?;
+        Some(i..i + 1)
+    }
+}
+
+impl OutputPathSeparator for u8 {
+    fn write_to_buf(&self, buf: &mut Vec<u8>) {
+        buf.push(*self);
+    }
+}
+
+impl OutputPathSeparator for [u8] {
+    fn write_to_buf(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+}
+
+/// A multi-byte separator, like `::` or `\r\n`, that only matches as a whole.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SepStr(Vec<u8>);
+
+impl SepStr {
+    /// [`sep`] can't be empty, since an empty separator would be everywhere.
+    pub fn new(sep: impl Into<Vec<u8>>) -> Option<Self> {
+        let sep = sep.into();
+        match sep.is_empty() {
+            true => None,
+            false => Some(Self(sep)),
+        }
+    }
+    
+    pub fn value(&self) -> &[u8] {
+        &self.0
+    }
+    
+    fn has_line_feed(&self) -> bool {
+        memchr(b'\n', &self.0).is_some()
+    }
+}
+
+/// If the separator has a `\n` in it, each `\n` ends a line, like in the input file.
+/// Otherwise, each whole separator does, not each byte of it.
+impl InputPathSeparator for SepStr {
+    /// A single byte is only a separator if it's the whole separator.
+    fn matches(&self, c: u8) -> bool {
+        self.0 == [c]
+    }
+    
+    fn is_line_end(&self, c: u8) -> bool {
+        c == b'\n' && self.has_line_feed()
+    }
+    
+    fn lines_ended(&self, sep: &[u8]) -> usize {
+        match self.has_line_feed() {
+            true => sep.iter().filter(|c| **c == b'\n').count(),
+            false => 1,
+        }
+    }
+    
+    fn find(&self, paths: &[u8]) -> Option<Range<usize>> {
+        let i = memmem::find(paths, &self.0)?;
+        Some(i..i + self.0.len())
+    }
+    
+    fn rfind(&self, paths: &[u8]) -> Option<Range<usize>> {
+        let i = memmem::rfind(paths, &self.0)?;
+        Some(i..i + self.0.len())
+    }
+}
+
+impl OutputPathSeparator for SepStr {
+    fn write_to_buf(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.0);
+    }
+}
+
+/// A set of single-byte separators, any of which ends a path.
+/// Runs of them are fine, since empty paths are skipped.
+#[derive(Clone)]
+pub struct ByteSet([bool; 256]);
+
+impl ByteSet {
+    pub fn new(bytes: &[u8]) -> Self {
+        Self::from_fn(|c| bytes.contains(&c))
+    }
+    
+    pub fn from_fn(matches: impl Fn(u8) -> bool) -> Self {
+        let mut set = [false; 256];
+        for c in 0..=u8::MAX {
+            set[c as usize] = matches(c);
+        }
+        Self(set)
+    }
+    
+    /// Any ASCII whitespace, i.e., space, `\t`, `\n`, `\x0C`, and `\r`.
+    pub fn ascii_whitespace() -> Self {
+        Self::from_fn(|c| c.is_ascii_whitespace())
+    }
+}
+
+impl InputPathSeparator for ByteSet {
+    fn matches(&self, c: u8) -> bool {
+        self.0[c as usize]
+    }
+    
+    fn is_line_end(&self, c: u8) -> bool {
+        match self.matches(b'\n') {
+            true => c == b'\n',
+            false => self.matches(c),
+        }
+    }
+}
+
+/// A single-byte separator decided by a closure.
+pub struct SepFn<F: Fn(u8) -> bool>(pub F);
+
+impl<F: Fn(u8) -> bool> InputPathSeparator for SepFn<F> {
+    fn matches(&self, c: u8) -> bool {
+        (self.0)(c)
+    }
+}
+
+/// Any kind of separator, as given on the command line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Separator {
+    Line(LineSep),
+    /// Any run of ASCII whitespace when reading, and `\n` when writing.
+    Whitespace,
+    Byte(u8),
+    Str(SepStr),
+}
+
+impl Separator {
+    /// The separator as it's written.
+    pub fn value(&self) -> &[u8] {
+        use Separator::*;
+        match self {
+            Line(sep) => sep.value().as_bytes(),
+            Whitespace => b"\n",
+            Byte(c) => std::slice::from_ref(c),
+            Str(sep) => sep.value(),
+        }
+    }
+}
+
+impl Default for Separator {
+    fn default() -> Self {
+        Separator::Line(LineSep::default())
+    }
+}
+
+impl InputPathSeparator for Separator {
+    fn matches(&self, c: u8) -> bool {
+        use Separator::*;
+        match self {
+            Line(sep) => sep.matches(c),
+            Whitespace => c.is_ascii_whitespace(),
+            Byte(sep) => sep.matches(c),
+            Str(sep) => sep.matches(c),
+        }
+    }
+    
+    fn is_line_end(&self, c: u8) -> bool {
+        use Separator::*;
+        match self {
+            Line(sep) => sep.is_line_end(c),
+            Whitespace => c == b'\n',
+            Byte(sep) => sep.is_line_end(c),
+            Str(sep) => sep.is_line_end(c),
+        }
+    }
+    
+    fn lines_ended(&self, sep: &[u8]) -> usize {
+        use Separator::*;
+        match self {
+            Line(line_sep) => line_sep.lines_ended(sep),
+            Whitespace => sep.iter().filter(|c| **c == b'\n').count(),
+            Byte(c) => c.lines_ended(sep),
+            Str(sep_str) => sep_str.lines_ended(sep),
+        }
+    }
+    
+    fn find(&self, paths: &[u8]) -> Option<Range<usize>> {
+        use Separator::*;
+        match self {
+            Line(sep) => sep.find(paths),
+            Whitespace => {
+                let i = paths.iter().position(u8::is_ascii_whitespace)?;
+                Some(i..i + 1)
+            }
+            Byte(sep) => sep.find(paths),
+            Str(sep) => sep.find(paths),
+        }
+    }
+    
+    fn rfind(&self, paths: &[u8]) -> Option<Range<usize>> {
+        use Separator::*;
+        match self {
+            Line(sep) => sep.rfind(paths),
+            Whitespace => {
+                let i = paths.iter().rposition(u8::is_ascii_whitespace)?;
+                Some(i..i + 1)
+            }
+            Byte(sep) => sep.rfind(paths),
+            Str(sep) => sep.rfind(paths),
+        }
+    }
+}
+
+impl OutputPathSeparator for Separator {
+    fn write_to_buf(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.value());
+    }
+}
+
+/// Parse a separator like [`LineSep`], `whitespace`, or any other string,
+/// where `\\`, `\0`, `\t`, `\n`, `\r`, and `\xHH` are escapes.
+impl FromStr for Separator {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // a [`LineSep`] can be written as `0`, but here that's just a `0`, and NUL is `\0`
+        if let Some(sep) = Some(s).filter(|s| *s != "0").and_then(|s| s.parse().ok()) {
+            return Ok(Separator::Line(sep));
+        }
+        if s.eq_ignore_ascii_case("whitespace") {
+            return Ok(Separator::Whitespace);
+        }
+        let sep = unescape(s)?;
+        match sep.as_slice() {
+            [] => Err("empty separator".into()),
+            [c] => Ok(Separator::Byte(*c)),
+            _ => Ok(Separator::Str(SepStr(sep))),
+        }
+    }
+}
+
+impl Display for Separator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use Separator::*;
+        match self {
+            Line(sep) => Display::fmt(sep, f),
+            Whitespace => write!(f, "whitespace"),
+            Byte(_) | Str(_) => write!(f, "{}", self.value().escape_ascii()),
+        }
+    }
+}
+
+fn unescape(s: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut chars = s.bytes();
+    while let Some(c) = chars.next() {
+        if c != b'\\' {
+            bytes.push(c);
+            continue;
+        }
+        let c = match chars.next() {
+            Some(b'\\') => b'\\',
+            Some(b'0') => 0,
+            Some(b't') => b'\t',
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b'x') => {
+                let hex = [chars.next(), chars.next()];
+                let hex = match hex {
+                    [Some(a), Some(b)] => [a, b],
+                    _ => return Err(format!("incomplete \\x escape in {:?}", s)),
+                };
+                std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("invalid \\x escape in {:?}", s))?
+            }
+            _ => return Err(format!("invalid escape in {:?}", s)),
+        };
+        bytes.push(c);
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,75 @@
+use std::ffi::OsStr;
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt;
+
+/// Receives the result of converting each path in [`Converter::convert_each`](super::Converter::convert_each),
+/// in input order.
+pub trait ConversionSink<E> {
+    /// [`input`] is the span of the path in the input,
+    /// and [`output`] is either the converted path or why it couldn't be converted.
+    /// The converted path is only borrowed until the next call,
+    /// so copy it wherever it needs to go.
+    fn accept(&mut self, input: Range<usize>, output: Result<&[u8], E>);
+}
+
+impl<E, F> ConversionSink<E> for F
+    where F: FnMut(Range<usize>, Result<&[u8], E>) {
+    fn accept(&mut self, input: Range<usize>, output: Result<&[u8], E>) {
+        self(input, output)
+    }
+}
+
+/// `(input span, Result<output span, error>)`
+pub type PathSpan<E> = (Range<usize>, Result<Range<usize>, E>);
+
+/// A [`ConversionSink`] that collects all the converted paths into one buffer,
+/// recording where each input and output path is.
+pub struct SpanSink<E> {
+    pub buf: Vec<u8>,
+    /// The output spans are in [`buf`](SpanSink::buf), in input order.
+    pub spans: Vec<PathSpan<E>>,
+}
+
+impl<E> SpanSink<E> {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+    
+    /// The converted path at [`index`] (of all the paths, not bytes),
+    /// or why it couldn't be converted.
+    pub fn get(&self, index: usize) -> Option<Result<&OsStr, &E>> {
+        let (_, output) = self.spans.get(index)?;
+        let output = output
+            .as_ref()
+            .map(|span| OsStr::from_bytes(&self.buf[span.clone()]));
+        Some(output)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+}
+
+impl<E> Default for SpanSink<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> ConversionSink<E> for SpanSink<E> {
+    fn accept(&mut self, input: Range<usize>, output: Result<&[u8], E>) {
+        let output = output.map(|path| {
+            let start = self.buf.len();
+            self.buf.extend_from_slice(path);
+            start..self.buf.len()
+        });
+        self.spans.push((input, output));
+    }
+}
@@ -0,0 +1,95 @@
+use std::io;
+use std::io::{BufRead, Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+use crate::convert::{ConversionIterator, Converter, InputPathSeparator, OneConvertError, OutputPathSeparator};
+
+impl<'a, C, InputSep, OutputSep, R> ConversionIterator<'a, C, InputSep, OutputSep, R>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator,
+          R: Read {
+    /// Convert everything, writing the converted paths to [`writer`]
+    /// and passing the error for each path that fails to convert to [`on_error`].
+    /// Returns the number of paths attempted.
+    pub fn write_to<W, F>(self, mut writer: W, mut on_error: F) -> io::Result<usize>
+        where W: Write,
+              F: FnMut(OneConvertError<C::Error>) {
+        let mut num_paths = 0;
+        for converted in self {
+            let converted = converted?;
+            writer.write_all(converted.paths.as_bytes())?;
+            num_paths += converted.num_paths;
+            converted.errors.into_iter().for_each(&mut on_error);
+        }
+        writer.flush()?;
+        Ok(num_paths)
+    }
+    
+    /// Read the converted paths instead of iterating over each chunk,
+    /// passing the error for each path that fails to convert to [`on_error`].
+    pub fn into_reader<F>(self, on_error: F) -> ConversionReader<'a, C, InputSep, OutputSep, R, F>
+        where F: FnMut(OneConvertError<C::Error>) {
+        ConversionReader {
+            iter: self,
+            on_error,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+/// A [`Read`] of converted paths, created by [`ConversionIterator::into_reader`].
+pub struct ConversionReader<'a, C, InputSep, OutputSep, R, F>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator,
+          R: Read,
+          F: FnMut(OneConvertError<C::Error>) {
+    iter: ConversionIterator<'a, C, InputSep, OutputSep, R>,
+    on_error: F,
+    /// The current converted chunk.
+    buf: Vec<u8>,
+    /// How much of [`buf`] has been read.
+    pos: usize,
+}
+
+impl<'a, C, InputSep, OutputSep, R, F> BufRead for ConversionReader<'a, C, InputSep, OutputSep, R, F>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator,
+          R: Read,
+          F: FnMut(OneConvertError<C::Error>) {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // a chunk can be empty if it had no complete paths, so keep going until one isn't
+        while self.pos == self.buf.len() {
+            let converted = match self.iter.next() {
+                None => break,
+                Some(converted) => converted?,
+            };
+            converted.errors.into_iter().for_each(&mut self.on_error);
+            self.buf = converted.paths.into_vec();
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+    
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+impl<'a, C, InputSep, OutputSep, R, F> Read for ConversionReader<'a, C, InputSep, OutputSep, R, F>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator,
+          R: Read,
+          F: FnMut(OneConvertError<C::Error>) {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
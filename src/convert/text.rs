@@ -0,0 +1,276 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::io;
+use std::io::{BufRead, Write};
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::convert::Converter;
+use crate::convert::path_sep::WindowsPathSep;
+
+/// Which kind of paths to find in text.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextPaths {
+    /// Like `C:\src\a.rs` or `\\wsl$\distro\home\a.rs`.
+    Windows,
+    /// Like `/home/me/a.rs`.
+    Linux,
+}
+
+/// How to find paths in free-form text, like compiler errors or logs.
+#[derive(Clone, Debug)]
+pub struct TextOptions {
+    pub paths: TextPaths,
+    /// Find Windows drive paths, like `C:\a`.
+    pub drives: bool,
+    /// Find Windows UNC paths, like `\\server\share\a`.
+    pub unc: bool,
+    /// Only find Linux paths whose first component exists, like `/home`,
+    /// to skip things like `/nologo` options.
+    pub existing_root: bool,
+    /// Let paths right after a `"` or `'` have spaces, up to the closing quote.
+    pub quoted_spaces: bool,
+    /// More bytes that end a path, besides whitespace, quotes, `<>|`, and `:` (other than a drive's).
+    pub terminators: Vec<u8>,
+    /// The separator the converter expects in Windows paths,
+    /// which the separators in the Windows paths found are changed to, since they can use either.
+    pub sep: WindowsPathSep,
+}
+
+impl TextOptions {
+    /// The default options for finding [`paths`].
+    pub fn new(paths: TextPaths) -> Self {
+        Self {
+            paths,
+            drives: true,
+            unc: true,
+            existing_root: true,
+            quoted_spaces: true,
+            terminators: Vec::new(),
+            sep: WindowsPathSep::default(),
+        }
+    }
+    
+    /// Change the separators in the found [`path`] to [`TextOptions::sep`] if it's a Windows path,
+    /// only copying it if that changes anything.
+    pub(crate) fn normalize_seps<'a>(&self, path: &'a [u8]) -> Cow<'a, [u8]> {
+        let sep = self.sep.value();
+        let is_other_sep = |c: &u8| (*c == b'/' || *c == b'\\') && *c != sep;
+        if self.paths != TextPaths::Windows || !path.iter().any(is_other_sep) {
+            return Cow::Borrowed(path);
+        }
+        Cow::Owned(path.iter().map(|c| if is_other_sep(c) { sep } else { *c }).collect())
+    }
+    
+    /// Whether a path can start at [`i`] in [`line`],
+    /// which is only at the start of a word, so `a/b` and `https://a` aren't paths.
+    fn is_start(&self, line: &[u8], i: usize) -> bool {
+        let is_boundary = match i.checked_sub(1).map(|i| line[i]) {
+            None => true,
+            Some(c) => !c.is_ascii_alphanumeric() && !b"/\\:._-~$%".contains(&c),
+        };
+        is_boundary && self.starts_path(&line[i..])
+    }
+    
+    /// Whether [`rest`] starts with what has to be at the start of a path.
+    fn starts_path(&self, rest: &[u8]) -> bool {
+        match self.paths {
+            TextPaths::Windows => match rest {
+                [drive, b':', b'\\' | b'/', ..] => self.drives && drive.is_ascii_alphabetic(),
+                [b'\\', b'\\', c, ..] => self.unc && !self.ends_path(*c) && *c != b'\\',
+                _ => false,
+            },
+            TextPaths::Linux => match rest {
+                [b'/', c, ..] => !self.ends_path(*c) && *c != b'/',
+                _ => false,
+            },
+        }
+    }
+    
+    fn ends_path(&self, c: u8) -> bool {
+        c.is_ascii_whitespace()
+            || c.is_ascii_control()
+            || b"\"'`<>|:".contains(&c)
+            || self.terminators.contains(&c)
+    }
+    
+    /// The end of the path starting at [`start`] in [`line`], before trimming.
+    fn path_end(&self, line: &[u8], start: usize) -> usize {
+        let quote = start.checked_sub(1).map(|i| line[i]).filter(|c| *c == b'"' || *c == b'\'');
+        if let (Some(quote), true) = (quote, self.quoted_spaces) {
+            if let Some(len) = line[start..].iter().position(|c| *c == quote) {
+                return start + len;
+            }
+        }
+        // a drive's : doesn't end it
+        let skip = match self.paths {
+            TextPaths::Windows if line[start] != b'\\' => 2,
+            _ => 0,
+        };
+        let len = line[start + skip..].iter().position(|c| self.ends_path(*c));
+        len.map_or(line.len(), |len| start + skip + len)
+    }
+    
+    /// Whether [`path`] passes [`TextOptions::existing_root`].
+    fn has_existing_root(&self, path: &[u8]) -> bool {
+        if self.paths != TextPaths::Linux || !self.existing_root {
+            return true;
+        }
+        let root = path[1..].iter().position(|c| *c == b'/').map_or(path, |i| &path[..1 + i]);
+        Path::new(OsStr::from_bytes(root)).exists()
+    }
+}
+
+/// Trim what's after a path in [`path`] that's usually not part of it:
+/// a `(line,column)` location, and sentence punctuation or a closing bracket.
+/// A `:line:column` location already ends the path at the `:`.
+fn trim(path: &[u8]) -> &[u8] {
+    let mut path = path;
+    loop {
+        let trimmed = match path {
+            [rest @ .., b'.' | b',' | b';' | b'!' | b'?'] => rest,
+            [rest @ .., b')'] => {
+                let location = rest
+                    .iter()
+                    .rposition(|c| *c == b'(')
+                    .filter(|open| {
+                        let location = &rest[open + 1..];
+                        !location.is_empty() && location.iter().all(|c| c.is_ascii_digit() || *c == b',')
+                    });
+                let count = |bracket| path.iter().filter(|c| **c == bracket).count();
+                match location {
+                    Some(open) => &rest[..open],
+                    // like `(see C:\a.h)`, but not `C:\Program Files (x86)`
+                    None if count(b'(') < count(b')') => rest,
+                    None => path,
+                }
+            }
+            [rest @ .., b']' | b'}'] => rest,
+            _ => path,
+        };
+        if trimmed.len() == path.len() {
+            return path;
+        }
+        path = trimmed;
+    }
+}
+
+/// The ranges of the paths in [`line`] to convert, without any `(line,column)` or `:line:column` after them.
+pub fn find_paths(line: &[u8], options: &TextOptions) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < line.len() {
+        if !options.is_start(line, i) {
+            i += 1;
+            continue;
+        }
+        let end = options.path_end(line, i);
+        let path = trim(&line[i..end]);
+        if options.starts_path(path) && options.has_existing_root(path) {
+            ranges.push(i..i + path.len());
+        }
+        i = end.max(i + 1);
+    }
+    ranges
+}
+
+/// How many paths were found in some text, and how many of them couldn't be converted.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct TextSummary {
+    pub found: usize,
+    pub failed: usize,
+}
+
+/// Replace each of [`ranges`] in [`line`], in order, with what [`convert`] returns for it,
+/// leaving the ones it returns [`None`] for, and any that overlap an earlier one, as is.
+/// [`line`] is only copied if something's replaced.
+pub fn replace_ranges<'a, I, F>(line: &'a [u8], ranges: I, mut convert: F) -> Cow<'a, [u8]>
+    where I: IntoIterator<Item = Range<usize>>,
+          F: FnMut(Range<usize>) -> Option<Cow<'a, [u8]>> {
+    let mut converted = Vec::new();
+    let mut end = 0;
+    for range in ranges {
+        if range.start < end {
+            continue;
+        }
+        let path = match convert(range.clone()) {
+            Some(path) => path,
+            None => continue,
+        };
+        converted.extend_from_slice(&line[end..range.start]);
+        converted.extend_from_slice(&path);
+        end = range.end;
+    }
+    if end == 0 {
+        return Cow::Borrowed(line);
+    }
+    converted.extend_from_slice(&line[end..]);
+    Cow::Owned(converted)
+}
+
+/// Copy each line read from [`reader`] to [`writer`],
+/// passing it through [`filter`] without its `\n` or `\r\n`, which is then written as is.
+/// Each line is flushed as soon as it's written, so this can follow a running program's output.
+pub fn filter_lines<R, W, F>(mut reader: R, mut writer: W, mut filter: F) -> io::Result<()>
+    where R: BufRead,
+          W: Write,
+          F: for<'a> FnMut(&'a [u8]) -> Cow<'a, [u8]> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        let content_len = match line.as_slice() {
+            [.., b'\r', b'\n'] => line.len() - 2,
+            [.., b'\n'] => line.len() - 1,
+            _ => line.len(),
+        };
+        writer.write_all(&filter(&line[..content_len]))?;
+        writer.write_all(&line[content_len..])?;
+        writer.flush()?;
+    }
+}
+
+/// Convert the paths found in [`line`] with [`converter`], leaving the rest of it as is.
+/// Paths that can't be converted are left as is, too, since they might not really be paths,
+/// but they're counted as failed in [`summary`].
+pub fn convert_line<'a, C: Converter>(
+    converter: &C,
+    line: &'a [u8],
+    options: &TextOptions,
+    summary: &mut TextSummary,
+) -> Cow<'a, [u8]> {
+    let ranges = find_paths(line, options);
+    summary.found += ranges.len();
+    replace_ranges(line, ranges, |range| {
+        let converted = match options.normalize_seps(&line[range]) {
+            Cow::Borrowed(path) => converter.convert_cow(path),
+            Cow::Owned(path) => converter.convert_cow(&path).map(|it| Cow::Owned(it.into_owned())),
+        };
+        match converted {
+            Ok(path) => Some(path),
+            Err(_) => {
+                summary.failed += 1;
+                None
+            }
+        }
+    })
+}
+
+/// Convert the paths found in each line read from [`reader`], writing the lines to [`writer`].
+/// Each line is flushed as soon as it's converted, so this can follow a running program's output.
+pub fn convert_text<C, R, W>(
+    converter: &C,
+    options: &TextOptions,
+    reader: R,
+    writer: W,
+) -> io::Result<TextSummary>
+    where C: Converter,
+          R: BufRead,
+          W: Write {
+    let mut summary = TextSummary::default();
+    filter_lines(reader, writer, |line| convert_line(converter, line, options, &mut summary))?;
+    Ok(summary)
+}
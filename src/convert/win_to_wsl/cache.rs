@@ -0,0 +1,260 @@
+use std::{env, fs};
+use std::ffi::OsStr;
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::convert::wsl::{get_wsl_distro_name, DrvFsMountPoint};
+
+/// Bump this whenever the file format changes.
+const VERSION: &[u8] = b"wslpath-cache-2";
+
+const BOOT_ID: &str = "/proc/sys/kernel/random/boot_id";
+
+const MOUNTS: &str = "/proc/mounts";
+
+/// `$XDG_CACHE_HOME/wslpath`, falling back to `~/.cache/wslpath`.
+pub fn default_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|it| !it.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_home.join("wslpath"))
+}
+
+/// 64-bit FNV-1a, which unlike [`DefaultHasher`](std::collections::hash_map::DefaultHasher),
+/// is the same in every build, so a cache written by one version can be checked by another.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, c| (hash ^ *c as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Everything the cached data depends on.
+/// If any of it changes, the cache is stale.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CacheKey {
+    boot_id: Vec<u8>,
+    distro: Vec<u8>,
+    mounts_hash: Vec<u8>,
+}
+
+impl CacheKey {
+    /// The key for [`boot_id`], [`distro`], and the contents of `/proc/mounts`, [`mounts`].
+    pub fn new(boot_id: &[u8], distro: &[u8], mounts: &[u8]) -> Self {
+        Self {
+            boot_id: boot_id.strip_suffix(b"\n").unwrap_or(boot_id).to_vec(),
+            distro: distro.to_vec(),
+            mounts_hash: format!("{:016x}", fnv1a(mounts)).into_bytes(),
+        }
+    }
+    
+    /// The key right now, or [`None`] if it can't be determined, e.g., if we're not on WSL.
+    /// [`distro`] overrides the detected distro name.
+    pub fn current(distro: Option<&OsStr>) -> Option<Self> {
+        let distro = match distro {
+            Some(distro) => distro.to_os_string(),
+            None => get_wsl_distro_name().ok()?,
+        }.into_vec();
+        Some(Self::new(&fs::read(BOOT_ID).ok()?, &distro, &fs::read(MOUNTS).ok()?))
+    }
+}
+
+/// What's cached, each of which may not have been looked up yet.
+#[derive(Default)]
+struct Entries {
+    windows_store_root: Option<PathBuf>,
+    mounts: Option<Vec<DrvFsMountPoint>>,
+}
+
+/// An on-disk cache of expensive environment lookups,
+/// so that short-lived processes don't have to redo them every time.
+/// Any failure to read or write the cache is treated like a cache miss.
+pub struct Cache {
+    path: PathBuf,
+    key: CacheKey,
+}
+
+fn path_from(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+impl Cache {
+    /// Returns [`None`] if the cache key can't be determined, e.g., if we're not on WSL.
+    /// [`distro`] overrides the detected distro name.
+    pub fn open(dir: &Path, distro: Option<&OsStr>) -> Option<Self> {
+        Some(Self::with_key(dir, CacheKey::current(distro)?))
+    }
+    
+    /// A cache in [`dir`] that's only valid for [`key`].
+    pub fn with_key(dir: &Path, key: CacheKey) -> Self {
+        Self {
+            path: dir.join("root"),
+            key,
+        }
+    }
+    
+    /// The cached entries, or none if the cache is stale or unreadable.
+    fn read(&self) -> Option<Entries> {
+        let contents = fs::read(&self.path).ok()?;
+        // fields are each terminated by a NUL, since that can't be in a path
+        let mut fields = contents.split(|c| *c == 0);
+        if fields.next()? != VERSION {
+            return None;
+        }
+        let key = CacheKey {
+            boot_id: fields.next()?.to_vec(),
+            distro: fields.next()?.to_vec(),
+            mounts_hash: fields.next()?.to_vec(),
+        };
+        if key != self.key {
+            return None;
+        }
+        let windows_store_root = Some(fields.next()?).filter(|it| !it.is_empty()).map(path_from);
+        // the number of mounts, then each one's WSL and Windows paths, or `-` if they're not cached
+        let mounts = match fields.next()? {
+            b"-" => None,
+            len => {
+                let len = std::str::from_utf8(len).ok()?.parse::<usize>().ok()?;
+                let mut mounts = Vec::with_capacity(len);
+                for _ in 0..len {
+                    mounts.push(DrvFsMountPoint {
+                        wsl: path_from(fields.next()?),
+                        win: path_from(fields.next()?),
+                    });
+                }
+                Some(mounts)
+            }
+        };
+        Some(Entries {
+            windows_store_root,
+            mounts,
+        })
+    }
+    
+    /// The cached Windows Store root, unless the cache is stale.
+    pub fn windows_store_root(&self) -> Option<PathBuf> {
+        self.read()?.windows_store_root
+    }
+    
+    pub fn set_windows_store_root(&self, path: &Path) {
+        let mut entries = self.read().unwrap_or_default();
+        entries.windows_store_root = Some(path.to_path_buf());
+        // it's only a cache, so failing to write it isn't an error
+        let _ = self.write(&entries);
+    }
+    
+    /// The cached drive mounts, unless the cache is stale.
+    pub fn mounts(&self) -> Option<Vec<DrvFsMountPoint>> {
+        self.read()?.mounts
+    }
+    
+    pub fn set_mounts(&self, mounts: &[DrvFsMountPoint]) {
+        let mut entries = self.read().unwrap_or_default();
+        entries.mounts = Some(mounts.to_vec());
+        let _ = self.write(&entries);
+    }
+    
+    fn write(&self, entries: &Entries) -> io::Result<()> {
+        let CacheKey { boot_id, distro, mounts_hash } = &self.key;
+        let windows_store_root = entries.windows_store_root.as_deref().unwrap_or_else(|| Path::new(""));
+        let mounts_len = match &entries.mounts {
+            Some(mounts) => mounts.len().to_string(),
+            None => "-".into(),
+        };
+        let mut fields = vec![
+            VERSION,
+            boot_id,
+            distro,
+            mounts_hash,
+            windows_store_root.as_os_str().as_bytes(),
+            mounts_len.as_bytes(),
+        ];
+        for mount in entries.mounts.iter().flatten() {
+            fields.push(mount.wsl.as_os_str().as_bytes());
+            fields.push(mount.win.as_os_str().as_bytes());
+        }
+        let mut contents = Vec::new();
+        for field in fields {
+            contents.extend_from_slice(field);
+            contents.push(0);
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // write then rename so concurrent readers never see a partial file
+        let tmp_path = self.path.with_extension(format!("tmp.{}", process::id()));
+        let mut tmp = fs::File::create(&tmp_path)?;
+        tmp.write_all(&contents)?;
+        drop(tmp);
+        let renamed = fs::rename(&tmp_path, &self.path);
+        if renamed.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        renamed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn mount(wsl: &str, win: &str) -> DrvFsMountPoint {
+        DrvFsMountPoint {
+            wsl: wsl.into(),
+            win: win.into(),
+        }
+    }
+    
+    #[test]
+    fn stable_hash() {
+        // the published FNV-1a test vectors
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(CacheKey::new(b"id\n", b"d", b"").mounts_hash, b"cbf29ce484222325");
+    }
+    
+    #[test]
+    fn hits() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = CacheKey::new(b"boot", b"distro", b"C:\\ /mnt/c drvfs rw 0 0\n");
+        let cache = Cache::with_key(dir.path(), key.clone());
+        assert_eq!(cache.windows_store_root(), None);
+        assert_eq!(cache.mounts(), None);
+        
+        cache.set_windows_store_root(Path::new("Users/me/rootfs"));
+        cache.set_mounts(&[mount("/mnt/c", "C:\\"), mount("/mnt/d", "D:")]);
+        // a new process with the same key
+        let cache = Cache::with_key(dir.path(), key);
+        assert_eq!(cache.windows_store_root(), Some(PathBuf::from("Users/me/rootfs")));
+        assert_eq!(cache.mounts(), Some(vec![mount("/mnt/c", "C:\\"), mount("/mnt/d", "D:")]));
+        
+        cache.set_mounts(&[]);
+        assert_eq!(cache.mounts(), Some(vec![]));
+        assert!(cache.windows_store_root().is_some());
+    }
+    
+    #[test]
+    fn invalidation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mounts = b"C:\\ /mnt/c drvfs rw 0 0\n";
+        let cache = Cache::with_key(dir.path(), CacheKey::new(b"boot", b"distro", mounts));
+        cache.set_windows_store_root(Path::new("rootfs"));
+        cache.set_mounts(&[mount("/mnt/c", "C:")]);
+        
+        let stale = [
+            CacheKey::new(b"reboot", b"distro", mounts),
+            CacheKey::new(b"boot", b"other", mounts),
+            CacheKey::new(b"boot", b"distro", b"D:\\ /mnt/d drvfs rw 0 0\n"),
+        ];
+        for key in stale {
+            let cache = Cache::with_key(dir.path(), key);
+            assert_eq!(cache.windows_store_root(), None);
+            assert_eq!(cache.mounts(), None);
+        }
+        
+        // an older format is a miss, too
+        fs::write(dir.path().join("root"), b"wslpath-cache-1\0boot\0distro\0").unwrap();
+        assert_eq!(cache.windows_store_root(), None);
+    }
+}
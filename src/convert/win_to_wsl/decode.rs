@@ -1,21 +1,26 @@
 use optional::Optioned;
 
+use crate::convert::path_sep::WindowsPathSep;
+use crate::convert::scan::Specials;
 use crate::convert::windows_file_name_char::{IllegalWindowsFileNameCharError, WindowsFileNameCharType};
 
-fn check_char(c: u8, i: usize) -> Result<u8, IllegalWindowsFileNameCharError> {
-    use WindowsFileNameCharType::*;
-    let char_type: WindowsFileNameCharType = c.into();
-    match char_type {
-        Legal | Slash => Ok(c),
-        _ => Err((c, i).into()),
-    }
-}
+/// the bytes that [`path`] has to do something with:
+/// control and reserved chars (`"*:<>?|`), which are illegal,
+/// `\`, which is either a path sep or illegal,
+/// and 239, which starts every encoded codepoint
+const SPECIAL: [u8; 9] = *b"\"*:<>?|\\\xEF";
 
-fn check_path(path: &[u8]) -> Result<(), IllegalWindowsFileNameCharError> {
-    for (i, c) in path.iter().enumerate() {
-        check_char(*c, i)?;
+/// [`SPECIAL`] plus `/`, which is illegal when `\` is the path sep,
+/// so that it's found in the same scan as everything else
+const SPECIAL_WITH_SLASH: [u8; 10] = *b"\"*:<>?|\\\xEF/";
+
+/// whether decoding [`path`] would leave it as is,
+/// i.e., it has no seps to convert, no encoded codepoints, and no illegal chars
+pub fn is_unchanged(path: &[u8], sep: WindowsPathSep) -> bool {
+    match sep {
+        WindowsPathSep::Slash => Specials::new(path, SPECIAL).next().is_none(),
+        WindowsPathSep::BackSlash => Specials::new(path, SPECIAL_WITH_SLASH).next().is_none(),
     }
-    Ok(())
 }
 
 /// decode a Windows WSL path codepoint
@@ -28,7 +33,8 @@ pub fn codepoint(codepoint: [u8; 3]) -> Optioned::<u8> {
     let [a, b, c] = codepoint;
     use WindowsFileNameCharType::*;
     let none = Optioned::none();
-    if a != 239 {
+    // c has to be a continuation byte, or it's not an encoded codepoint
+    if a != 239 || !(128..=191).contains(&c) {
         return none;
     }
     match b {
@@ -51,29 +57,55 @@ pub fn codepoint(codepoint: [u8; 3]) -> Optioned::<u8> {
 }
 
 /// decode a Windows WSL path
+/// converting each Windows path sep to / if [`sep`] is `\`
+/// and failing on a `/` if it's not the path sep
+/// this is done in a single pass, copying the runs of bytes in between special ones as is
 /// buf should be pre-reserved by path.len() here
-pub fn path(path: &[u8], buf: &mut Vec<u8>) -> Result<(), IllegalWindowsFileNameCharError> {
-    check_path(path)?;
+pub fn path(path: &[u8], sep: WindowsPathSep, buf: &mut Vec<u8>) -> Result<(), IllegalWindowsFileNameCharError> {
+    match sep {
+        WindowsPathSep::Slash => decode(path, Specials::new(path, SPECIAL), sep, buf),
+        WindowsPathSep::BackSlash => decode(path, Specials::new(path, SPECIAL_WITH_SLASH), sep, buf),
+    }
+}
+
+/// [`path`], given the indices of the special bytes in it
+fn decode(
+    path: &[u8],
+    specials: impl Iterator<Item = usize>,
+    sep: WindowsPathSep,
+    buf: &mut Vec<u8>,
+) -> Result<(), IllegalWindowsFileNameCharError> {
     // we're decoding multi-byte codepoints to bytes, so this is an overestimate
     buf.reserve(path.len());
-    let mut i = 0;
-    while i + 2 < path.len() {
-        // '\f000' is 3-bytes
-        let a = path[i + 0];
-        let b = path[i + 1];
-        let c = path[i + 2];
-        let d= match codepoint([a, b, c]).into_option() {
-            Some(d) => {
-                i += 3;
-                d
-            },
-            None => {
-                i += 1;
-                a
-            },
-        };
-        buf.push(d);
+    // path[..copied] has already been decoded
+    let mut copied = 0;
+    for i in specials {
+        if i < copied {
+            // inside an already decoded codepoint
+            continue;
+        }
+        buf.extend_from_slice(&path[copied..i]);
+        copied = i + 1;
+        let c = path[i];
+        match c {
+            239 => {
+                // '\f000' is 3-bytes
+                let d = match path.get(i..i + 3) {
+                    Some(&[a, b, c]) => codepoint([a, b, c]).into_option(),
+                    _ => None,
+                };
+                match d {
+                    Some(d) => {
+                        copied = i + 3;
+                        buf.push(d);
+                    }
+                    None => buf.push(c),
+                }
+            }
+            b'\\' if matches!(sep, WindowsPathSep::BackSlash) => buf.push(b'/'),
+            _ => return Err((c, i).into()),
+        }
     }
-    buf.extend_from_slice(&path[i..]);
+    buf.extend_from_slice(&path[copied..]);
     Ok(())
 }
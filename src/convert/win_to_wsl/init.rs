@@ -1,14 +1,22 @@
 use std::ffi::OsString;
 use std::io;
-use std::os::unix::ffi::OsStringExt;
+use std::io::Read;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
+use once_cell::sync::OnceCell;
 use thiserror::Error;
+use wait_timeout::ChildExt;
 
 use crate::convert::path_sep::WindowsPathSep;
-use crate::convert::wsl::{get_unc_root, NotWslError, DrvFsMountPoint, get_drvfs_mount_points, MountError};
+use crate::convert::win_to_wsl::cache::Cache;
+use crate::convert::wsl::{get_unc_root, DistroNameError, DrvFsMountPoint, get_drvfs_mount_points, MountError, check_interop, InteropError, WslVersion, get_wsl_version};
+use crate::util::lazy::LazyInit;
 
 #[derive(Error, Debug)]
 #[error("Windows environment variable lookup failed for {var}")]
@@ -31,6 +39,8 @@ pub enum WindowsStoreRootLookupError {
     #[error("not found")]
     NotFound,
     #[error(transparent)]
+    Interop(#[from] InteropError),
+    #[error(transparent)]
     WindowsEnvVar(#[from] WindowsEnvVarError),
     #[error("/mnt/c/\"%USERNAME%/AppData/Local\" not found")]
     LocalAppDataNotFound(io::Error),
@@ -41,20 +51,56 @@ pub enum WindowsStoreRootLookupError {
 #[derive(Error, Debug)]
 pub enum ConvertOptionsError {
     #[error(transparent)]
-    NotWsl(#[from] NotWslError),
+    DistroName(#[from] DistroNameError),
     #[error("WSL root not found in Windows Store packages")]
     WindowsStoreRootLookup(#[from] WindowsStoreRootLookupError),
     #[error(transparent)]
     Mount(#[from] MountError),
 }
 
-fn get_windows_env_var(var: &str) -> Result<OsString, WindowsEnvVarError> {
+/// Run a Windows command, killing it if it hasn't exited within [`timeout`],
+/// since `cmd.exe` can hang instead of failing when interop is broken.
+/// Its stdout is read on another thread while waiting,
+/// so it can't block on a full pipe before it exits.
+fn windows_command_output(command: &mut Command, timeout: Duration) -> io::Result<(ExitStatus, Vec<u8>)> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let pipe = child.stdout.take();
+    let reader = thread::spawn(move || -> io::Result<Vec<u8>> {
+        let mut stdout = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut stdout)?;
+        }
+        Ok(stdout)
+    });
+    let status = match child.wait_timeout(timeout)? {
+        Some(status) => status,
+        None => {
+            // already timed out, so just try our best to clean up,
+            // and let the reader finish on its own once the pipe closes
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Windows process didn't exit within {:?}", timeout),
+            ));
+        }
+    };
+    let stdout = reader
+        .join()
+        .map_err(|_| io::Error::other("reading Windows process output panicked"))??;
+    Ok((status, stdout))
+}
+
+fn get_windows_env_var(var: &str, timeout: Duration) -> Result<OsString, WindowsEnvVarError> {
     let win_cmd = format!("echo %{}%", var);
-    let output = Command::new("cmd.exe")
-        .args(&["/c", win_cmd.as_str()])
-        .output()
+    let mut command = Command::new("cmd.exe");
+    command.args(["/c", win_cmd.as_str()]);
+    let (status, stdout) = windows_command_output(&mut command, timeout)
         .map_err(WindowsEnvVarError::from(var))?;
-    let Output { status, stdout, stderr: _ } = output;
     if !status.success() {
         let code = status.code().unwrap_or_default();
         return Err(code)
@@ -69,14 +115,14 @@ fn get_windows_env_var(var: &str) -> Result<OsString, WindowsEnvVarError> {
     Ok(username)
 }
 
-fn get_windows_store_root() -> Result<PathBuf, WindowsStoreRootLookupError> {
+fn get_windows_store_root(timeout: Duration) -> Result<PathBuf, WindowsStoreRootLookupError> {
     use WindowsStoreRootLookupError::{LocalAppDataNotFound, IoError};
     let root_inode = Path::new("/")
         .metadata()
         .map_err(IoError)?
         .ino();
     let app_data_local = {
-        let username = get_windows_env_var("USERNAME")?;
+        let username = get_windows_env_var("USERNAME", timeout)?;
         let mut path = PathBuf::from("/mnt/c/Users");
         path.push(username);
         path.push("AppData/Local/Packages");
@@ -98,6 +144,23 @@ fn get_windows_store_root() -> Result<PathBuf, WindowsStoreRootLookupError> {
 pub struct Options {
     pub sep: WindowsPathSep,
     pub convert_root_loop: bool,
+    /// The Windows Store rootfs of this distro (`/mnt/c/Users/...`),
+    /// used instead of looking it up through `cmd.exe`.
+    pub windows_store_root: Option<PathBuf>,
+    /// How long to wait for a spawned Windows process like `cmd.exe`.
+    pub windows_timeout: Duration,
+    /// Look up the UNC root, Windows Store root and mounts only once a path needs them,
+    /// reporting any failures as errors converting that path.
+    /// Otherwise, look them up in [`Converter::new`](super::Converter::new).
+    pub lazy_init: bool,
+    /// The WSL distro name, instead of detecting it.
+    pub distro: Option<OsString>,
+    /// Convert relative Windows paths to relative WSL paths,
+    /// instead of failing to parse them.
+    pub keep_relative: bool,
+    /// Where to cache the Windows Store root and drive mounts across processes, if anywhere.
+    /// See [`cache::default_dir`](super::cache::default_dir).
+    pub cache_dir: Option<PathBuf>,
 }
 
 impl Default for Options {
@@ -105,31 +168,131 @@ impl Default for Options {
         Self {
             sep: WindowsPathSep::default(),
             convert_root_loop: true,
+            windows_store_root: None,
+            windows_timeout: Duration::from_secs(10),
+            lazy_init: true,
+            distro: None,
+            keep_relative: false,
+            cache_dir: None,
         }
     }
 }
 
 pub struct Root {
-    pub unc: OsString,
-    pub windows_store: Option<PathBuf>,
-    pub mounts: Vec<DrvFsMountPoint>,
+    version: OnceCell<Option<WslVersion>>,
+    unc: LazyInit<OsString, DistroNameError>,
+    windows_store: LazyInit<Option<PathBuf>, WindowsStoreRootLookupError>,
+    mounts: LazyInit<Vec<DrvFsMountPoint>, MountError>,
+}
+
+/// Find the Windows Store root relative to `/mnt/c/`, if we're fixing root loops.
+fn find_windows_store_root(options: &Options, version: Option<WslVersion>) -> Result<Option<PathBuf>, WindowsStoreRootLookupError> {
+    if !options.convert_root_loop {
+        return Ok(None);
+    }
+    let path = match &options.windows_store_root {
+        Some(path) => path.clone(),
+        // no need to spawn cmd.exe if there can't be a root loop anyways
+        None if version.is_some_and(|it| !it.has_root_loop()) => return Ok(None),
+        None => {
+            let cache = options.cache_dir
+                .as_deref()
+                .and_then(|dir| Cache::open(dir, options.distro.as_deref()));
+            match cache.as_ref().and_then(Cache::windows_store_root) {
+                Some(path) => path,
+                None => {
+                    check_interop()?;
+                    let path = get_windows_store_root(options.windows_timeout)?;
+                    if let Some(cache) = &cache {
+                        cache.set_windows_store_root(&path);
+                    }
+                    path
+                }
+            }
+        }
+    };
+    let relative = path
+        .strip_prefix("/mnt/c/")
+        .ok()
+        .map(Path::to_path_buf);
+    Ok(Some(relative.unwrap_or(path)))
+}
+
+/// Find the mounted Windows drives, from the cache if it's up to date.
+fn find_mounts(options: &Options, version: Option<WslVersion>) -> Result<Vec<DrvFsMountPoint>, MountError> {
+    let cache = options.cache_dir
+        .as_deref()
+        .and_then(|dir| Cache::open(dir, options.distro.as_deref()));
+    if let Some(mounts) = cache.as_ref().and_then(Cache::mounts) {
+        return Ok(mounts);
+    }
+    let mounts = get_drvfs_mount_points(version)?;
+    if let Some(cache) = &cache {
+        cache.set_mounts(&mounts);
+    }
+    Ok(mounts)
 }
 
 impl Root {
     pub fn new(options: &Options) -> Result<Self, ConvertOptionsError> {
+        if options.lazy_init {
+            return Ok(Self {
+                version: OnceCell::new(),
+                unc: LazyInit::new(),
+                windows_store: LazyInit::new(),
+                mounts: LazyInit::new(),
+            });
+        }
+        let version = get_wsl_version();
         Ok(Self {
-            unc: get_unc_root()?,
-            windows_store: match options.convert_root_loop {
-                true => {
-                    let path = get_windows_store_root()?
-                        .strip_prefix("/mnt/c/")
-                        .unwrap() // added /mnt/c/ as a literal, so it must be a prefix
-                        .to_path_buf();
-                    Some(path)
-                }
-                false => None,
-            },
-            mounts: get_drvfs_mount_points()?,
+            version: OnceCell::from(version),
+            unc: LazyInit::done(Ok(get_unc_root(options.distro.as_deref())?)),
+            // fixing root loops is optional,
+            // so don't make the whole converter unusable if we can't
+            windows_store: LazyInit::done(find_windows_store_root(options, version)),
+            mounts: LazyInit::done(Ok(find_mounts(options, version)?)),
         })
     }
+    
+    /// Whether we're on WSL1 or WSL2, which determines how to find everything else.
+    pub fn version(&self) -> Option<WslVersion> {
+        *self.version.get_or_init(get_wsl_version)
+    }
+    
+    pub fn unc(&self, options: &Options) -> Result<&OsString, Arc<DistroNameError>> {
+        self.unc.get_or_init(|| get_unc_root(options.distro.as_deref()))
+    }
+    
+    /// The Windows Store root relative to `/mnt/c/`,
+    /// or [`None`] if we're not fixing root loops.
+    pub fn windows_store(&self, options: &Options) -> Result<Option<&Path>, Arc<WindowsStoreRootLookupError>> {
+        self.windows_store
+            .get_or_init(|| find_windows_store_root(options, self.version()))
+            .map(Option::as_deref)
+    }
+    
+    /// Why root loops aren't being fixed even though [`Options::convert_root_loop`] is set.
+    /// This doesn't trigger the lookup if it hasn't happened yet.
+    pub fn windows_store_error(&self) -> Option<Arc<WindowsStoreRootLookupError>> {
+        self.windows_store.get()?.err()
+    }
+    
+    pub fn mounts(&self, options: &Options) -> Result<&[DrvFsMountPoint], Arc<MountError>> {
+        self.mounts
+            .get_or_init(|| find_mounts(options, self.version()))
+            .map(Vec::as_slice)
+    }
+    
+    /// Where [`drive`] is mounted, like `/mnt/c` for `C:`,
+    /// or [`None`] if it's not mounted as a whole drive.
+    pub fn drive_mount(&self, options: &Options, drive: u8) -> Result<Option<&Path>, Arc<MountError>> {
+        let mount = self.mounts(options)?.iter().find(|mount| {
+            // drvfs mounts are like `C:` or `C:\`
+            match mount.win.as_os_str().as_bytes() {
+                [c, b':'] | [c, b':', b'\\' | b'/'] => c.eq_ignore_ascii_case(&drive),
+                _ => false,
+            }
+        });
+        Ok(mount.map(|mount| mount.wsl.as_path()))
+    }
 }
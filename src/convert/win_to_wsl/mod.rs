@@ -1,14 +1,19 @@
 use std::os::unix::ffi::OsStrExt;
+use std::sync::Arc;
 
+use memchr::memchr;
 use thiserror::Error;
 
+use crate::convert::ConvertErrorInfo;
 use crate::convert::path_sep::WindowsPathSep;
 use crate::convert::win_to_wsl::init::{ConvertOptionsError, Root};
-pub use crate::convert::win_to_wsl::init::Options;
+pub use crate::convert::win_to_wsl::init::{Options, WindowsStoreRootLookupError};
 use crate::convert::windows_file_name_char::IllegalWindowsFileNameCharError;
+use crate::convert::wsl::{DistroNameError, MountError, WslVersion};
 
 mod init;
 mod decode;
+pub mod cache;
 
 pub struct Converter {
     options: Options,
@@ -16,24 +21,35 @@ pub struct Converter {
 }
 
 impl WindowsPathSep {
+    /// check that the other path sep isn't used,
+    /// since if we're accepting \ as the Windows sep, / is an illegal filename char
+    fn check_sep(&self, path: &[u8]) -> Result<(), IllegalWindowsFileNameCharError> {
+        use WindowsPathSep::*;
+        match self {
+            Slash => Ok(()),
+            BackSlash => match memchr(Slash.value(), path) {
+                Some(i) => Err((Slash.value(), i).into()),
+                None => Ok(()),
+            },
+        }
+    }
+    
     /// convert path sep to to posix path sep
     /// if we're accepting / as the Windows sep, then do nothing
     /// if we're accepting \ as the Windows sep, need to replace them w/ /
-    fn convert_sep(&self, path: &mut [u8]) -> Result<(), IllegalWindowsFileNameCharError> {
+    /// this is done byte-by-byte as we go so the input is never modified
+    fn normalize(&self, c: u8) -> u8 {
         use WindowsPathSep::*;
         match self {
-            Slash => {} // already all /,
-            BackSlash => {
-                for (i, c) in path.iter_mut().enumerate() {
-                    if *c == BackSlash.value() {
-                        *c = Slash.value();
-                    } else if *c == Slash.value() {
-                        return Err((*c, i).into());
-                    }
-                }
-            }
+            BackSlash if c == BackSlash.value() => Slash.value(),
+            _ => c,
         }
-        Ok(())
+    }
+    
+    /// [`<[u8]>::starts_with`], but comparing [`path`] after converting its seps to posix
+    fn starts_with_posix(&self, path: &[u8], prefix: &[u8]) -> bool {
+        path.len() >= prefix.len()
+            && path.iter().zip(prefix).all(|(c, p)| self.normalize(*c) == *p)
     }
 }
 
@@ -43,20 +59,53 @@ pub enum ConvertError {
     Parse,
     #[error(transparent)]
     IllegalFileNameChar(#[from] IllegalWindowsFileNameCharError),
+    #[error(transparent)]
+    DistroName(#[from] Arc<DistroNameError>),
+    #[error(transparent)]
+    Mount(#[from] Arc<MountError>),
+}
+
+impl ConvertErrorInfo for ConvertError {
+    fn kind(&self) -> &'static str {
+        use ConvertError::*;
+        match self {
+            Parse => "Parse",
+            IllegalFileNameChar(_) => "IllegalFileNameChar",
+            DistroName(_) => "DistroName",
+            Mount(_) => "Mount",
+        }
+    }
+    
+    fn index(&self) -> Option<usize> {
+        match self {
+            ConvertError::IllegalFileNameChar(e) => Some(e.index()),
+            _ => None,
+        }
+    }
 }
 
 impl Converter {
+    /// Whether we're on WSL1 or WSL2, if it can be detected.
+    pub fn wsl_version(&self) -> Option<WslVersion> {
+        self.root.version()
+    }
+    
+    /// If root loops aren't being fixed even though [`Options::convert_root_loop`] is set,
+    /// this is why, e.g., because WSL interop is disabled.
+    /// With [`Options::lazy_init`], this is only known once a path has needed it.
+    pub fn root_loop_warning(&self) -> Option<Arc<WindowsStoreRootLookupError>> {
+        self.root.windows_store_error()
+    }
+    
     fn try_fix_root_loop<'a>(&self, path: &'a [u8]) -> Option<&'a [u8]> {
+        // a failed lookup just means we don't fix root loops
         let root = self
             .root
-            .windows_store
-            .as_ref()?
+            .windows_store(&self.options)
+            .ok()??
             .as_os_str()
             .as_bytes();
-        if path.len() < root.len() {
-            return None;
-        }
-        if &path[..root.len()] != root {
+        if !self.options.sep.starts_with_posix(path, root) {
             return None;
         }
         Some(&path[root.len()..])
@@ -71,43 +120,85 @@ impl Converter {
             .unwrap_or(path)
     }
     
+    /// write where [`drive`] is mounted,
+    /// which is usually `/mnt/<drive>`, but can be changed in `/etc/wsl.conf` or `/etc/fstab`
+    fn push_drive(&self, drive: u8, buf: &mut Vec<u8>) -> Result<(), ConvertError> {
+        match self.root.drive_mount(&self.options, drive)? {
+            Some(mount) => buf.extend_from_slice(mount.as_os_str().as_bytes()),
+            None => {
+                buf.extend_from_slice(b"/mnt/");
+                buf.push(drive.to_ascii_lowercase());
+            }
+        }
+        Ok(())
+    }
+    
+    /// the error for a path that can't be parsed,
+    /// unless it has an illegal `/` in it, which is the more specific error
+    fn parse_error(&self, path: &[u8]) -> ConvertError {
+        match self.options.sep.check_sep(path) {
+            Err(e) => e.into(),
+            Ok(()) => ConvertError::Parse,
+        }
+    }
+    
+    /// decode the rest of [`path`] after its prefix has been converted,
+    /// checking the seps of the skipped prefix, since [`decode::path`] only checks what it decodes
+    fn decode_rest(&self, path: &[u8], rest: &[u8], buf: &mut Vec<u8>) -> Result<(), ConvertError> {
+        let sep = self.options.sep;
+        let skipped = path.len() - rest.len();
+        sep.check_sep(&path[..skipped])?;
+        decode::path(rest, sep, buf)
+            .map_err(|e| e.with_base_index(skipped))?;
+        Ok(())
+    }
+    
     /// do the actual path conversion,
-    /// assuming the posix path sep / is used
+    /// converting the Windows path sep to / as we go
     /// and not fixing root loops
     /// i.e., only convert prefix
-    fn raw_convert(&self, path: &[u8], buf: &mut Vec<u8>) -> Result<(), ConvertError> {
+    fn raw_convert(&self, original: &[u8], buf: &mut Vec<u8>) -> Result<(), ConvertError> {
+        let sep = self.options.sep;
+        let to_posix = |c| sep.normalize(c);
         let verbatim = b"//?/";
-        let path = if path.starts_with(verbatim) {
-            &path[verbatim.len()..]
+        let path = if sep.starts_with_posix(original, verbatim) {
+            &original[verbatim.len()..]
         } else {
-            path
+            original
         };
-        let unc_root = self.root.unc.as_bytes();
         match path {
-            _ if path.starts_with(unc_root) => {
-                let path = &path[unc_root.len()..];
-                buf.reserve(path.len());
-                decode::path(path, buf)
-                    .map_err(|e| e.with_base_index(unc_root.len()))?;
-            }
             [drive, b':'] => {
+                sep.check_sep(original)?;
                 buf.reserve("/mnt/c".len());
-                buf.extend_from_slice(b"/mnt/");
-                buf.push(drive.to_ascii_lowercase());
+                self.push_drive(*drive, buf)?;
             }
             [drive, b':', path @ ..] => {
-                if !path.first().contains(&&b'/') {
-                    return Err(ConvertError::Parse);
+                if path.first().map(|c| to_posix(*c)) != Some(b'/') {
+                    return Err(self.parse_error(original));
                 }
                 let path = self.fix_root_loop(path);
                 let len = path.len() + (b"/mnt/c".len() - b"C:".len());
                 buf.reserve(len);
-                buf.extend_from_slice(b"/mnt/");
-                buf.push(drive.to_ascii_lowercase());
-                decode::path(path, buf)
-                    .map_err(|e| e.with_base_index(b"C:/".len()))?;
+                self.push_drive(*drive, buf)?;
+                self.decode_rest(original, path, buf)?;
             }
-            _ => return Err(ConvertError::Parse),
+            [a, b, ..] if to_posix(*a) == b'/' && to_posix(*b) == b'/' => {
+                // an illegal `/` fails before the distro is looked up
+                sep.check_sep(&original[..original.len() - path.len() + 2])?;
+                // only look up the UNC root once we actually need it
+                let unc_root = self.root.unc(&self.options)?.as_bytes();
+                if !sep.starts_with_posix(path, unc_root) {
+                    return Err(self.parse_error(original));
+                }
+                let path = &path[unc_root.len()..];
+                buf.reserve(path.len());
+                self.decode_rest(original, path, buf)?;
+            }
+            [c, ..] if to_posix(*c) != b'/' && self.options.keep_relative => {
+                self.decode_rest(original, path, buf)?;
+            }
+            // not a UNC path, so don't look up the distro just to fail
+            _ => return Err(self.parse_error(original)),
         };
         Ok(())
     }
@@ -126,9 +217,17 @@ impl super::Converter for Converter {
         })
     }
     
-    fn convert_into_buf(&self, path: &mut [u8], buf: &mut Vec<u8>) -> Result<(), Self::Error> {
-        self.options.sep.convert_sep(path)?;
-        self.raw_convert(path, buf)?;
-        Ok(())
+    fn convert_slice_into_buf(&self, path: &[u8], buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        // an illegal `/` is found by the same scan that decodes the path
+        self.raw_convert(path, buf)
+    }
+    
+    fn is_unchanged(&self, path: &[u8]) -> bool {
+        // only relative paths without any seps to convert or chars to decode are left as is
+        let sep = self.options.sep;
+        self.options.keep_relative
+            && path.first().is_some_and(|c| sep.normalize(*c) != b'/')
+            && path.get(1) != Some(&b':')
+            && decode::is_unchanged(path, sep)
     }
 }
@@ -56,6 +56,11 @@ impl From<(u8, usize)> for IllegalWindowsFileNameCharError {
 }
 
 impl IllegalWindowsFileNameCharError {
+    /// The byte index of the illegal char in the path.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    
     pub fn with_base_index(self, i: usize) -> Self {
         Self {
             index: self.index + i,
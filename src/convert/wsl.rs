@@ -1,23 +1,105 @@
-use std::{env, io};
-use std::ffi::OsString;
-use std::os::unix::ffi::OsStringExt;
+use std::{env, fmt, fs, io};
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
 
 use thiserror::Error;
 use std::path::PathBuf;
-use proc_mounts::MountIter;
+use proc_mounts::{MountInfo, MountIter};
 use itertools::Itertools;
 
 #[derive(Error, Debug)]
 #[error("not running on WSL")]
 pub struct NotWslError {}
 
-pub fn get_wsl_distro_name() -> Result<OsString, NotWslError> {
-    env::var_os("WSL_DISTRO_NAME")
-        .ok_or(NotWslError {})
+#[derive(Error, Debug)]
+pub enum DistroNameError {
+    #[error(transparent)]
+    NotWsl(#[from] NotWslError),
+    #[error("running on WSL, but couldn't find the distro name (WSL_DISTRO_NAME isn't set)")]
+    Unknown,
+}
+
+const DISTRO_NAME_VAR: &[u8] = b"WSL_DISTRO_NAME";
+
+/// Whether we're running on WSL at all,
+/// even if the usual environment variables aren't set.
+pub fn is_wsl() -> bool {
+    env::var_os("WSL_INTEROP").is_some()
+        || Path::new(WSL_INTEROP_BINFMT).exists()
+        || fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|it| it.to_ascii_lowercase().contains("microsoft"))
+        .unwrap_or(false)
+}
+
+/// Find a non-empty `WSL_DISTRO_NAME` in a process's environment,
+/// read from `/proc/<pid>/environ`, where each variable is terminated by a NUL.
+fn distro_name_in_environ(environ: &[u8]) -> Option<OsString> {
+    environ
+        .split(|c| *c == 0)
+        .filter_map(|var| {
+            let value = var.strip_prefix(DISTRO_NAME_VAR)?.strip_prefix(b"=")?;
+            Some(OsStr::from_bytes(value).to_os_string())
+        })
+        .find(|it| !it.is_empty())
+}
+
+/// The parent pid in a process's `/proc/<pid>/stat`.
+fn parent_pid_in_stat(stat: &[u8]) -> Option<&str> {
+    // the command name is in parentheses and can contain anything, including spaces,
+    // so skip past the last ')' before splitting: `pid (comm) state ppid ...`
+    let fields = &stat[stat.iter().rposition(|c| *c == b')')? + 1..];
+    std::str::from_utf8(fields).ok()?.split_whitespace().nth(1)
+}
+
+/// Look for `WSL_DISTRO_NAME` in the environments of our ancestor processes and then of init,
+/// reading `/proc` files with [`read`].
+fn distro_name_from_procs<F>(read: F) -> Option<OsString>
+    where F: Fn(&str) -> Option<Vec<u8>> {
+    let environ_of = |pid: &str| distro_name_in_environ(&read(&format!("/proc/{}/environ", pid))?);
+    let mut pid = String::from("self");
+    // bound this in case of a cycle from pid reuse
+    for _ in 0..64 {
+        let stat = match read(&format!("/proc/{}/stat", pid)) {
+            Some(stat) => stat,
+            None => break,
+        };
+        pid = match parent_pid_in_stat(&stat) {
+            Some(ppid) if ppid != "0" => ppid.to_string(),
+            _ => break,
+        };
+        if let Some(name) = environ_of(&pid) {
+            return Some(name);
+        }
+    }
+    environ_of("1")
+}
+
+/// Get the name of the current WSL distro.
+///
+/// `WSL_DISTRO_NAME` isn't set under `sudo`, `env -i`, cron, systemd services, or ssh,
+/// so if it's missing, look for it in the environments of our ancestor processes and of init.
+pub fn get_wsl_distro_name() -> Result<OsString, DistroNameError> {
+    if let Some(name) = env::var_os("WSL_DISTRO_NAME").filter(|it| !it.is_empty()) {
+        return Ok(name);
+    }
+    if let Some(name) = distro_name_from_procs(|path| fs::read(path).ok()) {
+        return Ok(name);
+    }
+    if !is_wsl() {
+        return Err(NotWslError {}.into());
+    }
+    Err(DistroNameError::Unknown)
 }
 
-pub fn get_unc_root() -> Result<OsString, NotWslError> {
-    let mut distro = get_wsl_distro_name()?.into_vec();
+/// Get the `//wsl$/<distro>` UNC root,
+/// using [`distro`] as the distro name if given, or else [`get_wsl_distro_name`].
+pub fn get_unc_root(distro: Option<&OsStr>) -> Result<OsString, DistroNameError> {
+    let mut distro = match distro {
+        Some(distro) => distro.to_os_string(),
+        None => get_wsl_distro_name()?,
+    }.into_vec();
     let prefix = b"//wsl$/";
     let mut path = Vec::with_capacity(prefix.len() + distro.len());
     path.extend_from_slice(prefix);
@@ -26,6 +108,7 @@ pub fn get_unc_root() -> Result<OsString, NotWslError> {
     Ok(path)
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DrvFsMountPoint {
     pub wsl: PathBuf,
     pub win: PathBuf,
@@ -37,16 +120,267 @@ pub struct MountError {
     #[from] source: io::Error,
 }
 
-pub fn get_drvfs_mount_points() -> Result<Vec<DrvFsMountPoint>, MountError> {
+/// The drive a WSL2 9p mount is of, from its `aname=drvfs;path=C:\;...` option.
+/// Returns [`None`] if it's not a drive mount at all.
+fn get_9p_drvfs_path(mount: &MountInfo) -> Option<PathBuf> {
+    let drvfs_options = mount.options
+        .iter()
+        .find(|it| it.starts_with("aname=drvfs"))?;
+    let path = drvfs_options
+        .split(';')
+        .find_map(|it| it.strip_prefix("path="))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| mount.source.clone());
+    Some(path)
+}
+
+fn get_drvfs_mount_point(mount: MountInfo, version: Option<WslVersion>) -> Option<DrvFsMountPoint> {
+    use WslVersion::*;
+    let win = match (mount.fstype.as_str(), version) {
+        ("drvfs", None) | ("drvfs", Some(Wsl1)) => mount.source,
+        ("9p", None) | ("9p", Some(Wsl2)) => get_9p_drvfs_path(&mount)?,
+        _ => return None,
+    };
+    Some(DrvFsMountPoint {
+        wsl: mount.dest,
+        win,
+    })
+}
+
+/// Get the mounted Windows drives.
+/// These are drvfs mounts on WSL1, but 9p mounts on WSL2,
+/// so if [`version`] isn't known, look for both.
+pub fn get_drvfs_mount_points(version: Option<WslVersion>) -> Result<Vec<DrvFsMountPoint>, MountError> {
     let mut mounts = Vec::new();
     for mount in MountIter::new()? {
-        let mount = mount?;
-        if mount.fstype.as_str() == "drvfs" {
-            mounts.push(DrvFsMountPoint {
-                wsl: mount.dest,
-                win: mount.source,
-            })
+        if let Some(mount) = get_drvfs_mount_point(mount?, version) {
+            mounts.push(mount);
         }
     }
     Ok(mounts)
 }
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WslVersion {
+    Wsl1,
+    Wsl2,
+}
+
+impl WslVersion {
+    pub fn number(&self) -> u8 {
+        use WslVersion::*;
+        match self {
+            Wsl1 => 1,
+            Wsl2 => 2,
+        }
+    }
+    
+    /// Only WSL1 stores its rootfs in the Windows Store package directory,
+    /// creating a loop from `/mnt/c/` back to `/`.
+    /// WSL2 uses a VHD instead.
+    pub fn has_root_loop(&self) -> bool {
+        *self == WslVersion::Wsl1
+    }
+}
+
+impl Display for WslVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
+
+/// WSL1 kernels are like `4.4.0-19041-Microsoft`,
+/// while WSL2 kernels are like `5.15.90.1-microsoft-standard-WSL2`,
+/// where only WSL2's have a lowercase `microsoft`.
+fn wsl_version_in_kernel_release(release: &str) -> Option<WslVersion> {
+    if release.contains("WSL2") || release.contains("microsoft") {
+        Some(WslVersion::Wsl2)
+    } else if release.contains("Microsoft") {
+        Some(WslVersion::Wsl1)
+    } else {
+        None
+    }
+}
+
+fn get_wsl_version_from_kernel_release() -> Option<WslVersion> {
+    wsl_version_in_kernel_release(&fs::read_to_string("/proc/sys/kernel/osrelease").ok()?)
+}
+
+/// WSL1 mounts drives with drvfs and has an lxfs or wslfs root,
+/// while WSL2 mounts drives over 9p.
+fn get_wsl_version_from_mounts() -> Option<WslVersion> {
+    for mount in MountIter::new().ok()? {
+        let mount = mount.ok()?;
+        match mount.fstype.as_str() {
+            "drvfs" | "lxfs" | "wslfs" => return Some(WslVersion::Wsl1),
+            "9p" if get_9p_drvfs_path(&mount).is_some() => return Some(WslVersion::Wsl2),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Only WSL2 talks to Windows over an interop socket in `WSL_INTEROP`.
+/// Its absence doesn't mean anything, though.
+fn get_wsl_version_from_interop() -> Option<WslVersion> {
+    env::var_os("WSL_INTEROP").map(|_| WslVersion::Wsl2)
+}
+
+/// Detect whether we're running on WSL1 or WSL2.
+/// Returns [`None`] if we're not on WSL or it can't be determined.
+pub fn get_wsl_version() -> Option<WslVersion> {
+    get_wsl_version_from_kernel_release()
+        .or_else(get_wsl_version_from_mounts)
+        .or_else(get_wsl_version_from_interop)
+}
+
+const WSL_INTEROP_BINFMT: &str = "/proc/sys/fs/binfmt_misc/WSLInterop";
+const WSL_CONF: &str = "/etc/wsl.conf";
+
+#[derive(Error, Debug)]
+pub enum InteropError {
+    #[error("WSL interop is disabled by `[interop] enabled=false` in {}", WSL_CONF)]
+    DisabledInConfig,
+    #[error("`[interop] enabled={0}` in {} isn't true, false, 1, or 0", WSL_CONF)]
+    InvalidConfig(String),
+    #[error("WSL interop is not registered ({} is missing)", WSL_INTEROP_BINFMT)]
+    NotRegistered,
+    #[error("WSL interop is disabled in {}", WSL_INTEROP_BINFMT)]
+    Disabled,
+}
+
+/// Parse `[interop] enabled` out of the contents of `/etc/wsl.conf`, if it's set,
+/// returning the value as is if it's not `true`, `false`, `1`, or `0`.
+fn parse_interop_enabled(config: &str) -> Option<Result<bool, String>> {
+    let mut in_interop = false;
+    let mut enabled = None;
+    for line in config.lines() {
+        let line = match line.find(['#', ';']) {
+            Some(i) => &line[..i],
+            None => line,
+        }.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            in_interop = line[1..line.len() - 1].trim().eq_ignore_ascii_case("interop");
+            continue;
+        }
+        if !in_interop {
+            continue;
+        }
+        let mut kv = line.splitn(2, '=');
+        let (key, value) = match (kv.next(), kv.next()) {
+            (Some(key), Some(value)) => (key.trim(), value.trim()),
+            _ => continue,
+        };
+        if !key.eq_ignore_ascii_case("enabled") {
+            continue;
+        }
+        enabled = Some(match value {
+            "1" => Ok(true),
+            "0" => Ok(false),
+            _ if value.eq_ignore_ascii_case("true") => Ok(true),
+            _ if value.eq_ignore_ascii_case("false") => Ok(false),
+            _ => Err(value.to_string()),
+        });
+    }
+    enabled
+}
+
+/// Check whether Windows executables like `cmd.exe` can be run from here,
+/// so we don't have to spawn one just to find out it fails or hangs.
+///
+/// An `[interop] enabled` setting we can't parse is an error, too,
+/// so that it's reported instead of silently guessing what was meant.
+pub fn check_interop() -> Result<(), InteropError> {
+    let config = fs::read_to_string(WSL_CONF).unwrap_or_default();
+    match parse_interop_enabled(&config) {
+        Some(Ok(false)) => return Err(InteropError::DisabledInConfig),
+        Some(Err(value)) => return Err(InteropError::InvalidConfig(value)),
+        Some(Ok(true)) | None => {}
+    }
+    let binfmt = Path::new(WSL_INTEROP_BINFMT);
+    let status = fs::read_to_string(binfmt)
+        .map_err(|_| InteropError::NotRegistered)?;
+    match status.lines().next() {
+        Some("enabled") => Ok(()),
+        _ => Err(InteropError::Disabled),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    
+    use super::*;
+    
+    #[test]
+    fn environ() {
+        assert_eq!(distro_name_in_environ(b"A=1\0WSL_DISTRO_NAME=Ubuntu\0B=2\0"), Some("Ubuntu".into()));
+        assert_eq!(distro_name_in_environ(b"WSL_DISTRO_NAME=\0WSL_DISTRO_NAME=Debian"), Some("Debian".into()));
+        assert_eq!(distro_name_in_environ(b"XWSL_DISTRO_NAME=a\0WSL_DISTRO_NAMES=b\0"), None);
+        assert_eq!(distro_name_in_environ(b""), None);
+    }
+    
+    #[test]
+    fn stat() {
+        assert_eq!(parent_pid_in_stat(b"42 (bash) S 7 42 42 0"), Some("7"));
+        assert_eq!(parent_pid_in_stat(b"42 (a) b (c)) R 1 42"), Some("1"));
+        assert_eq!(parent_pid_in_stat(b"42 bash S 7"), None);
+    }
+    
+    #[test]
+    fn ancestors() {
+        let procs = |files: &[(&str, &[u8])]| {
+            let files = files
+                .iter()
+                .map(|(path, contents)| (path.to_string(), contents.to_vec()))
+                .collect::<HashMap<_, _>>();
+            distro_name_from_procs(|path| files.get(path).cloned())
+        };
+        let chain: &[(&str, &[u8])] = &[
+            ("/proc/self/stat", b"30 (wslpath) R 20 0"),
+            ("/proc/20/stat", b"20 (sudo) S 10 0"),
+            ("/proc/20/environ", b"HOME=/root\0"),
+            ("/proc/10/stat", b"10 (bash) S 1 0"),
+            ("/proc/10/environ", b"WSL_DISTRO_NAME=Ubuntu\0"),
+            ("/proc/1/environ", b"WSL_DISTRO_NAME=Init\0"),
+        ];
+        // the closest ancestor wins
+        assert_eq!(procs(chain), Some("Ubuntu".into()));
+        assert_eq!(procs(&chain[..4]), None);
+        // then init
+        let mut without_bash = chain[..3].to_vec();
+        without_bash.push(("/proc/1/environ", b"WSL_DISTRO_NAME=Init\0"));
+        assert_eq!(procs(&without_bash), Some("Init".into()));
+        // even if our own stat can't be read
+        assert_eq!(procs(&chain[5..]), Some("Init".into()));
+        assert_eq!(procs(&[]), None);
+    }
+    
+    #[test]
+    fn kernel_release() {
+        let version = |release| wsl_version_in_kernel_release(release).map(|it| it.number());
+        assert_eq!(version("4.4.0-19041-Microsoft\n"), Some(1));
+        assert_eq!(version("4.19.128-microsoft-standard"), Some(2));
+        assert_eq!(version("5.15.90.1-microsoft-standard-WSL2\n"), Some(2));
+        assert_eq!(version("6.6.36.3-microsoft-standard-WSL2+"), Some(2));
+        assert_eq!(version("6.8.0-45-generic"), None);
+        // a custom WSL2 kernel without the usual suffix
+        assert_eq!(version("5.10.16.3-microsoft"), Some(2));
+    }
+    
+    #[test]
+    fn interop_config() {
+        let parse = |config: &str| parse_interop_enabled(config);
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("[interop]\nappendWindowsPath=false\n"), None);
+        assert_eq!(parse("[automount]\nenabled=false\n"), None);
+        assert_eq!(parse("[interop]\nenabled=false\n"), Some(Ok(false)));
+        assert_eq!(parse(" [ Interop ]\n  Enabled = FALSE # off\n"), Some(Ok(false)));
+        assert_eq!(parse("[interop]\nenabled=0\n"), Some(Ok(false)));
+        assert_eq!(parse("[interop]\nenabled=1\n"), Some(Ok(true)));
+        assert_eq!(parse("[interop]\nenabled=True\n"), Some(Ok(true)));
+        assert_eq!(parse("[interop]\nenabled=no\n"), Some(Err("no".into())));
+        // the last one wins
+        assert_eq!(parse("[interop]\nenabled=false\n[boot]\n[interop]\nenabled=true\n"), Some(Ok(true)));
+    }
+}
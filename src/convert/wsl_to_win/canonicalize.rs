@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// `ENOTDIR`, so errors read the same as from [`Path::canonicalize`].
+const NOT_A_DIRECTORY: i32 = 20;
+
+/// `ELOOP`, so errors read the same as from [`Path::canonicalize`].
+const TOO_MANY_LINKS: i32 = 40;
+
+/// How many symlinks can be followed in one path, like `realpath`.
+const MAX_LINKS: u32 = 40;
+
+/// Limits on how much a [`CanonicalizeCache`] remembers.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CacheLimits {
+    /// The most directories to remember at once.
+    /// When there'd be more, expired ones are dropped, and then everything if that's not enough.
+    pub max_dirs: Option<usize>,
+    /// How long a canonicalized directory is trusted for,
+    /// in case symlinks are changed while converting.
+    pub max_age: Option<Duration>,
+}
+
+impl CacheLimits {
+    /// A [`max_dirs`](Self::max_dirs) that keeps memory bounded when converting whole trees,
+    /// since each directory is only a path or two, but the number of them isn't.
+    pub const DEFAULT_MAX_DIRS: usize = 100_000;
+}
+
+struct Entry {
+    canonical: PathBuf,
+    inserted: Instant,
+}
+
+/// A path that's been resolved.
+struct Resolved {
+    path: PathBuf,
+    is_dir: bool,
+}
+
+/// Canonicalizes paths like [`Path::canonicalize`],
+/// but one component at a time, remembering each directory it resolves.
+/// Paths in the same directories then only cost one syscall for their last component,
+/// instead of redoing every parent directory.
+pub struct CanonicalizeCache {
+    limits: CacheLimits,
+    dirs: RwLock<HashMap<PathBuf, Entry>>,
+}
+
+impl CanonicalizeCache {
+    pub fn new(limits: CacheLimits) -> Self {
+        Self {
+            limits,
+            dirs: Default::default(),
+        }
+    }
+    
+    /// Canonicalize [`path`], resolving relative paths against the current directory.
+    pub fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let absolute;
+        let path = match path.is_relative() {
+            true => {
+                absolute = std::env::current_dir()?.join(path);
+                absolute.as_path()
+            }
+            false => path,
+        };
+        let mut links = 0;
+        let resolved = self.resolve(path, &mut links)?;
+        // components drop a trailing /, but it still means path has to be a directory
+        if !resolved.is_dir && path.as_os_str().as_bytes().ends_with(b"/") {
+            return Err(io::Error::from_raw_os_error(NOT_A_DIRECTORY));
+        }
+        Ok(resolved.path)
+    }
+    
+    /// The number of directories currently remembered.
+    pub fn len(&self) -> usize {
+        self.dirs.read().unwrap().len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// Forget all remembered directories.
+    pub fn clear(&self) {
+        self.dirs.write().unwrap().clear();
+    }
+    
+    fn is_expired(&self, entry: &Entry) -> bool {
+        self.limits.max_age.is_some_and(|max_age| entry.inserted.elapsed() > max_age)
+    }
+    
+    fn get(&self, dir: &Path) -> Option<PathBuf> {
+        let dirs = self.dirs.read().unwrap();
+        let entry = dirs.get(dir)?;
+        match self.is_expired(entry) {
+            true => None,
+            false => Some(entry.canonical.clone()),
+        }
+    }
+    
+    fn insert(&self, dir: &Path, canonical: &Path) {
+        let mut dirs = self.dirs.write().unwrap();
+        if let Some(max_dirs) = self.limits.max_dirs {
+            if dirs.len() >= max_dirs && !dirs.contains_key(dir) {
+                dirs.retain(|_, entry| !self.is_expired(entry));
+                if dirs.len() >= max_dirs {
+                    dirs.clear();
+                }
+            }
+            if max_dirs == 0 {
+                return;
+            }
+        }
+        dirs.insert(dir.to_path_buf(), Entry {
+            canonical: canonical.to_path_buf(),
+            inserted: Instant::now(),
+        });
+    }
+    
+    /// Canonicalize the absolute [`dir`], which must be a directory.
+    fn resolve_dir(&self, dir: &Path, links: &mut u32) -> io::Result<PathBuf> {
+        if let Some(canonical) = self.get(dir) {
+            return Ok(canonical);
+        }
+        let resolved = self.resolve(dir, links)?;
+        if !resolved.is_dir {
+            return Err(io::Error::from_raw_os_error(NOT_A_DIRECTORY));
+        }
+        self.insert(dir, &resolved.path);
+        Ok(resolved.path)
+    }
+    
+    /// Canonicalize the absolute [`path`] by resolving its parent directory
+    /// and then only its last component.
+    fn resolve(&self, path: &Path, links: &mut u32) -> io::Result<Resolved> {
+        let mut components = path.components();
+        let last = components.next_back();
+        let parent = components.as_path();
+        let name = match last {
+            None | Some(Component::RootDir) | Some(Component::Prefix(_)) => {
+                return Ok(Resolved {
+                    path: PathBuf::from("/"),
+                    is_dir: true,
+                });
+            }
+            Some(Component::CurDir) => {
+                return Ok(Resolved {
+                    path: self.resolve_dir(parent, links)?,
+                    is_dir: true,
+                });
+            }
+            Some(Component::ParentDir) => {
+                // parent is already canonical, so .. is just its parent
+                let parent = self.resolve_dir(parent, links)?;
+                return Ok(Resolved {
+                    path: parent.parent().unwrap_or(&parent).to_path_buf(),
+                    is_dir: true,
+                });
+            }
+            Some(Component::Normal(name)) => name,
+        };
+        let parent = self.resolve_dir(parent, links)?;
+        let path = parent.join(name);
+        let metadata = path.symlink_metadata()?;
+        if !metadata.file_type().is_symlink() {
+            return Ok(Resolved {
+                path,
+                is_dir: metadata.is_dir(),
+            });
+        }
+        *links += 1;
+        if *links > MAX_LINKS {
+            return Err(io::Error::from_raw_os_error(TOO_MANY_LINKS));
+        }
+        // an absolute target replaces parent
+        let target = parent.join(path.read_link()?);
+        self.resolve(&target, links)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::convert::path_sep::WindowsPathSep;
+use crate::convert::scan::Specials;
+use crate::convert::windows_file_name_char::WindowsFileNameCharType;
+use crate::convert::wsl_to_win::ConvertError;
+
+/// encode a byte of a WSL path for Windows
+/// the inverse of `win_to_wsl::decode::codepoint`
+/// chars that are legal in Linux filenames but illegal in Windows filenames
+/// are encoded as `'\f000' + c` in UTF-8, which is 3 bytes
+pub fn byte(c: u8, buf: &mut Vec<u8>) {
+    use WindowsFileNameCharType::*;
+    match WindowsFileNameCharType::from(c) {
+        Low | Reserved | BackSlash => {
+            let (b, c) = if c < 64 {
+                (128, c + 128)
+            } else {
+                (129, c + 64)
+            };
+            buf.extend_from_slice(&[239, b, c]);
+        }
+        _ => buf.push(c),
+    }
+}
+
+/// the bytes that [`path`] has to do something with:
+/// control and reserved chars (`"*:<>?|`) and `\`, which are encoded,
+/// and `/`, which is converted to the Windows path sep
+const SPECIAL: [u8; 9] = *b"\"*:<>?|\\/";
+
+/// whether encoding [`path`] would leave it as is
+pub fn is_unchanged(path: &[u8], sep: WindowsPathSep) -> bool {
+    Specials::new(path, SPECIAL).all(|i| path[i] == b'/' && sep.value() == b'/')
+}
+
+/// encode a WSL path for Windows, replacing `/` with [`sep`]
+/// this is done in a single pass, copying the runs of bytes in between special ones as is,
+/// which also finds any null byte, since it's a control char
+pub fn path(path: &[u8], sep: WindowsPathSep, buf: &mut Vec<u8>) -> Result<(), ConvertError> {
+    // most paths don't have any chars that need encoding
+    buf.reserve(path.len());
+    // path[..copied] has already been encoded
+    let mut copied = 0;
+    for i in Specials::new(path, SPECIAL) {
+        buf.extend_from_slice(&path[copied..i]);
+        match path[i] {
+            b'/' => buf.push(sep.value()),
+            0 => return Err(ConvertError::NullByte { index: i }),
+            c => byte(c, buf),
+        }
+        copied = i + 1;
+    }
+    buf.extend_from_slice(&path[copied..]);
+    Ok(())
+}
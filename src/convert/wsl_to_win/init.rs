@@ -1,18 +1,29 @@
+use std::ffi::OsString;
 use std::io;
 use std::path::PathBuf;
 
 use thiserror::Error;
 
 use crate::convert::path_sep::WindowsPathSep;
-use crate::convert::wsl::{get_unc_root, NotWslError, DrvFsMountPoint, get_drvfs_mount_points, MountError};
+use crate::convert::wsl_to_win::canonicalize::CacheLimits;
+use crate::convert::wsl::{get_unc_root, DistroNameError, DrvFsMountPoint, get_drvfs_mount_points, MountError, WslVersion, get_wsl_version};
 
 pub struct Options {
     pub sep: WindowsPathSep,
     pub base_directory: Option<PathBuf>,
     pub canonicalize: bool,
+    /// The WSL distro name, instead of detecting it.
+    pub distro: Option<OsString>,
+    /// Convert relative paths to relative Windows paths,
+    /// instead of resolving them against [`base_directory`](Options::base_directory).
+    pub keep_relative: bool,
+    /// Remember canonicalized directories across paths, within these limits,
+    /// or canonicalize each path from scratch if [`None`].
+    pub canonicalize_cache: Option<CacheLimits>,
 }
 
 pub struct Root {
+    pub version: Option<WslVersion>,
     pub unc: PathBuf,
     pub mounts: Vec<DrvFsMountPoint>,
 }
@@ -28,7 +39,7 @@ pub enum WslPathError {
 #[derive(Error, Debug)]
 pub enum ConvertOptionsError {
     #[error(transparent)]
-    NotWsl(#[from] NotWslError),
+    DistroName(#[from] DistroNameError),
     #[error(transparent)]
     WslPath(#[from] WslPathError),
     #[error(transparent)]
@@ -51,11 +62,13 @@ impl Options {
 }
 
 impl Root {
-    pub fn new(_options: &Options) -> Result<Self, ConvertOptionsError> {
+    pub fn new(options: &Options) -> Result<Self, ConvertOptionsError> {
+        let version = get_wsl_version();
         Ok(Self {
-            unc: get_unc_root()
+            version,
+            unc: get_unc_root(options.distro.as_deref())
                 .map(PathBuf::from)?,
-            mounts: get_drvfs_mount_points()?,
+            mounts: get_drvfs_mount_points(version)?,
         })
     }
 }
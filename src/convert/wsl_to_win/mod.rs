@@ -1,15 +1,20 @@
+use std::borrow::Cow;
 use std::ffi::OsStr;
 use std::os::unix::ffi::OsStrExt;
-use std::os::unix::ffi::OsStringExt;
 use std::path::Path;
 
+use memchr::memchr;
 use thiserror::Error;
 
+use crate::convert::ConvertErrorInfo;
+use crate::convert::wsl::WslVersion;
+use crate::convert::wsl_to_win::canonicalize::CanonicalizeCache;
 use crate::convert::wsl_to_win::init::{ConvertOptionsError, Root, WslPathError};
 pub use crate::convert::wsl_to_win::init::Options;
 
 mod init;
 mod encode;
+pub mod canonicalize;
 
 #[derive(Error, Debug)]
 pub enum ConvertError {
@@ -19,66 +24,118 @@ pub enum ConvertError {
     NullByte { index: usize },
 }
 
+impl ConvertError {
+    /// offset the index of a [`ConvertError::NullByte`] by [`base_index`],
+    /// for when it was found in a suffix of the path
+    fn with_base_index(self, base_index: usize) -> Self {
+        match self {
+            ConvertError::NullByte { index } => ConvertError::NullByte { index: base_index + index },
+            e => e,
+        }
+    }
+}
+
+impl ConvertErrorInfo for ConvertError {
+    fn kind(&self) -> &'static str {
+        use ConvertError::*;
+        match self {
+            WslPath(WslPathError::NotAbsolute) => "NotAbsolute",
+            WslPath(WslPathError::Canonicalization(_)) => "Canonicalization",
+            NullByte { .. } => "NullByte",
+        }
+    }
+    
+    fn index(&self) -> Option<usize> {
+        match self {
+            ConvertError::NullByte { index } => Some(*index),
+            _ => None,
+        }
+    }
+}
+
 pub struct Converter {
     options: Options,
     root: Root,
+    canonicalize_cache: Option<CanonicalizeCache>,
+}
+
+/// strip [`prefix`] from [`path`] only if it's a whole number of path components,
+/// and then strip the leading `/` from what's left
+fn strip_dir_prefix<'a>(path: &'a [u8], prefix: &[u8]) -> Option<&'a [u8]> {
+    let rest = path.strip_prefix(prefix)?;
+    match rest {
+        [] => Some(rest),
+        [b'/', rest @ ..] => Some(rest),
+        _ => None,
+    }
 }
 
 impl Converter {
-    //
-//
-//     /// root contains the wsl unc prefix
-//     /// prefix is the absolute prefix of the path, w/o a trailing /
-//     /// path is the path relative to the prefix, w/o a leading /
-//     /// even if the given path was absolute,
-//     /// "/" is passed as the prefix and path is relativized
-//     fn convert_raw(root: &Root, prefix: &[u8], path: &[u8], buf: &mut Vec<u8>) {
-//         let mnt = b"/mnt/";
-//         if prefix.starts_with(mnt) {
-//             let prefix = &prefix[mnt.len()..];
-//             if prefix.len() >= mnt.len() + 2 {
-//                 prefix[mnt.len()]
-//             } else {
-//
-//             }
-//         }
-//         match prefix {
-//             mnt => {
-//
-//             },
-//             b"/mnt/" => {
-//                 // the trailing / here means the prefix contains the next path component
-//
-//             }
-//             b"/" => {
-//
-//             },
-//             b"/mnt" => {
-//
-//             },
-//         }
-//     }
-//
-    fn convert_raw(&self, _path: &mut [u8], _buf: &mut Vec<u8>) -> Result<(), ConvertError> {
-        todo!()
+    /// Whether we're on WSL1 or WSL2, if it can be detected.
+    pub fn wsl_version(&self) -> Option<WslVersion> {
+        self.root.version
+    }
+    
+    /// The canonicalized directories remembered so far, if they're being remembered.
+    pub fn canonicalize_cache(&self) -> Option<&CanonicalizeCache> {
+        self.canonicalize_cache.as_ref()
+    }
+    
+    /// make the path absolute, if it isn't already, and canonicalize it if we're canonicalizing
+    fn absolutize<'a>(&self, path: &'a Path) -> Result<Cow<'a, Path>, WslPathError> {
+        let path = match &self.options.base_directory {
+            Some(base) if path.is_relative() => Cow::Owned(base.join(path)),
+            _ => Cow::Borrowed(path),
+        };
+        if self.options.canonicalize {
+            // canonicalizing also makes it absolute
+            let path = match &self.canonicalize_cache {
+                Some(cache) => cache.canonicalize(&path)?,
+                None => path.canonicalize()?,
+            };
+            return Ok(Cow::Owned(path));
+        }
+        if path.is_relative() {
+            return Err(WslPathError::NotAbsolute);
+        }
+        Ok(path)
+    }
+    
+    /// convert an absolute path,
+    /// either to a path on a mounted Windows drive if it's under one,
+    /// or to a path under the `\\wsl$\<distro>` UNC root
+    fn convert_absolute(&self, path: &[u8], buf: &mut Vec<u8>) -> Result<(), ConvertError> {
+        let sep = self.options.sep;
+        let mount = self.root.mounts
+            .iter()
+            .filter_map(|mount| {
+                let rest = strip_dir_prefix(path, mount.wsl.as_os_str().as_bytes())?;
+                Some((mount, rest))
+            })
+            // the most specific mount point wins
+            .max_by_key(|(mount, _)| mount.wsl.as_os_str().len());
+        match mount {
+            Some((mount, rest)) => {
+                // drvfs mounts are like `C:` or `C:\`
+                let drive = mount.win.as_os_str().as_bytes();
+                let drive = match drive {
+                    [drive @ .., b'\\'] | [drive @ .., b'/'] => drive,
+                    drive => drive,
+                };
+                buf.reserve(drive.len() + 1 + rest.len());
+                buf.extend_from_slice(drive);
+                buf.push(sep.value());
+                encode::path(rest, sep, buf)
+                    .map_err(|e| e.with_base_index(path.len() - rest.len()))
+            }
+            None => {
+                let unc = self.root.unc.as_os_str().as_bytes();
+                buf.reserve(unc.len() + path.len());
+                encode::path(unc, sep, buf)?;
+                encode::path(path, sep, buf)
+            }
+        }
     }
-//
-//     fn convert_absolute_path_into_buf(&self, path: &mut [u8], buf: &mut Vec<u8>) -> Result<(), ConvertError> {
-//         if self.options.canonicalize {
-//             let path = path.canonicalize()
-//                 .map_err(WslPathError::Canonicalization)?;
-//             let mut path = path
-//                 .into_os_string()
-//                 .into_vec();
-//             self.convert_absolute_canonical_path_into_buf(path.as_mut_slice(), buf)?;
-//         } else {
-//             if !path.is_absolute() {
-//                 Err(WslPathError::NotAbsolute)?;
-//                 self.convert_absolute_canonical_path_into_buf(path_bytes, buf)?;
-//             }
-//         }
-//         Ok(())
-//     }
 }
 
 impl super::Converter for Converter {
@@ -89,63 +146,104 @@ impl super::Converter for Converter {
     fn new(mut options: Self::Options) -> Result<Self, Self::OptionsError> {
         options.init()?;
         let root = Root::new(&options)?;
+        let canonicalize_cache = options.canonicalize_cache
+            .filter(|_| options.canonicalize)
+            .map(CanonicalizeCache::new);
         Ok(Self {
             options,
             root,
+            canonicalize_cache,
         })
     }
     
-    fn convert_into_buf(&self, path_bytes: &mut [u8], buf: &mut Vec<u8>) -> Result<(), Self::Error> {
-        for (i, c) in path_bytes.iter().enumerate() {
-            if *c == 0 {
-                return Err(ConvertError::NullByte { index: i });
+    fn convert_slice_into_buf(&self, path_bytes: &[u8], buf: &mut Vec<u8>) -> Result<(), Self::Error> {
+        // otherwise, a null byte is found by the same scan that encodes the path,
+        // but canonicalizing it would fail first
+        if self.options.canonicalize {
+            if let Some(index) = memchr(0, path_bytes) {
+                return Err(ConvertError::NullByte { index });
             }
         }
         
-        let path = Path::new(OsStr::from_bytes(path_bytes.as_ref()));
+        let path = Path::new(OsStr::from_bytes(path_bytes));
+        if path.is_relative() && self.options.keep_relative {
+            return encode::path(path_bytes, self.options.sep, buf);
+        }
+        let absolute = self.absolutize(path)?;
+        let absolute = absolute.as_os_str().as_bytes();
+        // without canonicalizing, the original path is a suffix of the absolute one
+        self.convert_absolute(absolute, buf)
+            .map_err(|e| match e {
+                ConvertError::NullByte { index } => ConvertError::NullByte {
+                    index: index.saturating_sub(absolute.len() - path_bytes.len()),
+                },
+                e => e,
+            })
+    }
+    
+    fn is_unchanged(&self, path: &[u8]) -> bool {
+        // only kept relative paths that don't need encoding are left as is
+        self.options.keep_relative
+            && path.first().is_some_and(|c| *c != b'/')
+            && encode::is_unchanged(path, self.options.sep)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    
+    use crate::convert::path_sep::WindowsPathSep;
+    use crate::convert::wsl::DrvFsMountPoint;
+    
+    use super::*;
+    
+    fn mount(wsl: &str, win: &str) -> DrvFsMountPoint {
+        DrvFsMountPoint {
+            wsl: wsl.into(),
+            win: win.into(),
+        }
+    }
+    
+    /// mounts can't be faked through [`Converter::new`], since they're read from `/proc/mounts`
+    fn converter(sep: WindowsPathSep) -> Converter {
+        Converter {
+            options: Options {
+                sep,
+                base_directory: None,
+                canonicalize: false,
+                distro: None,
+                keep_relative: false,
+                canonicalize_cache: None,
+            },
+            root: Root {
+                version: None,
+                unc: PathBuf::from("//wsl$/test"),
+                mounts: vec![mount("/mnt/c", "C:\\"), mount("/mnt/d", "D:"), mount("/mnt/c/x", "E:\\")],
+            },
+            canonicalize_cache: None,
+        }
+    }
+    
+    fn convert(converter: &Converter, path: &str) -> String {
+        let mut buf = Vec::new();
+        converter.convert_absolute(path.as_bytes(), &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+    
+    #[test]
+    fn mounts() {
+        let converter = converter(WindowsPathSep::BackSlash);
+        assert_eq!(convert(&converter, "/mnt/c"), r"C:\");
+        assert_eq!(convert(&converter, "/mnt/c/Users/a"), r"C:\Users\a");
+        assert_eq!(convert(&converter, "/mnt/d/a:b"), "D:\\a\u{f03a}b");
+        // the most specific mount point wins
+        assert_eq!(convert(&converter, "/mnt/c/x/a"), r"E:\a");
+        // only whole components match a mount point
+        assert_eq!(convert(&converter, "/mnt/cc/a"), r"\\wsl$\test\mnt\cc\a");
+        assert_eq!(convert(&converter, "/mnt"), r"\\wsl$\test\mnt");
         
-        // let convert_absolute = || -> Result<(), ConvertError> {
-        //     self.convert_raw(path_bytes, buf)?;
-        //     Ok(())
-        // };
-        //
-        // let canonicalize_and_convert = || -> Result<(), ConvertError> {
-        //     let path = path.canonicalize()
-        //         .map_err(WslPathError::Canonicalization)?;
-        //     let mut path = path
-        //         .into_os_string()
-        //         .into_vec();
-        //     self.convert_raw(path.as_mut_slice(), buf)?;
-        //     Ok(())
-        // };
-        //
-        // if let Some(base_dir) = &self.options.base_directory {
-        //     if path.is_absolute() {
-        //         // if already absolute, don't relativize it
-        //         if self.options.canonicalize {
-        //             canonicalize_and_convert()?;
-        //         } else {
-        //             convert_absolute()?;
-        //         }
-        //     } else {
-        //         let path = base_dir.join(path);
-        //         if self.options.canonicalize {
-        //             todo!()
-        //         } else {
-        //             todo!()
-        //         }
-        //     }
-        // } else {
-        //     if self.options.canonicalize {
-        //         canonicalize_and_convert()?;
-        //     } else {
-        //         if !path.is_absolute() {
-        //             Err(WslPathError::NotAbsolute)?;
-        //         } else {
-        //             convert_absolute()?;
-        //         }
-        //     }
-        // }
-        Ok(())
+        let converter = self::converter(WindowsPathSep::Slash);
+        assert_eq!(convert(&converter, "/mnt/c/Users/a"), "C:/Users/a");
     }
 }
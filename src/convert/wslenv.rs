@@ -0,0 +1,302 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::convert::Converter;
+use crate::convert::path_list::{convert_list, EmptyEntryPolicy, FailedEntryPolicy, ListError, ListOptions, ListSyntax};
+use crate::util::enum_arg::EnumArg;
+
+/// The variable listing which variables are shared between WSL and Windows.
+pub const WSLENV: &str = "WSLENV";
+
+/// Which side an environment is being translated for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Target {
+    /// For a Windows process launched from WSL.
+    Win,
+    /// For a WSL process launched from Windows.
+    Wsl,
+}
+
+impl Target {
+    /// The [`ListOptions`] for translating `/l` variables for this side.
+    pub fn list_options(&self) -> ListOptions {
+        use Target::*;
+        match self {
+            Win => ListOptions::new(ListSyntax::Unix, ListSyntax::Windows),
+            Wsl => ListOptions::new(ListSyntax::Windows, ListSyntax::Unix),
+        }
+    }
+}
+
+impl EnumArg for Target {
+    fn variants() -> &'static [Self] {
+        use Target::*;
+        &[Win, Wsl]
+    }
+    
+    fn displays(&self) -> &'static [&'static str] {
+        use Target::*;
+        match self {
+            Win => &["win"],
+            Wsl => &["wsl"],
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        EnumArg::fmt(self, f)
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EnumArg::from_str(s)
+    }
+}
+
+/// How a shared variable's value is translated.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Translation {
+    /// No flag, so it's passed as is.
+    Unchanged,
+    /// `/p`, a single path.
+    Path,
+    /// `/l`, a list of paths like `PATH`.
+    List,
+}
+
+/// One `NAME/flags` entry in `WSLENV`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SharedVar {
+    pub name: OsString,
+    pub translation: Translation,
+    /// `/w`
+    pub to_win: bool,
+    /// `/u`
+    pub to_wsl: bool,
+}
+
+impl SharedVar {
+    /// Whether this variable is passed to [`target`] at all.
+    pub fn is_shared_to(&self, target: Target) -> bool {
+        match target {
+            Target::Win => self.to_win,
+            Target::Wsl => self.to_wsl,
+        }
+    }
+}
+
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum WslEnvError {
+    #[error("unknown flag {flag:?} for {name:?} in WSLENV")]
+    UnknownFlag { name: OsString, flag: char },
+    #[error("{name:?} can't be both a path (/p) and a path list (/l) in WSLENV")]
+    PathAndList { name: OsString },
+}
+
+/// A parsed `WSLENV`, a `:`-separated list of variable names, each optionally followed by `/` and flags:
+/// `p` to translate it as a path, `l` as a list of paths,
+/// `u` to only share it with WSL, and `w` to only share it with Windows.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WslEnv {
+    vars: Vec<SharedVar>,
+}
+
+impl WslEnv {
+    /// Parse [`spec`], skipping empty entries like WSL does.
+    pub fn parse(spec: &[u8]) -> Result<Self, WslEnvError> {
+        let mut vars = Vec::new();
+        for entry in spec.split(|c| *c == b':').filter(|entry| !entry.is_empty()) {
+            let (name, flags) = match entry.iter().position(|c| *c == b'/') {
+                Some(i) => (&entry[..i], &entry[i + 1..]),
+                None => (entry, &[][..]),
+            };
+            let name = OsString::from_vec(name.to_vec());
+            let mut translation = Translation::Unchanged;
+            let mut to_win = false;
+            let mut to_wsl = false;
+            for flag in flags {
+                match flag {
+                    b'p' | b'l' => {
+                        let flag = match flag {
+                            b'p' => Translation::Path,
+                            _ => Translation::List,
+                        };
+                        if translation != Translation::Unchanged && translation != flag {
+                            return Err(WslEnvError::PathAndList { name });
+                        }
+                        translation = flag;
+                    }
+                    b'w' => to_win = true,
+                    b'u' => to_wsl = true,
+                    flag => return Err(WslEnvError::UnknownFlag { name, flag: *flag as char }),
+                }
+            }
+            // neither restricts it, so it goes both ways
+            if !to_win && !to_wsl {
+                to_win = true;
+                to_wsl = true;
+            }
+            vars.push(SharedVar {
+                name,
+                translation,
+                to_win,
+                to_wsl,
+            });
+        }
+        Ok(Self { vars })
+    }
+    
+    pub fn vars(&self) -> &[SharedVar] {
+        &self.vars
+    }
+    
+    /// The entry for [`name`], where later entries override earlier ones.
+    pub fn get(&self, name: &OsStr) -> Option<&SharedVar> {
+        self.vars.iter().rev().find(|var| var.name == name)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct EnvOptions {
+    pub target: Target,
+    /// What to do with empty entries in `/l` variables.
+    pub empty: EmptyEntryPolicy,
+    /// What to do with `/p` variables and `/l` entries that can't be translated.
+    pub failed: FailedEntryPolicy,
+    /// Only return the shared variables and `WSLENV` itself,
+    /// which is all WSL passes to the other side,
+    /// instead of the whole environment with the shared variables translated.
+    /// Either way, variables in `WSLENV` that are only shared the other way are left out.
+    pub shared_only: bool,
+}
+
+impl EnvOptions {
+    /// The default policies for translating for [`target`].
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            empty: Default::default(),
+            failed: Default::default(),
+            shared_only: false,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VarError<E: std::error::Error + 'static> {
+    #[error(transparent)]
+    Path(E),
+    #[error(transparent)]
+    List(ListError<E>),
+}
+
+#[derive(Error, Debug)]
+#[error("error translating {name:?}: {source}")]
+pub struct EnvError<E: std::error::Error + 'static> {
+    name: OsString,
+    source: VarError<E>,
+}
+
+impl<E: std::error::Error + 'static> EnvError<E> {
+    /// The name of the variable that couldn't be translated.
+    pub fn name(&self) -> &OsStr {
+        self.name.as_os_str()
+    }
+    
+    pub fn error(&self) -> &VarError<E> {
+        &self.source
+    }
+}
+
+/// A translated environment,
+/// and the errors for the variables or entries that were kept or dropped because they couldn't be translated.
+#[derive(Debug)]
+pub struct TranslatedEnv<E: std::error::Error + 'static> {
+    pub vars: Vec<(OsString, OsString)>,
+    pub errors: Vec<EnvError<E>>,
+}
+
+/// Translate [`env`] for [`EnvOptions::target`] like WSL does when launching a process on the other side,
+/// converting the `/p` and `/l` variables in [`wslenv`] with [`converter`],
+/// which has to convert towards [`EnvOptions::target`].
+/// Fails on the first variable that can't be translated if that's the [`FailedEntryPolicy`].
+pub fn translate_env<C, I>(
+    converter: &C,
+    wslenv: &WslEnv,
+    env: I,
+    options: &EnvOptions,
+) -> Result<TranslatedEnv<C::Error>, EnvError<C::Error>>
+    where C: Converter,
+          I: IntoIterator<Item = (OsString, OsString)> {
+    let list_options = ListOptions {
+        empty: options.empty,
+        failed: options.failed,
+        ..options.target.list_options()
+    };
+    let mut vars = Vec::new();
+    let mut errors = Vec::new();
+    for (name, value) in env {
+        let var = match wslenv.get(&name) {
+            Some(var) if var.is_shared_to(options.target) => var,
+            // WSLENV itself is always passed along
+            _ if name == WSLENV => {
+                vars.push((name, value));
+                continue;
+            }
+            // it's in WSLENV, but only shared the other way, so WSL doesn't pass it
+            Some(_) => continue,
+            None => {
+                if !options.shared_only {
+                    vars.push((name, value));
+                }
+                continue;
+            }
+        };
+        let bytes = value.as_bytes();
+        let translated = match var.translation {
+            // an empty path is left empty rather than being an error
+            Translation::Path if bytes.is_empty() => Ok(None),
+            Translation::Unchanged => Ok(None),
+            Translation::Path => converter.convert_cow(bytes)
+                .map(|path| Some(OsString::from_vec(path.into_owned())))
+                .map_err(VarError::Path),
+            Translation::List => match convert_list(converter, bytes, &list_options) {
+                Ok(converted) => {
+                    errors.extend(converted.errors.into_iter().map(|e| EnvError {
+                        name: name.clone(),
+                        source: VarError::List(e),
+                    }));
+                    Ok(Some(converted.list))
+                }
+                Err(e) => Err(VarError::List(e)),
+            },
+        };
+        let source = match translated {
+            Ok(translated) => {
+                vars.push((name, translated.unwrap_or(value)));
+                continue;
+            }
+            Err(source) => source,
+        };
+        let error = EnvError {
+            name: name.clone(),
+            source,
+        };
+        match options.failed {
+            FailedEntryPolicy::Error => return Err(error),
+            FailedEntryPolicy::Drop => {}
+            FailedEntryPolicy::Keep => vars.push((name, value)),
+        }
+        errors.push(error);
+    }
+    Ok(TranslatedEnv { vars, errors })
+}
@@ -0,0 +1,335 @@
+use std::cell::RefCell;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::{env, fs};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use thiserror::Error;
+
+use crate::convert::Converter;
+
+pub mod spawn;
+pub mod response_file;
+pub mod msvc;
+
+/// A program and its arguments.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Command {
+    pub program: OsString,
+    pub args: Vec<OsString>,
+}
+
+impl Command {
+    /// The command line as it could be pasted into a POSIX shell,
+    /// quoting anything that isn't obviously safe.
+    pub fn shell_line(&self) -> Vec<u8> {
+        let mut line = Vec::new();
+        for (i, arg) in std::iter::once(&self.program).chain(&self.args).enumerate() {
+            if i != 0 {
+                line.push(b' ');
+            }
+            shell_quote(arg.as_bytes(), &mut line);
+        }
+        line
+    }
+}
+
+fn shell_quote(arg: &[u8], buf: &mut Vec<u8>) {
+    let is_safe = |c: &u8| c.is_ascii_alphanumeric() || b"%+,-./:=@_".contains(c);
+    if !arg.is_empty() && arg.iter().all(is_safe) {
+        buf.extend_from_slice(arg);
+        return;
+    }
+    buf.push(b'\'');
+    for c in arg {
+        match c {
+            b'\'' => buf.extend_from_slice(b"'\\''"),
+            c => buf.push(*c),
+        }
+    }
+    buf.push(b'\'');
+}
+
+/// Which arguments of a command are paths, for generic programs.
+#[derive(Clone, Debug, Default)]
+pub struct ArgRules {
+    /// Any argument that's an existing path, like `.` or `~/file`,
+    /// unless it starts with `-`.
+    pub existing: bool,
+    /// The value of any `--name=value` argument that's an existing path.
+    pub flag_values: bool,
+    /// Prefixes like `--out=` where the rest of the argument is always a path, even if it doesn't exist.
+    /// The longest matching prefix wins.
+    pub prefixes: Vec<OsString>,
+    /// Flags like `-o` where the next argument is always a path, even if it doesn't exist.
+    pub flags: Vec<OsString>,
+}
+
+/// What part of an argument is a path.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArgKind {
+    /// No part of it, so it's left as is.
+    Plain,
+    /// A path starting at this index and running to the end of the argument.
+    Path(usize),
+    /// A response file like `@args.rsp` starting at this index,
+    /// whose contents are more arguments.
+    ResponseFile(usize),
+}
+
+/// Decides which parts of a command's arguments are paths.
+pub trait ArgGrammar {
+    /// The [`ArgKind`] of each of [`args`],
+    /// which can depend on the arguments around it, like a flag before it.
+    fn classify(&self, args: &[OsString]) -> Vec<ArgKind>;
+}
+
+impl ArgRules {
+    /// Detect existing paths and `--name=value` paths, but nothing else.
+    pub fn existing() -> Self {
+        Self {
+            existing: true,
+            flag_values: true,
+            ..Default::default()
+        }
+    }
+    
+    /// The index in [`arg`] where a path starts, if it has one,
+    /// given whether the previous argument was one of [`ArgRules::flags`].
+    fn path_start(&self, arg: &[u8], after_flag: bool) -> Option<usize> {
+        if after_flag {
+            return Some(0);
+        }
+        let prefix = self.prefixes
+            .iter()
+            .map(|prefix| prefix.as_bytes())
+            .filter(|prefix| arg.len() > prefix.len() && arg.starts_with(prefix))
+            .map(<[u8]>::len)
+            .max();
+        if prefix.is_some() {
+            return prefix;
+        }
+        match arg {
+            [b'-', ..] if self.flag_values => {
+                let i = arg.iter().position(|c| *c == b'=')? + 1;
+                Some(i).filter(|_| exists(&arg[i..]))
+            }
+            [b'-', ..] => None,
+            _ if self.existing && exists(arg) => Some(0),
+            _ => None,
+        }
+    }
+}
+
+impl ArgGrammar for ArgRules {
+    fn classify(&self, args: &[OsString]) -> Vec<ArgKind> {
+        let mut after_flag = false;
+        args.iter()
+            .map(|arg| {
+                let start = self.path_start(arg.as_bytes(), after_flag);
+                after_flag = self.flags.contains(arg);
+                start.map_or(ArgKind::Plain, ArgKind::Path)
+            })
+            .collect()
+    }
+}
+
+/// Make [`path`] absolute against the current directory, if it can be,
+/// dropping any `.` components so `.` itself isn't converted to `dir\.`,
+/// but keeping a trailing `/`, which can mean a directory, like in `cl /Fo:out/`.
+fn absolute(path: &Path) -> PathBuf {
+    let absolute = match std::env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    };
+    let mut absolute = absolute.components().collect::<PathBuf>().into_os_string();
+    if path.as_os_str().as_bytes().ends_with(b"/") && !absolute.as_bytes().ends_with(b"/") {
+        absolute.push("/");
+    }
+    absolute.into()
+}
+
+pub(crate) fn exists(path: &[u8]) -> bool {
+    !path.is_empty() && Path::new(OsStr::from_bytes(path)).exists()
+}
+
+#[derive(Error, Debug)]
+pub enum ArgErrorKind<E: std::error::Error + 'static> {
+    #[error(transparent)]
+    Convert(E),
+    #[error("error rewriting response file")]
+    ResponseFile(#[from] io::Error),
+    /// Only the outer error's message says it's in a response file,
+    /// and the argument in it that failed is its [`source`](std::error::Error::source),
+    /// since formatting it in the message would make [`Display`](std::fmt::Display) recursive.
+    #[error("in response file")]
+    InResponseFile(#[source] Box<ArgError<E>>),
+}
+
+#[derive(Error, Debug)]
+#[error("error converting argument {index} {arg:?}")]
+pub struct ArgError<E: std::error::Error + 'static> {
+    index: usize,
+    arg: OsString,
+    source: ArgErrorKind<E>,
+}
+
+impl<E: std::error::Error + 'static> ArgError<E> {
+    /// The index of the argument, not counting the program,
+    /// or in its response file if it was in one.
+    pub fn arg_index(&self) -> usize {
+        self.index
+    }
+    
+    pub fn arg(&self) -> &OsStr {
+        self.arg.as_os_str()
+    }
+    
+    pub fn error(&self) -> &ArgErrorKind<E> {
+        &self.source
+    }
+}
+
+/// A rewritten response file, which is removed when this is dropped.
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Where to write the rewritten response file for [`path`],
+/// in the temp dir so that the original's directory is never written to,
+/// but keeping its file name, since tools might show it.
+fn rewritten_response_file_path(path: &Path) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let mut name = OsString::from(format!("wslpath-{}-{}-", process::id(), COUNT.fetch_add(1, Ordering::Relaxed)));
+    name.push(path.file_name().unwrap_or_else(|| OsStr::new("args")));
+    env::temp_dir().join(name)
+}
+
+/// Converts the arguments of a command that are paths, as decided by an [`ArgGrammar`],
+/// so that it can be run on the other side.
+/// The program itself is never converted, since it's still launched from this side.
+pub struct ArgConverter<C, G = ArgRules> {
+    converter: C,
+    grammar: G,
+    dry_run: bool,
+    /// The rewritten response files, which have to outlive the command that uses them.
+    response_files: RefCell<Vec<TempFile>>,
+}
+
+impl<C: Converter, G: ArgGrammar> ArgConverter<C, G> {
+    pub fn new(converter: C, grammar: G) -> Self {
+        Self {
+            converter,
+            grammar,
+            dry_run: false,
+            response_files: Default::default(),
+        }
+    }
+    
+    /// Don't write rewritten response files, for a dry run,
+    /// so the arguments refer to where they would have been written.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+    
+    pub fn converter(&self) -> &C {
+        &self.converter
+    }
+    
+    pub fn grammar(&self) -> &G {
+        &self.grammar
+    }
+    
+    /// Convert the path part of each argument that has one, leaving the rest as is.
+    /// Response files are rewritten to temp files, and the argument then refers to the rewritten file.
+    /// They're removed when this is dropped, so keep it until the command has run.
+    pub fn convert_args<I>(&self, args: I) -> Result<Vec<OsString>, ArgError<C::Error>>
+        where I: IntoIterator<Item = OsString> {
+        self.convert_args_in(args.into_iter().collect(), false)
+    }
+    
+    /// Like [`ArgConverter::convert_args`],
+    /// but not expanding response files again if [`args`] are already from one.
+    fn convert_args_in(
+        &self,
+        args: Vec<OsString>,
+        in_response_file: bool,
+    ) -> Result<Vec<OsString>, ArgError<C::Error>> {
+        let kinds = self.grammar.classify(&args);
+        let mut converted = Vec::with_capacity(args.len());
+        for (index, (arg, kind)) in args.into_iter().zip(kinds).enumerate() {
+            let result = match kind {
+                ArgKind::Plain => Ok(None),
+                ArgKind::ResponseFile(_) if in_response_file => Ok(None),
+                ArgKind::Path(start) => self.convert_path(arg.as_bytes(), start).map(Some),
+                ArgKind::ResponseFile(start) => self.convert_response_file(arg.as_bytes(), start).map(Some),
+            };
+            match result {
+                Ok(new_arg) => converted.push(new_arg.unwrap_or(arg)),
+                Err(source) => return Err(ArgError {
+                    index,
+                    arg,
+                    source,
+                }),
+            }
+        }
+        Ok(converted)
+    }
+    
+    /// Convert the path in [`arg`] starting at [`start`], keeping what's before it.
+    fn convert_path(&self, arg: &[u8], start: usize) -> Result<OsString, ArgErrorKind<C::Error>> {
+        let mut buf = arg[..start].to_vec();
+        let path = absolute(Path::new(OsStr::from_bytes(&arg[start..])));
+        self.converter
+            .convert_slice_into_buf(path.as_os_str().as_bytes(), &mut buf)
+            .map_err(ArgErrorKind::Convert)?;
+        Ok(OsString::from_vec(buf))
+    }
+    
+    /// Convert the arguments in the response file in [`arg`] starting at [`start`],
+    /// write them to a new response file, and refer to that instead.
+    fn convert_response_file(&self, arg: &[u8], start: usize) -> Result<OsString, ArgErrorKind<C::Error>> {
+        let path = Path::new(OsStr::from_bytes(&arg[start..]));
+        let contents = fs::read(path)?;
+        let (encoding, text) = response_file::decode(&contents);
+        let args = response_file::split(&text)
+            .into_iter()
+            .map(OsString::from_vec)
+            .collect();
+        let args = self.convert_args_in(args, true)
+            .map_err(|e| ArgErrorKind::InResponseFile(Box::new(e)))?;
+        let rewritten = rewritten_response_file_path(path);
+        if !self.dry_run {
+            let text = response_file::join(args.iter().map(|arg| arg.as_bytes()));
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&rewritten)?;
+            // even a partially written one is removed
+            self.response_files.borrow_mut().push(TempFile(rewritten.clone()));
+            file.write_all(&response_file::encode(encoding, &text))?;
+        }
+        let rewritten = rewritten.into_os_string();
+        let mut arg = arg[..start].to_vec();
+        arg.extend_from_slice(self.convert_path(rewritten.as_bytes(), 0)?.as_bytes());
+        Ok(OsString::from_vec(arg))
+    }
+    
+    /// The [`Command`] for running [`program`] with [`args`] converted.
+    pub fn command<I>(&self, program: OsString, args: I) -> Result<Command, ArgError<C::Error>>
+        where I: IntoIterator<Item = OsString> {
+        Ok(Command {
+            program,
+            args: self.convert_args(args)?,
+        })
+    }
+}
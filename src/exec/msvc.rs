@@ -0,0 +1,249 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::convert::{text, Converter};
+use crate::exec::{ArgGrammar, ArgKind, exists};
+use crate::exec::spawn::LineFilter;
+use crate::util::enum_arg::EnumArg;
+
+/// `cl` options followed by a path, like `/Fo<path>` or `/Fo:<path>`.
+/// These are case-sensitive, since `/FA` and `/Fa` are different options.
+const CL_PATH_OPTIONS: &[&str] = &[
+    "AI", "FI", "FR", "FU", "Fa", "Fd", "Fe", "Fi", "Fm", "Fo", "Fp", "Fr", "I", "Tc", "Tp", "external:I",
+];
+
+/// `cl` options that can also have their path as the next argument, like `/I <path>`.
+const CL_SEPARATE_PATH_OPTIONS: &[&str] = &["AI", "FI", "FU", "I", "external:I", "sourceDependencies"];
+
+/// `link` options followed by `:<path>`, which are case-insensitive.
+const LINK_PATH_OPTIONS: &[&str] = &[
+    "DEF", "IDLOUT", "ILK", "IMPLIB", "LIBPATH", "MANIFESTFILE", "MANIFESTINPUT", "MAP", "NATVIS", "OUT", "PDB",
+    "PGD", "SOURCELINK", "TLBOUT", "WHOLEARCHIVE", "WINMDFILE",
+];
+
+/// `lib` options followed by `:<path>`, which are case-insensitive.
+const LIB_PATH_OPTIONS: &[&str] = &["DEF", "LIBPATH", "LIST", "OUT"];
+
+/// An MSVC tool whose flag grammar we know.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Tool {
+    Cl,
+    Link,
+    Lib,
+}
+
+impl Tool {
+    /// The tool [`program`] is, like `cl.exe` or `/path/to/LINK.EXE`.
+    pub fn from_program(program: &OsStr) -> Option<Self> {
+        let name = Path::new(program).file_name()?.to_str()?.to_ascii_lowercase();
+        let name = name.strip_suffix(".exe").unwrap_or(&name);
+        name.parse().ok()
+    }
+}
+
+impl EnumArg for Tool {
+    fn variants() -> &'static [Self] {
+        use Tool::*;
+        &[Cl, Link, Lib]
+    }
+    
+    fn displays(&self) -> &'static [&'static str] {
+        use Tool::*;
+        match self {
+            Cl => &["cl"],
+            Link => &["link"],
+            Lib => &["lib"],
+        }
+    }
+}
+
+impl Display for Tool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        EnumArg::fmt(self, f)
+    }
+}
+
+impl FromStr for Tool {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EnumArg::from_str(s)
+    }
+}
+
+/// The flag grammar of an MSVC [`Tool`], where options start with `/` or `-`,
+/// anything else is an input file, and `@file` is a response file.
+///
+/// Since `/` also starts absolute Linux paths,
+/// an argument starting with `/` that isn't a known path option
+/// is a path if its first component exists, like `/home/me/a.c`,
+/// and otherwise an option, like `/nologo` or `/DDIR=/x`.
+/// Input files are only converted if they exist,
+/// so that libraries searched for in `/LIBPATH`, like `kernel32.lib`,
+/// and the values of options like `/D X=a/b` are left as is.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Msvc {
+    pub tool: Tool,
+}
+
+/// The length of the longest of [`options`] that [`option`] starts with,
+/// and then an optional `:`, if [`option`] has anything after that.
+fn option_len(option: &[u8], options: &[&str], ignore_case: bool) -> Option<usize> {
+    let starts_with = |name: &&str| {
+        let name = name.as_bytes();
+        let prefix = option.get(..name.len());
+        match ignore_case {
+            true => prefix.is_some_and(|prefix| prefix.eq_ignore_ascii_case(name)),
+            false => prefix == Some(name),
+        }
+    };
+    let mut len = options.iter().filter(|name| starts_with(name)).map(|name| name.len()).max()?;
+    if option.get(len) == Some(&b':') {
+        len += 1;
+    }
+    Some(len).filter(|len| option.len() > *len)
+}
+
+impl Msvc {
+    /// Classify [`option`], an argument without its leading `/` or `-`, for [`tool`],
+    /// also returning whether the next argument is its path.
+    fn classify_option(tool: Tool, option: &[u8]) -> (ArgKind, bool) {
+        let start = match tool {
+            Tool::Cl => option_len(option, CL_PATH_OPTIONS, false),
+            // these always have a : before the path
+            Tool::Link => option_len(option, LINK_PATH_OPTIONS, true).filter(|len| option[len - 1] == b':'),
+            Tool::Lib => option_len(option, LIB_PATH_OPTIONS, true).filter(|len| option[len - 1] == b':'),
+        };
+        if let Some(start) = start {
+            return (ArgKind::Path(1 + start), false);
+        }
+        let separate = tool == Tool::Cl && CL_SEPARATE_PATH_OPTIONS.iter().any(|name| option == name.as_bytes());
+        (ArgKind::Plain, separate)
+    }
+    
+    /// [`kind`], unless it's a path in [`arg`] that's already a Windows path, like `/FoC:\out\`,
+    /// which is passed through as is.
+    fn unless_windows_absolute(arg: &[u8], kind: ArgKind) -> ArgKind {
+        match kind {
+            ArgKind::Path(start) if is_windows_absolute(&arg[start..]) => ArgKind::Plain,
+            kind => kind,
+        }
+    }
+}
+
+impl ArgGrammar for Msvc {
+    fn classify(&self, args: &[OsString]) -> Vec<ArgKind> {
+        let mut tool = self.tool;
+        let mut next_is_path = false;
+        let mut kinds = Vec::with_capacity(args.len());
+        for arg in args {
+            let arg = arg.as_bytes();
+            if next_is_path {
+                next_is_path = false;
+                kinds.push(Self::unless_windows_absolute(arg, ArgKind::Path(0)));
+                continue;
+            }
+            let kind = match arg {
+                [b'@', ..] => ArgKind::ResponseFile(1),
+                [b'/' | b'-', option @ ..] => {
+                    // the rest of cl's arguments are for link
+                    if tool == Tool::Cl && option.eq_ignore_ascii_case(b"link") {
+                        tool = Tool::Link;
+                        kinds.push(ArgKind::Plain);
+                        continue;
+                    }
+                    let (kind, separate) = Self::classify_option(tool, option);
+                    next_is_path = separate;
+                    let root = option.iter().position(|c| *c == b'/').map_or(arg, |i| &arg[..1 + i]);
+                    let is_linux_path = arg[0] == b'/' && exists(root);
+                    match kind {
+                        ArgKind::Plain if is_linux_path => ArgKind::Path(0),
+                        kind => kind,
+                    }
+                }
+                _ if exists(arg) => ArgKind::Path(0),
+                _ => ArgKind::Plain,
+            };
+            kinds.push(Self::unless_windows_absolute(arg, kind));
+        }
+        kinds
+    }
+}
+
+/// Whether [`path`] starts like an absolute Windows path, like `C:\` or `\\server`.
+fn is_windows_absolute(path: &[u8]) -> bool {
+    match path {
+        [drive, b':', b'\\' | b'/', ..] => drive.is_ascii_alphabetic(),
+        [b'\\', b'\\', ..] => true,
+        _ => false,
+    }
+}
+
+/// The range of the file in a diagnostic like `C:\src\a.c(12,5): error C2065: ...`.
+fn diagnostic_path(line: &[u8]) -> Option<Range<usize>> {
+    let start = line.iter().position(|c| !c.is_ascii_whitespace())?;
+    if !is_windows_absolute(&line[start..]) {
+        return None;
+    }
+    // the last (line) or (line,column) before the first ": "
+    let colon = start + line[start..].windows(2).position(|w| w == b": ")?;
+    let before = line[..colon].strip_suffix(b" ").unwrap_or(&line[..colon]);
+    let location = before.strip_suffix(b")")?;
+    let open = location.iter().rposition(|c| *c == b'(')?;
+    let location = &location[open + 1..];
+    let is_location = !location.is_empty() && location.iter().all(|c| c.is_ascii_digit() || *c == b',');
+    Some(start..open).filter(|_| is_location)
+}
+
+/// The range of the header in a `/showIncludes` note, like `Note: including file:  C:\include\a.h`.
+fn include_path(line: &[u8]) -> Option<Range<usize>> {
+    const NOTE: &[u8] = b"Note: including file:";
+    let rest = line.strip_prefix(NOTE)?;
+    let start = NOTE.len() + rest.iter().position(|c| *c != b' ')?;
+    Some(start..line.len()).filter(|range| is_windows_absolute(&line[range.clone()]))
+}
+
+/// The ranges of absolute Windows paths in `'` or `"` quotes, like `cannot open file 'C:\a.lib'`.
+fn quoted_paths(line: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while let Some(open) = line[i..].iter().position(|c| *c == b'\'' || *c == b'"') {
+        let open = i + open;
+        let quote = line[open];
+        let close = match line[open + 1..].iter().position(|c| *c == quote) {
+            None => break,
+            Some(close) => open + 1 + close,
+        };
+        if is_windows_absolute(&line[open + 1..close]) {
+            ranges.push(open + 1..close);
+        }
+        i = close + 1;
+    }
+    ranges
+}
+
+/// Converts the Windows paths in MSVC output back, like [`convert_output_line`].
+pub struct OutputConverter<C>(pub C);
+
+impl<C: Converter> LineFilter for OutputConverter<C> {
+    fn filter<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        convert_output_line(&self.0, line)
+    }
+}
+
+/// Convert the Windows paths in a line of MSVC output, without its line ending, with [`converter`]:
+/// the file in diagnostics, the header in `/showIncludes` notes, and quoted absolute paths.
+/// Any that don't convert are left as is.
+pub fn convert_output_line<'a, C: Converter>(converter: &C, line: &'a [u8]) -> Cow<'a, [u8]> {
+    let mut ranges = Vec::new();
+    ranges.extend(include_path(line).or_else(|| diagnostic_path(line)));
+    ranges.extend(quoted_paths(line));
+    ranges.sort_by_key(|range| range.start);
+    text::replace_ranges(line, ranges, |range| converter.convert_cow(&line[range]).ok())
+}
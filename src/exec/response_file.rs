@@ -0,0 +1,133 @@
+//! Response files like `@args.rsp`, which hold more arguments,
+//! separated by whitespace and quoted like a Windows command line.
+
+/// How a response file is encoded, so it can be written back the same way.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Encoding {
+    /// No BOM, which MSVC reads as the ANSI code page, but is usually just ASCII or UTF-8.
+    Plain,
+    /// UTF-8 with a BOM.
+    Utf8,
+    /// UTF-16LE with a BOM, which is what MSVC writes.
+    Utf16,
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const UTF16_BOM: &[u8] = b"\xFF\xFE";
+
+/// Decode [`contents`] to bytes we can parse, and how they were encoded.
+pub fn decode(contents: &[u8]) -> (Encoding, Vec<u8>) {
+    if let Some(text) = contents.strip_prefix(UTF16_BOM) {
+        let units = text
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect::<Vec<_>>();
+        return (Encoding::Utf16, String::from_utf16_lossy(&units).into_bytes());
+    }
+    match contents.strip_prefix(UTF8_BOM) {
+        Some(text) => (Encoding::Utf8, text.to_vec()),
+        None => (Encoding::Plain, contents.to_vec()),
+    }
+}
+
+/// Encode [`text`] back like it was decoded from [`encoding`].
+pub fn encode(encoding: Encoding, text: &[u8]) -> Vec<u8> {
+    match encoding {
+        Encoding::Plain => text.to_vec(),
+        Encoding::Utf8 => [UTF8_BOM, text].concat(),
+        Encoding::Utf16 => {
+            let text = String::from_utf8_lossy(text);
+            let mut contents = UTF16_BOM.to_vec();
+            contents.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            contents
+        }
+    }
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+/// Split [`text`] into arguments like `CommandLineToArgvW`, where newlines are whitespace too.
+/// `"` quotes, `\` only escapes a `"` (or a `\` before one), and `""` in quotes is a literal `"`.
+pub fn split(text: &[u8]) -> Vec<Vec<u8>> {
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < text.len() && is_space(text[i]) {
+            i += 1;
+        }
+        if i == text.len() {
+            return args;
+        }
+        let mut arg = Vec::new();
+        let mut quoted = false;
+        while i < text.len() {
+            match text[i] {
+                c if is_space(c) && !quoted => break,
+                b'\\' => {
+                    let backslashes = text[i..].iter().take_while(|c| **c == b'\\').count();
+                    i += backslashes;
+                    if text.get(i) != Some(&b'"') {
+                        arg.resize(arg.len() + backslashes, b'\\');
+                        continue;
+                    }
+                    arg.resize(arg.len() + backslashes / 2, b'\\');
+                    // an odd one escapes the ", and otherwise the " is still a quote
+                    if backslashes % 2 == 1 {
+                        arg.push(b'"');
+                        i += 1;
+                    }
+                }
+                b'"' if quoted && text.get(i + 1) == Some(&b'"') => {
+                    arg.push(b'"');
+                    i += 2;
+                }
+                b'"' => {
+                    quoted = !quoted;
+                    i += 1;
+                }
+                c => {
+                    arg.push(c);
+                    i += 1;
+                }
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// Quote [`arg`] if it needs to be, so that [`split`] gets it back.
+fn quote(arg: &[u8], buf: &mut Vec<u8>) {
+    if !arg.is_empty() && !arg.iter().any(|c| is_space(*c) || *c == b'"') {
+        buf.extend_from_slice(arg);
+        return;
+    }
+    buf.push(b'"');
+    let mut backslashes = 0;
+    for c in arg {
+        match c {
+            b'\\' => backslashes += 1,
+            b'"' => {
+                // double the ones before the ", and then escape the " too
+                buf.resize(buf.len() + backslashes + 1, b'\\');
+                backslashes = 0;
+            }
+            _ => backslashes = 0,
+        }
+        buf.push(*c);
+    }
+    // and double the ones before the closing "
+    buf.resize(buf.len() + backslashes, b'\\');
+    buf.push(b'"');
+}
+
+/// Join [`args`] into response file text, one per line.
+pub fn join<'a>(args: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut text = Vec::new();
+    for arg in args {
+        quote(arg, &mut text);
+        text.extend_from_slice(b"\r\n");
+    }
+    text
+}
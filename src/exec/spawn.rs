@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+use std::io;
+use std::io::{BufReader, Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process;
+use std::process::{ExitStatus, Stdio};
+use std::thread;
+
+use crate::convert::text;
+use crate::exec::Command;
+
+/// Runs [`Command`]s, so that how they're run can be swapped out,
+/// e.g., for a dry run or in tests without Windows.
+pub trait Spawner {
+    /// Run [`command`] to completion, returning its exit code.
+    fn spawn(&mut self, command: &Command) -> io::Result<i32>;
+}
+
+impl<S: Spawner + ?Sized> Spawner for &mut S {
+    fn spawn(&mut self, command: &Command) -> io::Result<i32> {
+        (**self).spawn(command)
+    }
+}
+
+/// Runs the program for real, with our stdin, stdout, and stderr.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProcessSpawner;
+
+impl Spawner for ProcessSpawner {
+    fn spawn(&mut self, command: &Command) -> io::Result<i32> {
+        let status = process::Command::new(&command.program)
+            .args(&command.args)
+            .status()?;
+        Ok(exit_code(status))
+    }
+}
+
+fn exit_code(status: ExitStatus) -> i32 {
+    // killed by a signal, which shells report as 128 + the signal
+    status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
+
+/// Rewrites a program's output a line at a time, e.g., to convert the paths in it back.
+pub trait LineFilter {
+    /// Filter [`line`], which doesn't have its `\n` or `\r\n`.
+    fn filter<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]>;
+}
+
+/// Runs the program like [`ProcessSpawner`],
+/// but passes each line of its stdout and stderr through a [`LineFilter`] on the way out.
+pub struct FilteringSpawner<F>(pub F);
+
+/// Copy [`from`] to [`to`] a line at a time, filtering each line with [`filter`].
+fn filter_lines<F: LineFilter>(from: impl Read, to: impl Write, filter: &F) -> io::Result<()> {
+    text::filter_lines(BufReader::new(from), to, |line| filter.filter(line))
+}
+
+impl<F: LineFilter + Sync> Spawner for FilteringSpawner<F> {
+    fn spawn(&mut self, command: &Command) -> io::Result<i32> {
+        let mut child = process::Command::new(&command.program)
+            .args(&command.args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let filter = &self.0;
+        let (stdout, stderr) = thread::scope(|scope| {
+            let stderr = scope.spawn(|| filter_lines(stderr, io::stderr(), filter));
+            let stdout = filter_lines(stdout, io::stdout(), filter);
+            (stdout, stderr.join().expect("filtering stderr panicked"))
+        });
+        let status = child.wait()?;
+        stdout?;
+        stderr?;
+        Ok(exit_code(status))
+    }
+}
+
+/// Only writes each command line to [`W`], like [`Command::shell_line`], instead of running it.
+pub struct DryRun<W: Write>(pub W);
+
+impl<W: Write> Spawner for DryRun<W> {
+    fn spawn(&mut self, command: &Command) -> io::Result<i32> {
+        let mut line = command.shell_line();
+        line.push(b'\n');
+        self.0.write_all(&line)?;
+        self.0.flush()?;
+        Ok(0)
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::str;
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+use wslpath::convert::{ConvertErrorInfo, Converter, InputPathSeparator};
+use wslpath::util::enum_arg::EnumArg;
+
+use crate::Summary;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Plain,
+    JsonLines,
+}
+
+impl EnumArg for OutputFormat {
+    fn variants() -> &'static [Self] {
+        use OutputFormat::*;
+        &[Plain, JsonLines]
+    }
+    
+    fn displays(&self) -> &'static [&'static str] {
+        use OutputFormat::*;
+        match self {
+            Plain => &["plain"],
+            JsonLines => &["jsonl", "json-lines"],
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        EnumArg::fmt(self, f)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EnumArg::from_str(s)
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        use OutputFormat::*;
+        Plain
+    }
+}
+
+/// Paths aren't necessarily UTF-8, so encode them losslessly:
+/// as a string if they're UTF-8, or else as `{"bytes": [...]}`.
+fn bytes_value(bytes: &[u8]) -> Value {
+    match str::from_utf8(bytes) {
+        Ok(s) => Value::from(s),
+        Err(_) => json!({ "bytes": bytes }),
+    }
+}
+
+/// The error's message followed by those of its sources, like `anyhow`'s `{:#}`.
+fn message(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message.push_str(": ");
+        message.push_str(&error.to_string());
+        source = error.source();
+    }
+    message
+}
+
+/// Write one JSON object on its own line for a single input path.
+/// [`source`] is the file the path was read from, or [`None`] for arguments,
+/// and [`line`] is the 1-based line (or argument) number in it.
+pub fn write_record<E>(
+    out: &mut impl Write,
+    source: Option<&Path>,
+    line: usize,
+    input: &[u8],
+    result: Result<&[u8], &E>,
+) -> io::Result<()>
+    where E: std::error::Error + ConvertErrorInfo + 'static {
+    let mut record = json!({
+        "source": source.map(|it| bytes_value(it.as_os_str().as_bytes())),
+        "line": line,
+        "input": bytes_value(input),
+    });
+    match result {
+        Ok(output) => record["output"] = bytes_value(output),
+        Err(e) => record["error"] = json!({
+            "kind": e.kind(),
+            "message": message(e),
+            "index": e.index(),
+        }),
+    }
+    serde_json::to_writer(&mut *out, &record)?;
+    out.write_all(b"\n")
+}
+
+/// The number of lines ended in [`seps`], which has nothing but separators and empty paths in it.
+fn lines_ended<S: InputPathSeparator>(mut seps: &[u8], input_sep: &S) -> usize {
+    let mut lines = 0;
+    while let Some(sep) = input_sep.find(seps) {
+        lines += input_sep.lines_ended(&seps[sep.clone()]);
+        seps = &seps[sep.end..];
+    }
+    lines
+}
+
+/// Convert each path in [`input`] ended by [`input_sep`] with [`Converter::convert_each`],
+/// writing a [`write_record`] for each one and adding it to [`summary`].
+/// [`line`] is the line [`input`] starts on, and is advanced to the line the remainder starts on.
+/// Returns the index of the unterminated remainder, which nothing's written for yet.
+pub fn write_records<C, S>(
+    converter: &C,
+    input: &[u8],
+    input_sep: S,
+    source: Option<&Path>,
+    line: &mut usize,
+    out: &mut impl Write,
+    summary: &mut Summary,
+) -> io::Result<usize>
+    where C: Converter,
+          S: InputPathSeparator {
+    // the end of the last path, from which to count lines up to the next one
+    let mut scanned = 0;
+    let mut written = Ok(());
+    let mut sink = |span: Range<usize>, output: Result<&[u8], C::Error>| {
+        if written.is_err() {
+            return;
+        }
+        *line += lines_ended(&input[scanned..span.start], &input_sep);
+        scanned = span.end;
+        summary.add(output.is_ok());
+        written = write_record(out, source, *line, &input[span], output.as_ref().map(|it| *it));
+    };
+    let remainder_index = converter.convert_each(input, &input_sep, &mut sink);
+    written?;
+    *line += lines_ended(&input[scanned..remainder_index], &input_sep);
+    Ok(remainder_index)
+}
@@ -4,4 +4,5 @@
 compile_error!("This crate only works on WSL.");
 
 pub mod convert;
+pub mod exec;
 pub mod util;
@@ -1,15 +1,37 @@
-use std::ffi::OsString;
-use std::os::unix::ffi::OsStringExt;
-use std::path::Path;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use itertools::{Either, Itertools};
+use itertools::Itertools;
 use structopt::StructOpt;
+use thiserror::Error;
 
 use print_bytes::{eprint_bytes, print_bytes};
-use wslpath::convert::{BulkConversion, Converter, PathSeparators, win_to_wsl, wsl_to_win};
+use wslpath::convert::{BufferSizeBlocks, BulkConversion, ConversionIterator, Converter, InputPathSeparator, OutputPathSeparator, PathSeparators, win_to_wsl, wsl_to_win};
+use wslpath::convert::wsl::get_wsl_version;
+use wslpath::convert::eof::EofPolicy;
 use wslpath::convert::line_sep::LineSep;
+use wslpath::convert::mmap::MMAP_MIN_LEN;
+use wslpath::convert::separator::Separator;
+use wslpath::convert::path_list::{EmptyEntryPolicy, FailedEntryPolicy, ListOptions, ListSyntax, PathListConverter};
 use wslpath::convert::path_sep::WindowsPathSep;
-use std::env;
+use wslpath::convert::text;
+use wslpath::convert::build_log::{BuildLog, BuildLogOptions};
+use wslpath::convert::text::{TextOptions, TextPaths};
+use wslpath::convert::wslenv::{translate_env, EnvOptions, Target, Translation, WslEnv, WSLENV};
+use wslpath::exec::{ArgConverter, ArgGrammar, ArgRules};
+use wslpath::exec::msvc::{Msvc, OutputConverter, Tool};
+use wslpath::exec::spawn::{DryRun, FilteringSpawner, ProcessSpawner, Spawner};
+
+use crate::jsonl::OutputFormat;
+use std::{env, process};
+
+mod compat;
+mod jsonl;
 
 #[derive(StructOpt, Debug)]
 struct SharedArgs {
@@ -17,52 +39,465 @@ struct SharedArgs {
     from_files: bool,
     #[structopt(long, default_value)]
     path_sep: WindowsPathSep,
+    /// what ends each input path: null, LF, CRLF, whitespace,
+    /// or any other string, with \\, \0, \t, \n, \r, and \xHH escapes
+    #[structopt(long, default_value)]
+    read_line_sep: Separator,
+    /// what to write after each output path, like --read-line-sep,
+    /// where whitespace is written as \n
+    #[structopt(long, default_value)]
+    write_line_sep: Separator,
+    /// convert paths on this many threads, or 0 for one per CPU
+    #[structopt(short, long, default_value = "1")]
+    jobs: usize,
+    /// what to do with a last path that isn't followed by a separator
+    #[structopt(long, default_value)]
+    eof: EofPolicy,
+    /// map input files of 16 MiB or more instead of reading them,
+    /// which crashes with SIGBUS if one is truncated while it's converted
+    #[structopt(long)]
+    mmap: bool,
+    /// convert each input as a path list like PATH instead of as one path,
+    /// separated by ; on Windows and : on Linux
+    #[structopt(long)]
+    list: bool,
+    /// what to do with empty entries in a path list
+    #[structopt(long, default_value)]
+    empty_entries: EmptyEntryPolicy,
+    /// what to do with entries in a path list that can't be converted
     #[structopt(long, default_value)]
-    read_line_sep: LineSep,
+    failed_entries: FailedEntryPolicy,
+    /// plain paths or JSON Lines with one object per input path
     #[structopt(long, default_value)]
-    write_line_sep: LineSep,
+    format: OutputFormat,
+    /// the WSL distro name, if it can't be detected
+    #[structopt(long, parse(from_os_str))]
+    distro: Option<OsString>,
+    /// also print debugging info about the environment to stderr
+    #[structopt(short, long)]
+    verbose: bool,
+    /// don't print any errors or warnings to stderr, only use the exit code
+    #[structopt(short, long, conflicts_with = "verbose")]
+    quiet: bool,
     #[structopt(parse(from_os_str))]
     paths: Vec<OsString>,
 }
 
 #[derive(StructOpt, Debug)]
+#[structopt(after_help = "\
+Only converted paths are printed to stdout, each followed by the output separator. \
+A path that fails to convert is printed as an empty path, so outputs line up with inputs. \
+Errors and warnings are printed to stderr.
+
+EXIT CODES:
+    0    all paths were converted
+    1    invalid arguments
+    2    some paths failed to convert, or some entries of path lists did
+    3    all paths failed to convert
+    4    environment error, e.g., not running on WSL")]
 enum Args {
     Win {
         #[structopt(flatten)]
         args: SharedArgs,
         #[structopt(long)]
         dont_canonicalize: bool,
+        /// canonicalize each path from scratch instead of remembering canonicalized directories
+        #[structopt(long)]
+        no_dir_cache: bool,
+        /// the most canonicalized directories to remember at once [default: 100000]
+        #[structopt(long)]
+        dir_cache_max: Option<usize>,
     },
     WSL {
         #[structopt(flatten)]
         args: SharedArgs,
         #[structopt(long)]
         dont_convert_root_loop: bool,
+        #[structopt(long, parse(from_os_str))]
+        windows_store_root: Option<PathBuf>,
+        #[structopt(long, default_value = "10")]
+        windows_timeout_secs: u64,
+        /// cache environment lookups in $XDG_CACHE_HOME/wslpath
+        #[structopt(long)]
+        cache: bool,
+        #[structopt(long, parse(from_os_str))]
+        cache_dir: Option<PathBuf>,
     },
+    /// print the environment translated for the other side like WSL does,
+    /// converting the paths and path lists marked in WSLENV
+    Env {
+        /// the side to translate the environment for: win or wsl
+        target: Target,
+        /// use this instead of $WSLENV
+        #[structopt(long, parse(from_os_str))]
+        wslenv: Option<OsString>,
+        /// only print the variables shared by WSLENV, which is all WSL passes to the other side
+        #[structopt(long)]
+        shared_only: bool,
+        /// end each variable with \0 instead of \n, like env -0
+        #[structopt(short = "0", long)]
+        null: bool,
+        #[structopt(long, default_value)]
+        path_sep: WindowsPathSep,
+        /// what to do with empty entries in a path list
+        #[structopt(long, default_value)]
+        empty_entries: EmptyEntryPolicy,
+        /// what to do with paths and path list entries that can't be converted
+        #[structopt(long, default_value)]
+        failed_entries: FailedEntryPolicy,
+        /// the WSL distro name, if it can't be detected
+        #[structopt(long, parse(from_os_str))]
+        distro: Option<OsString>,
+        /// don't print any errors or warnings to stderr, only use the exit code
+        #[structopt(short, long)]
+        quiet: bool,
+    },
+    /// run a Windows program, converting the arguments that are Linux paths to Windows paths,
+    /// and exit with its exit code, or like env, with 125 if wslpath itself fails,
+    /// 126 if the program can't be run, or 127 if it isn't found
+    Exec {
+        /// only print the converted command line instead of running it
+        #[structopt(short = "n", long)]
+        dry_run: bool,
+        /// don't convert arguments just because they're existing paths
+        #[structopt(long)]
+        no_existing: bool,
+        /// don't convert the values of --name=value arguments that are existing paths
+        #[structopt(long)]
+        no_flag_values: bool,
+        /// a prefix like --out= where the rest of an argument is always a path
+        #[structopt(long = "prefix", number_of_values = 1, allow_hyphen_values = true, parse(from_os_str))]
+        prefixes: Vec<OsString>,
+        /// a flag like -o where the next argument is always a path
+        #[structopt(long = "flag", number_of_values = 1, allow_hyphen_values = true, parse(from_os_str))]
+        flags: Vec<OsString>,
+        /// canonicalize paths, which then have to exist
+        #[structopt(long)]
+        canonicalize: bool,
+        /// parse arguments like an MSVC tool: cl, link, or lib, detected from the program name,
+        /// rewrite response files, and convert the paths in its output back to Linux paths
+        #[structopt(long)]
+        msvc: bool,
+        /// which MSVC tool the program is, if it can't be detected, which implies --msvc
+        #[structopt(long)]
+        msvc_tool: Option<Tool>,
+        /// don't convert the paths in an MSVC tool's output back
+        #[structopt(long)]
+        keep_output: bool,
+        #[structopt(long, default_value)]
+        path_sep: WindowsPathSep,
+        /// the WSL distro name, if it can't be detected
+        #[structopt(long, parse(from_os_str))]
+        distro: Option<OsString>,
+        /// don't print any errors or warnings to stderr, only use the exit code
+        #[structopt(short, long)]
+        quiet: bool,
+        /// the program and its arguments
+        #[structopt(parse(from_os_str), required = true, last = true)]
+        command: Vec<OsString>,
+    },
+    /// convert the paths found in each line of text, like compiler errors or logs,
+    /// leaving the rest of each line, including any :line:column or (line,column), as is
+    Filter {
+        /// the side to convert paths for: win or wsl
+        target: Target,
+        /// don't convert drive paths like C:\a
+        #[structopt(long)]
+        no_drives: bool,
+        /// don't convert UNC paths like \\wsl$\distro\a
+        #[structopt(long)]
+        no_unc: bool,
+        /// also convert Linux paths whose first component doesn't exist, like /nologo
+        #[structopt(long)]
+        any_root: bool,
+        /// don't let paths in quotes have spaces
+        #[structopt(long)]
+        no_quoted_spaces: bool,
+        /// more characters that end a path, besides whitespace, quotes, <>|, and :
+        #[structopt(long)]
+        terminators: Option<String>,
+        /// read a make, ninja, or MSBuild log, following its directory changes
+        /// to also convert relative file:line and file(line) paths to absolute ones,
+        /// only for target wsl
+        #[structopt(long)]
+        build_log: bool,
+        /// the directory, Windows or WSL, that a build log's paths are relative to
+        /// before it changes directories [default: the current directory]
+        #[structopt(long, parse(from_os_str), requires = "build-log")]
+        base_dir: Option<OsString>,
+        #[structopt(long, default_value)]
+        path_sep: WindowsPathSep,
+        /// the WSL distro name, if it can't be detected
+        #[structopt(long, parse(from_os_str))]
+        distro: Option<OsString>,
+        /// don't print any errors or warnings to stderr, only use the exit code
+        #[structopt(short, long)]
+        quiet: bool,
+        /// the files to read, or stdin if none or -
+        #[structopt(parse(from_os_str))]
+        files: Vec<OsString>,
+    },
+    /// print whether this is WSL 1 or 2
+    WslVersion,
+}
+
+/// Exit codes, so that scripts can tell what went wrong.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Status {
+    Success = 0,
+    // 1 is used by clap for usage errors
+    PartialFailure = 2,
+    TotalFailure = 3,
+    /// e.g., not running on WSL
+    Environment = 4,
 }
 
+/// Exit codes for `exec`, reserved like `env`'s,
+/// since any other exit code is the program's own.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum ExecStatus {
+    /// e.g., an argument couldn't be converted
+    Failure = 125,
+    CannotRun = 126,
+    NotFound = 127,
+}
+
+#[derive(Error, Debug)]
+#[error("couldn't run {program:?}")]
+struct SpawnError {
+    program: OsString,
+    source: io::Error,
+}
+
+impl From<&anyhow::Error> for ExecStatus {
+    fn from(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<SpawnError>() {
+            Some(e) if e.source.kind() == io::ErrorKind::NotFound => ExecStatus::NotFound,
+            Some(_) => ExecStatus::CannotRun,
+            None => ExecStatus::Failure,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+impl SharedArgs {
+    fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, true) => Verbosity::Verbose,
+            (false, false) => Verbosity::Normal,
+        }
+    }
+}
+
+impl Args {
+    fn verbosity(&self) -> Verbosity {
+        use Args::*;
+        match self {
+            Win { args, .. } | WSL { args, .. } => args.verbosity(),
+            Env { quiet: true, .. } | Exec { quiet: true, .. } | Filter { quiet: true, .. } => Verbosity::Quiet,
+            Env { .. } | Exec { .. } | Filter { .. } | WslVersion => Verbosity::Normal,
+        }
+    }
+    
+    /// Check the combinations of arguments that clap can't express,
+    /// so they're usage errors like clap's own.
+    fn validate(&self) -> clap::Result<()> {
+        match self {
+            Args::Filter { build_log: true, target: Target::Win, .. } => Err(clap::Error::with_description(
+                "--build-log only converts to wsl, not win",
+                clap::ErrorKind::ArgumentConflict,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    attempted: usize,
+    failed: usize,
+    /// Paths that were converted, but only partly, like path lists with failed entries left out or kept.
+    partial: usize,
+}
+
+impl Summary {
+    fn add(&mut self, converted: bool) {
+        self.attempted += 1;
+        if !converted {
+            self.failed += 1;
+        }
+    }
+    
+    fn status(&self) -> Status {
+        match (self.failed, self.partial) {
+            (0, 0) => Status::Success,
+            (failed, _) if failed == self.attempted => Status::TotalFailure,
+            _ => Status::PartialFailure,
+        }
+    }
+}
+
+/// Only converted paths go to stdout, and everything else goes to stderr.
 fn print_converted<C: Converter>(
-    converted: &BulkConversion<C>, source_path: Option<&Path>, line_sep: &LineSep,
+    converted: &BulkConversion<C>, source_path: &Path, verbosity: Verbosity, summary: &mut Summary,
 ) {
     let BulkConversion {
         paths,
         remainder_index: _,
+        num_paths,
         errors,
+        next: _,
     } = converted;
     print_bytes(paths);
-    if errors.is_empty() {
+    summary.attempted += num_paths;
+    summary.failed += errors.len();
+    if verbosity == Verbosity::Quiet {
         return;
     }
-    if let Some(source_path) = source_path {
+    for error in errors {
+        // file:line is what editors and terminals know how to jump to
         eprint_bytes(source_path);
-        eprint!(":{}", line_sep.value());
+        eprint!(":{}: ", error.position().line);
+        eprint_bytes(error.path());
+        eprintln!(": {}", error.error());
     }
-    for error in errors {
-        eprint!("\t{:#?}{}", error, line_sep.value());
+}
+
+fn print_file<C, InputSep, OutputSep, R>(
+    file: ConversionIterator<C, InputSep, OutputSep, R>,
+    path: &Path,
+    verbosity: Verbosity,
+    summary: &mut Summary,
+) where C: Converter,
+        InputSep: InputPathSeparator,
+        OutputSep: OutputPathSeparator,
+        R: Read {
+    for converted in file {
+        match converted {
+            Err(e) => {
+                summary.add(false);
+                print_error(path, e, verbosity);
+                break;
+            }
+            Ok(converted) => {
+                print_converted(&converted, path, verbosity, summary);
+            }
+        };
+    }
+}
+
+#[cfg(feature = "rayon")]
+fn with_jobs<C, InputSep, OutputSep, R>(
+    file: ConversionIterator<C, InputSep, OutputSep, R>,
+    parallel: bool,
+) -> ConversionIterator<C, InputSep, OutputSep, R>
+    where C: Converter + Sync,
+          InputSep: InputPathSeparator + Sync,
+          OutputSep: OutputPathSeparator + Sync,
+          R: Read {
+    match parallel {
+        true => file.parallel(),
+        false => file,
     }
 }
 
-fn run<C: Converter>(args: SharedArgs, converter: C) {
+#[cfg(not(feature = "rayon"))]
+fn with_jobs<C, InputSep, OutputSep, R>(
+    file: ConversionIterator<C, InputSep, OutputSep, R>,
+    _parallel: bool,
+) -> ConversionIterator<C, InputSep, OutputSep, R>
+    where C: Converter,
+          InputSep: InputPathSeparator,
+          OutputSep: OutputPathSeparator,
+          R: Read {
+    file
+}
+
+/// What to read from when given no paths or `-`.
+const STDIN_PATH: &str = "-";
+
+fn input_paths(paths: &[OsString]) -> Vec<&Path> {
+    match paths {
+        [] => vec![Path::new(STDIN_PATH)],
+        paths => paths.iter().map(Path::new).collect(),
+    }
+}
+
+fn print_error(source_path: &Path, error: impl Into<anyhow::Error>, verbosity: Verbosity) {
+    if verbosity == Verbosity::Quiet {
+        return;
+    }
+    eprint_bytes(source_path);
+    eprintln!(": {:#}", error.into());
+}
+
+fn run<C: Converter + Sync>(args: SharedArgs, converter: &C) -> io::Result<Summary> {
+    if let OutputFormat::JsonLines = args.format {
+        return run_jsonl(args, converter);
+    }
+    if args.jobs == 1 {
+        return run_plain(args, converter, false);
+    }
+    #[cfg(feature = "rayon")] {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(args.jobs)
+            .build()
+            .map_err(io::Error::other)?;
+        pool.install(|| run_plain(args, converter, true))
+    }
+    #[cfg(not(feature = "rayon"))] {
+        if args.verbosity() > Verbosity::Quiet {
+            eprintln!("warning: ignoring --jobs, since this wslpath was built without the rayon feature");
+        }
+        run_plain(args, converter, false)
+    }
+}
+
+/// Like [`run`], but converting each input as a path list from [`input`] to [`output`] syntax
+/// if [`SharedArgs::list`], and then giving [`converter`] back.
+/// Lists with entries that were kept or dropped because they couldn't be converted only partly succeed,
+/// and those entries' errors are printed as warnings after all the lists.
+fn run_lists<C: Converter + Sync>(
+    args: SharedArgs,
+    converter: C,
+    input: ListSyntax,
+    output: ListSyntax,
+) -> io::Result<(Summary, C)> {
+    if !args.list {
+        let summary = run(args, &converter)?;
+        return Ok((summary, converter));
+    }
+    let options = ListOptions {
+        input,
+        output,
+        empty: args.empty_entries,
+        failed: args.failed_entries,
+    };
+    let verbosity = args.verbosity();
+    let converter = PathListConverter::from_converter(converter, options);
+    let mut summary = run(args, &converter)?;
+    let entry_errors = converter.take_entry_errors();
+    if verbosity > Verbosity::Quiet {
+        for e in entry_errors.iter().flatten() {
+            eprintln!("warning: {}", e);
+        }
+    }
+    summary.partial += entry_errors.len();
+    Ok((summary, converter.into_converter()))
+}
+
+/// Like [`run`], but for [`OutputFormat::Plain`],
+/// converting in parallel on the current rayon thread pool if [`parallel`].
+fn run_plain<C: Converter + Sync>(args: SharedArgs, converter: &C, parallel: bool) -> io::Result<Summary> {
+    let verbosity = args.verbosity();
+    let mut summary = Summary::default();
     if !args.from_files {
         let mut paths = args.paths
             .into_iter()
@@ -75,79 +510,520 @@ fn run<C: Converter>(args: SharedArgs, converter: C) {
             input: LineSep::Null,
             output: args.write_line_sep,
         };
-        let converted = converter.convert_all(paths.as_mut_slice(), &seps);
-        print_converted(&converted, Some(Path::new("args")), &args.write_line_sep);
+        let converted = converter.convert_reader(paths.as_slice(), &seps, paths.len());
+        print_file(with_jobs(converted, parallel), Path::new("args"), verbosity, &mut summary);
     } else {
         let seps = PathSeparators {
-            input: args.read_line_sep,
-            output: args.write_line_sep,
+            input: &args.read_line_sep,
+            output: &args.write_line_sep,
+        };
+        let blocks = BufferSizeBlocks {
+            mmap_min_len: match args.mmap {
+                true => MMAP_MIN_LEN,
+                false => u64::MAX,
+            },
+            ..Default::default()
         };
-        let stdin_path = "/proc/self/fd/0"; // /proc/self/fd/0 is more portable than /dev/stdin
-        let default_paths = [stdin_path.into()];
-        let (errors, files): (Vec<_>, Vec<_>) = match args.paths.as_slice() {
-            [] => &default_paths,
-            paths => paths,
-        }.iter()
-            .map(Path::new)
-            .map(|path| {
+        for path in input_paths(&args.paths) {
+            let converted = if path == Path::new(STDIN_PATH) {
                 converter
-                    .convert_file(path, &seps, Default::default())
-                    .map(|it| (path, it))
-                    .map_err(|it| (path, it))
-            })
-            .partition_map(Either::from);
-        if !errors.is_empty() {
-            for (path, error) in errors {
-                eprint!("{:#?}: {:#?}{}", path, error, args.write_line_sep.value());
+                    .convert_fd(io::stdin(), &seps, blocks)
+                    .map(|file| {
+                        let file = with_jobs(file.with_eof_policy(args.eof), parallel);
+                        print_file(file, path, verbosity, &mut summary)
+                    })
+            } else {
+                converter
+                    .convert_file(path, &seps, blocks)
+                    .map(|file| {
+                        let file = with_jobs(file.with_eof_policy(args.eof), parallel);
+                        print_file(file, path, verbosity, &mut summary)
+                    })
+            };
+            if let Err(e) = converted {
+                // a file we can't read at all is one failed input
+                summary.add(false);
+                print_error(path, e, verbosity);
             }
-            return;
         }
-        for (path, file) in files {
-            for (i, converted) in file.enumerate() {
-                let source_path = Some(path).filter(|_| i == 0);
-                match converted {
+    }
+    Ok(summary)
+}
+
+/// Like [`run`], but convert and write each path separately for [`OutputFormat::JsonLines`].
+fn run_jsonl<C: Converter>(args: SharedArgs, converter: &C) -> io::Result<Summary> {
+    let verbosity = args.verbosity();
+    let mut summary = Summary::default();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if !args.from_files {
+        // each argument is its own line
+        let mut paths = Vec::new();
+        for path in &args.paths {
+            paths.extend_from_slice(path.as_bytes());
+            paths.push(0);
+        }
+        jsonl::write_records(converter, &paths, LineSep::Null, None, &mut 1, &mut out, &mut summary)?;
+    } else {
+        let mut chunk = vec![0; BufferSizeBlocks::DEFAULT_BLOCK_SIZE];
+        for path in input_paths(&args.paths) {
+            let file = if path == Path::new(STDIN_PATH) {
+                Ok(Box::new(io::stdin()) as Box<dyn Read>)
+            } else {
+                File::open(path).map(|it| Box::new(it) as Box<dyn Read>)
+            };
+            let mut file = match file {
+                Ok(file) => file,
+                Err(e) => {
+                    summary.add(false);
+                    print_error(path, e, verbosity);
+                    continue;
+                }
+            };
+            // what's been read, but not yet converted, since it's not a whole path yet
+            let mut buf = Vec::new();
+            let mut line = 1;
+            loop {
+                // convert whatever's been read so far instead of waiting to fill the chunk
+                let bytes_read = match file.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(bytes_read) => bytes_read,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
                     Err(e) => {
-                        eprint!("{:#?}: {:#?}{}", path, e, args.write_line_sep.value());
+                        summary.add(false);
+                        print_error(path, e, verbosity);
+                        buf.clear();
                         break;
                     }
-                    Ok(converted) => {
-                        print_converted(&converted, source_path, &args.write_line_sep);
-                    }
                 };
+                buf.extend_from_slice(&chunk[..bytes_read]);
+                let remainder_index = jsonl::write_records(
+                    converter, &buf, &args.read_line_sep, Some(path), &mut line, &mut out, &mut summary,
+                )?;
+                buf.drain(..remainder_index);
+            }
+            if buf.is_empty() {
+                continue;
+            }
+            match args.eof {
+                EofPolicy::Convert => {
+                    let mut converted = Vec::new();
+                    let result = converter.convert_slice_into_buf(&buf, &mut converted);
+                    summary.add(result.is_ok());
+                    let result = result.as_ref().map(|_| converted.as_slice());
+                    jsonl::write_record(&mut out, Some(path), line, &buf, result)?;
+                }
+                EofPolicy::Error => {
+                    summary.add(false);
+                    let e = io::Error::new(io::ErrorKind::UnexpectedEof, format!(
+                        "unterminated path {:?} at line {}", OsStr::from_bytes(&buf), line,
+                    ));
+                    print_error(path, e, verbosity);
+                }
+                EofPolicy::Ignore => {}
+            }
+        }
+    }
+    out.flush()?;
+    Ok(summary)
+}
+
+/// Translate the current environment with [`converter`] and print it like `env`,
+/// counting each translated variable as a path.
+fn print_env<C: Converter>(
+    converter: &C,
+    wslenv: &WslEnv,
+    options: &EnvOptions,
+    terminator: u8,
+    verbosity: Verbosity,
+) -> Summary {
+    let mut summary = Summary::default();
+    let attempted = wslenv.vars()
+        .iter()
+        .filter(|var| var.is_shared_to(options.target) && var.translation != Translation::Unchanged)
+        .filter(|var| env::var_os(&var.name).is_some())
+        .count();
+    let translated = match translate_env(converter, wslenv, env::vars_os(), options) {
+        Ok(translated) => translated,
+        Err(e) => {
+            if verbosity > Verbosity::Quiet {
+                eprintln!("error: {}", e);
+            }
+            summary.attempted = attempted.max(1);
+            summary.failed = summary.attempted;
+            return summary;
+        }
+    };
+    let mut out = Vec::new();
+    for (name, value) in &translated.vars {
+        out.extend_from_slice(name.as_bytes());
+        out.push(b'=');
+        out.extend_from_slice(value.as_bytes());
+        out.push(terminator);
+    }
+    print_bytes(&out);
+    if verbosity > Verbosity::Quiet {
+        for e in &translated.errors {
+            eprintln!("warning: {}", e);
+        }
+    }
+    summary.attempted = attempted;
+    // a variable with several failed entries still only counts once
+    summary.failed = translated.errors.iter().map(|e| e.name()).unique().count();
+    summary
+}
+
+/// Convert the arguments of [`command`] and run it with [`spawner`], returning its exit code.
+/// A [`SpawnError`] means it couldn't be run, and any other error that an argument couldn't be converted.
+fn run_command<C, G>(
+    converter: &ArgConverter<C, G>,
+    command: Vec<OsString>,
+    spawner: &mut impl Spawner,
+) -> anyhow::Result<i32>
+    where C: Converter,
+          C::Error: Send + Sync,
+          G: ArgGrammar {
+    let mut command = command.into_iter();
+    let program = command.next().unwrap_or_default();
+    let command = converter.command(program, command)?;
+    let code = spawner.spawn(&command).map_err(|source| SpawnError {
+        program: command.program,
+        source,
+    })?;
+    Ok(code)
+}
+
+/// Convert the paths found in the lines of each of [`files`], printing them to stdout.
+/// Each path found counts, and fails if it can't be converted, as does each file that can't be read.
+fn run_filter<C: Converter>(
+    converter: &C,
+    options: &TextOptions,
+    files: &[OsString],
+    verbosity: Verbosity,
+) -> Summary {
+    let mut summary = Summary::default();
+    let stdout = io::stdout();
+    for path in input_paths(files) {
+        let result = match path == Path::new(STDIN_PATH) {
+            true => text::convert_text(converter, options, io::stdin().lock(), stdout.lock()),
+            false => File::open(path)
+                .and_then(|file| text::convert_text(converter, options, io::BufReader::new(file), stdout.lock())),
+        };
+        match result {
+            Ok(paths) => {
+                summary.attempted += paths.found;
+                summary.failed += paths.failed;
+            }
+            Err(e) => {
+                summary.add(false);
+                print_error(path, e, verbosity);
             }
         }
     }
+    summary
 }
 
-#[paw::main]
-fn main(args: Args) -> anyhow::Result<()> {
-    for mount in proc_mounts::MountIter::new()? {
-        let mount = mount?;
-        print_bytes(&mount.source);
-        print!(" ");
-        print_bytes(&mount.dest);
-        println!();
+/// Convert the paths in each of [`files`] as one build log, printing them to stdout,
+/// so that directory changes carry over from one file to the next.
+/// Each path found counts, and fails if it can't be converted or resolved, as does each file that can't be read.
+fn run_build_log<C: Converter>(mut log: BuildLog<C>, files: &[OsString], verbosity: Verbosity) -> Summary {
+    let mut summary = Summary::default();
+    let stdout = io::stdout();
+    for path in input_paths(files) {
+        let result = match path == Path::new(STDIN_PATH) {
+            true => log.convert_log(io::stdin().lock(), stdout.lock()),
+            false => File::open(path).and_then(|file| log.convert_log(io::BufReader::new(file), stdout.lock())),
+        };
+        match result {
+            Ok(()) => {}
+            Err(e) => {
+                summary.add(false);
+                print_error(path, e, verbosity);
+            }
+        }
     }
+    let paths = log.summary();
+    summary.attempted += paths.found;
+    summary.failed += paths.failed;
+    summary
+}
+
+/// Debugging info about how we were invoked and what environment we're in.
+fn print_environment(args: &Args) {
     eprintln!("{:#?}", args);
+    eprintln!("WSL version: {:?}", get_wsl_version());
+    if let Ok(mounts) = proc_mounts::MountIter::new() {
+        for mount in mounts.flatten() {
+            eprint_bytes(&mount.source);
+            eprint!(" ");
+            eprint_bytes(&mount.dest);
+            eprintln!(" {}", mount.fstype);
+        }
+    }
+}
+
+fn run_args(args: Args) -> anyhow::Result<Status> {
+    let verbosity = args.verbosity();
+    if verbosity == Verbosity::Verbose {
+        print_environment(&args);
+    }
     use Args::*;
-    match args {
-        WSL { args, dont_convert_root_loop } => {
+    let summary = match args {
+        WSL {
+            args,
+            dont_convert_root_loop,
+            windows_store_root,
+            windows_timeout_secs,
+            cache,
+            cache_dir,
+        } => {
             use win_to_wsl::{Converter, Options};
             let options = Options {
                 sep: args.path_sep,
                 convert_root_loop: !dont_convert_root_loop,
+                windows_store_root,
+                windows_timeout: Duration::from_secs(windows_timeout_secs),
+                lazy_init: true,
+                distro: args.distro.clone(),
+                keep_relative: false,
+                cache_dir: cache_dir.or_else(|| match cache {
+                    true => win_to_wsl::cache::default_dir(),
+                    false => None,
+                }),
             };
-            run(args, Converter::new(options)?);
+            let converter = Converter::new(options)?;
+            let (summary, converter) = run_lists(args, converter, ListSyntax::Windows, ListSyntax::Unix)?;
+            if let Some(e) = converter.root_loop_warning() {
+                if verbosity > Verbosity::Quiet {
+                    eprintln!("warning: not converting root loops: {}", e);
+                }
+            }
+            summary
         }
-        Win {args, dont_canonicalize} => {
+        Win {args, dont_canonicalize, no_dir_cache, dir_cache_max} => {
             use wsl_to_win::{Converter, Options};
+            use wsl_to_win::canonicalize::CacheLimits;
+            let canonicalize_cache = CacheLimits {
+                max_dirs: Some(dir_cache_max.unwrap_or(CacheLimits::DEFAULT_MAX_DIRS)),
+                ..Default::default()
+            };
             let options = Options {
                 sep: args.path_sep,
                 canonicalize: !dont_canonicalize,
                 base_directory: Some(env::current_dir()?),
+                distro: args.distro.clone(),
+                keep_relative: false,
+                canonicalize_cache: Some(canonicalize_cache).filter(|_| !no_dir_cache),
+            };
+            run_lists(args, Converter::new(options)?, ListSyntax::Unix, ListSyntax::Windows)?.0
+        }
+        Env {
+            target,
+            wslenv,
+            shared_only,
+            null,
+            path_sep,
+            empty_entries,
+            failed_entries,
+            distro,
+            quiet: _,
+        } => {
+            let spec = wslenv.or_else(|| env::var_os(WSLENV)).unwrap_or_default();
+            let wslenv = WslEnv::parse(spec.as_bytes())?;
+            let options = EnvOptions {
+                target,
+                empty: empty_entries,
+                failed: failed_entries,
+                shared_only,
+            };
+            let terminator = match null {
+                true => b'\0',
+                false => b'\n',
+            };
+            // like WSL, paths don't have to exist, and relative paths stay relative
+            match target {
+                Target::Win => {
+                    let options_ = wsl_to_win::Options {
+                        sep: path_sep,
+                        canonicalize: false,
+                        base_directory: None,
+                        distro,
+                        keep_relative: true,
+                        canonicalize_cache: None,
+                    };
+                    let converter = wsl_to_win::Converter::new(options_)?;
+                    print_env(&converter, &wslenv, &options, terminator, verbosity)
+                }
+                Target::Wsl => {
+                    let options_ = win_to_wsl::Options {
+                        sep: path_sep,
+                        distro,
+                        keep_relative: true,
+                        ..Default::default()
+                    };
+                    let converter = win_to_wsl::Converter::new(options_)?;
+                    print_env(&converter, &wslenv, &options, terminator, verbosity)
+                }
+            }
+        }
+        Exec {
+            dry_run,
+            no_existing,
+            no_flag_values,
+            prefixes,
+            flags,
+            canonicalize,
+            msvc,
+            msvc_tool,
+            keep_output,
+            path_sep,
+            distro,
+            quiet: _,
+            command,
+        } => {
+            let options = wsl_to_win::Options {
+                sep: path_sep,
+                canonicalize,
+                base_directory: Some(env::current_dir()?),
+                distro: distro.clone(),
+                keep_relative: false,
+                canonicalize_cache: None,
             };
-            run(args, Converter::new(options)?);
+            let converter = wsl_to_win::Converter::new(options)?;
+            let tool = msvc_tool.or_else(|| match msvc {
+                true => command.first().and_then(|program| Tool::from_program(program)),
+                false => None,
+            });
+            let code = match tool {
+                None if msvc => anyhow::bail!("{:?} isn't an MSVC tool, so pass --msvc-tool", command[0]),
+                None => {
+                    let rules = ArgRules {
+                        existing: !no_existing,
+                        flag_values: !no_flag_values,
+                        prefixes,
+                        flags,
+                    };
+                    let converter = ArgConverter::new(converter, rules);
+                    match dry_run {
+                        true => run_command(&converter.dry_run(), command, &mut DryRun(io::stdout()))?,
+                        false => run_command(&converter, command, &mut ProcessSpawner)?,
+                    }
+                }
+                Some(tool) => {
+                    let converter = ArgConverter::new(converter, Msvc { tool });
+                    let output_options = win_to_wsl::Options {
+                        sep: path_sep,
+                        convert_root_loop: false,
+                        distro,
+                        ..Default::default()
+                    };
+                    let filter = OutputConverter(win_to_wsl::Converter::new(output_options)?);
+                    match (dry_run, keep_output) {
+                        (true, _) => run_command(&converter.dry_run(), command, &mut DryRun(io::stdout()))?,
+                        (false, true) => run_command(&converter, command, &mut ProcessSpawner)?,
+                        (false, false) => run_command(&converter, command, &mut FilteringSpawner(filter))?,
+                    }
+                }
+            };
+            // the program's exit code isn't one of ours
+            process::exit(code);
         }
+        Filter {
+            target,
+            no_drives,
+            no_unc,
+            any_root,
+            no_quoted_spaces,
+            terminators,
+            build_log,
+            base_dir,
+            path_sep,
+            distro,
+            quiet: _,
+            files,
+        } => {
+            let paths = match target {
+                Target::Win => TextPaths::Linux,
+                Target::Wsl => TextPaths::Windows,
+            };
+            let options = TextOptions {
+                drives: !no_drives,
+                unc: !no_unc,
+                existing_root: !any_root,
+                quoted_spaces: !no_quoted_spaces,
+                terminators: terminators.unwrap_or_default().into_bytes(),
+                sep: path_sep,
+                ..TextOptions::new(paths)
+            };
+            // paths in text don't have to exist here
+            match target {
+                Target::Win => {
+                    let converter_options = wsl_to_win::Options {
+                        sep: path_sep,
+                        canonicalize: false,
+                        base_directory: None,
+                        distro,
+                        keep_relative: false,
+                        canonicalize_cache: None,
+                    };
+                    let converter = wsl_to_win::Converter::new(converter_options)?;
+                    run_filter(&converter, &options, &files, verbosity)
+                }
+                Target::Wsl => {
+                    let converter_options = win_to_wsl::Options {
+                        sep: path_sep,
+                        convert_root_loop: false,
+                        distro,
+                        ..Default::default()
+                    };
+                    let converter = win_to_wsl::Converter::new(converter_options)?;
+                    match build_log {
+                        false => run_filter(&converter, &options, &files, verbosity),
+                        true => {
+                            let base_dir = match base_dir {
+                                Some(dir) => dir.into_vec(),
+                                None => env::current_dir()?.into_os_string().into_vec(),
+                            };
+                            let log_options = BuildLogOptions {
+                                text: options,
+                                base_dir: Some(base_dir),
+                            };
+                            run_build_log(BuildLog::new(converter, log_options), &files, verbosity)
+                        }
+                    }
+                }
+            }
+        }
+        WslVersion => match get_wsl_version() {
+            Some(version) => {
+                println!("{}", version);
+                Summary::default()
+            }
+            None => anyhow::bail!("couldn't detect the WSL version"),
+        },
+    };
+    Ok(summary.status())
+}
+
+fn main() {
+    let mut args = env::args_os().collect_vec();
+    if compat::is_compat(&mut args) {
+        process::exit(compat::run(&args));
     }
-    Ok(())
+    let args = Args::from_iter(args);
+    args.validate().unwrap_or_else(|e| e.exit());
+    let quiet = args.verbosity() == Verbosity::Quiet;
+    let exec = matches!(args, Args::Exec { .. });
+    let status = run_args(args).map(|status| status as i32).unwrap_or_else(|e| {
+        if !quiet {
+            eprintln!("error: {:#}", e);
+        }
+        match exec {
+            // the program's exit codes are passed through, so ours can't overlap them
+            true => ExecStatus::from(&e) as i32,
+            // anything failing before we get to the paths is the environment's fault
+            false => Status::Environment as i32,
+        }
+    });
+    process::exit(status);
 }
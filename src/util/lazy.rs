@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use once_cell::sync::OnceCell;
+
+/// A value that's computed on first use, safely from any thread.
+/// If computing it fails, the error is kept and shared with every later use,
+/// so the computation is only ever attempted once.
+pub struct LazyInit<T, E> {
+    cell: OnceCell<Result<T, Arc<E>>>,
+}
+
+impl<T, E> LazyInit<T, E> {
+    pub fn new() -> Self {
+        Self {
+            cell: OnceCell::new(),
+        }
+    }
+    
+    /// Already computed, e.g., when initializing eagerly.
+    pub fn done(result: Result<T, E>) -> Self {
+        Self {
+            cell: OnceCell::from(result.map_err(Arc::new)),
+        }
+    }
+    
+    /// Get the result if it's already been computed, without computing it.
+    pub fn get(&self) -> Option<Result<&T, Arc<E>>> {
+        self.cell
+            .get()
+            .map(|it| it.as_ref().map_err(Arc::clone))
+    }
+    
+    pub fn get_or_init<F>(&self, init: F) -> Result<&T, Arc<E>>
+        where F: FnOnce() -> Result<T, E> {
+        self.cell
+            .get_or_init(|| init().map_err(Arc::new))
+            .as_ref()
+            .map_err(Arc::clone)
+    }
+}
+
+impl<T, E> Default for LazyInit<T, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
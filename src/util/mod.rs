@@ -1 +1,2 @@
 pub mod enum_arg;
+pub mod lazy;
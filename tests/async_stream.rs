@@ -0,0 +1,120 @@
+#![cfg(feature = "async")]
+
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_core::Stream;
+use tokio::io::AsyncReadExt;
+
+use wslpath::convert::{async_stream, BufferSizeBlocks, PathSeparators};
+use wslpath::convert::eof::EofPolicy;
+use wslpath::convert::line_sep::LineSep;
+use wslpath::convert::mmap::MMAP_CHUNK_LEN;
+use wslpath::convert::win_to_wsl;
+
+mod common;
+
+use common::test_converter;
+
+fn converter() -> Arc<win_to_wsl::Converter> {
+    Arc::new(test_converter(false))
+}
+
+fn seps() -> Arc<PathSeparators<LineSep, LineSep>> {
+    Arc::new(PathSeparators {
+        input: LineSep::LF,
+        output: LineSep::LF,
+    })
+}
+
+const INPUT: &[u8] = b"C:\\a\nbad\nD:\\b\\c\nworse\nE:\\";
+
+const OUTPUT: &[u8] = b"/mnt/c/a\n\n/mnt/d/b/c\n\n/mnt/e/\n";
+
+#[tokio::test]
+async fn stream() {
+    for buf_len in [1, 3, 64] {
+        let mut stream = async_stream::convert_reader(converter(), INPUT, seps(), buf_len);
+        let mut output = Vec::new();
+        let mut lines = Vec::new();
+        while let Some(converted) = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            let converted = converted.unwrap();
+            output.extend_from_slice(converted.paths.to_str().unwrap().as_bytes());
+            lines.extend(converted.errors.iter().map(|e| e.position().line));
+        }
+        assert_eq!(output, OUTPUT, "buf_len {}", buf_len);
+        assert_eq!(lines, vec![2, 4]);
+    }
+}
+
+#[tokio::test]
+async fn write_to() {
+    for buf_len in [1, 3, 64] {
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+        let num_paths = async_stream::convert_reader(converter(), INPUT, seps(), buf_len)
+            .write_to(&mut output, |e| errors.push(e.path().to_owned()))
+            .await
+            .unwrap();
+        assert_eq!(num_paths, 5);
+        assert_eq!(output, OUTPUT);
+        assert_eq!(errors, vec!["bad", "worse"]);
+    }
+}
+
+#[tokio::test]
+async fn eof_policy() {
+    let mut output = Vec::new();
+    let result = async_stream::convert_reader(converter(), INPUT, seps(), 4)
+        .with_eof_policy(EofPolicy::Error)
+        .write_to(&mut output, |_| {})
+        .await;
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+    assert_eq!(output, b"/mnt/c/a\n\n/mnt/d/b/c\n\n");
+    
+    let mut output = Vec::new();
+    async_stream::convert_reader(converter(), INPUT, seps(), 4)
+        .with_eof_policy(EofPolicy::Ignore)
+        .write_to(&mut output, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(output, b"/mnt/c/a\n\n/mnt/d/b/c\n\n");
+}
+
+#[tokio::test]
+async fn async_reader() {
+    for buf_len in [1, 3, 64] {
+        let mut errors = 0;
+        let mut reader = async_stream::convert_reader(converter(), INPUT, seps(), buf_len)
+            .into_async_reader(|_| errors += 1);
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).await.unwrap();
+        drop(reader);
+        assert_eq!(output, OUTPUT);
+        assert_eq!(errors, 2);
+    }
+}
+
+#[tokio::test]
+async fn large_file() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    let mut expected = Vec::new();
+    let mut len = 0;
+    let mut i = 0;
+    while len <= MMAP_CHUNK_LEN {
+        let path = format!("C:\\dir\\file{}\n", i);
+        file.write_all(path.as_bytes()).unwrap();
+        expected.extend_from_slice(format!("/mnt/c/dir/file{}\n", i).as_bytes());
+        len += path.len();
+        i += 1;
+    }
+    let converted = async_stream::convert_file(converter(), file.path(), seps(), BufferSizeBlocks::default())
+        .await
+        .unwrap();
+    // the file isn't read all at once
+    assert_eq!(converted.buf_len(), MMAP_CHUNK_LEN);
+    let mut output = Vec::new();
+    assert_eq!(converted.write_to(&mut output, |_| {}).await.unwrap(), i);
+    assert!(output == expected);
+}
@@ -0,0 +1,101 @@
+use wslpath::convert::build_log::{BuildLog, BuildLogOptions};
+use wslpath::convert::path_sep::WindowsPathSep;
+use wslpath::convert::text::TextSummary;
+use wslpath::convert::win_to_wsl;
+
+mod common;
+
+use common::test_converter;
+
+fn build_log(base_dir: Option<&str>) -> BuildLog<win_to_wsl::Converter> {
+    let converter = test_converter(false);
+    let options = BuildLogOptions {
+        base_dir: base_dir.map(|dir| dir.as_bytes().to_vec()),
+        ..BuildLogOptions::new(WindowsPathSep::BackSlash)
+    };
+    BuildLog::new(converter, options)
+}
+
+fn convert(log: &mut BuildLog<win_to_wsl::Converter>, input: &str) -> String {
+    let mut output = Vec::new();
+    log.convert_log(input.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn make() {
+    let input = "\
+src/a.c:3:1: error: x
+make[1]: Entering directory 'C:/proj/lib'
+b.c:4:2: warning: see ../inc/c.h:5 and C:/proj/d.h:6
+  from sub\\e.h(7),
+make[2]: Entering directory `/home/me/gen'
+f.c:1: note: version 1.2:3
+make[2]: Leaving directory `/home/me/gen'
+make[1]: Leaving directory 'C:/proj/lib'
+g.c:8: error
+";
+    let output = "\
+/home/me/src/a.c:3:1: error: x
+make[1]: Entering directory '/mnt/c/proj/lib'
+/mnt/c/proj/lib/b.c:4:2: warning: see /mnt/c/proj/inc/c.h:5 and /mnt/c/proj/d.h:6
+  from /mnt/c/proj/lib/sub/e.h(7),
+make[2]: Entering directory `/home/me/gen'
+/home/me/gen/f.c:1: note: version 1.2:3
+make[2]: Leaving directory `/home/me/gen'
+make[1]: Leaving directory '/mnt/c/proj/lib'
+/home/me/g.c:8: error
+";
+    assert_eq!(convert(&mut build_log(Some("/home/me")), input), output);
+}
+
+#[test]
+fn ninja() {
+    let mut log = build_log(Some(r"C:\proj"));
+    let input = "ninja: Entering directory `out\\build'\r\n../../src/a.cc:3:4: error: x\r\n";
+    let output = "ninja: Entering directory `out\\build'\r\n/mnt/c/proj/src/a.cc:3:4: error: x\r\n";
+    assert_eq!(convert(&mut log, input), output);
+}
+
+#[test]
+fn msbuild() {
+    let input = r#"1>Project "C:\s\a.sln" on node 1 (default targets).
+1>Project "C:\s\a.sln" (1) is building "C:\s\lib\lib.vcxproj" (2) on node 2 (default targets).
+2>x.cpp(12,5): error C2065: 'y': undeclared identifier [C:\s\lib\lib.vcxproj]
+1>a.cpp(1): warning C4100: unused
+2>Done Building Project "C:\s\lib\lib.vcxproj" (default targets).
+1>..\b.cpp(2): warning
+"#;
+    let output = r#"1>Project "/mnt/c/s/a.sln" on node 1 (default targets).
+1>Project "/mnt/c/s/a.sln" (1) is building "/mnt/c/s/lib/lib.vcxproj" (2) on node 2 (default targets).
+2>/mnt/c/s/lib/x.cpp(12,5): error C2065: 'y': undeclared identifier [/mnt/c/s/lib/lib.vcxproj]
+1>/mnt/c/s/a.cpp(1): warning C4100: unused
+2>Done Building Project "/mnt/c/s/lib/lib.vcxproj" (default targets).
+1>/mnt/c/b.cpp(2): warning
+"#;
+    assert_eq!(convert(&mut build_log(None), input), output);
+}
+
+#[test]
+fn unresolved() {
+    let mut log = build_log(None);
+    let input = "a.c:1: error\nmake: Entering directory 'C:/x'\nb.c:2: error\n";
+    let output = "a.c:1: error\nmake: Entering directory '/mnt/c/x'\n/mnt/c/x/b.c:2: error\n";
+    assert_eq!(convert(&mut log, input), output);
+    assert_eq!(log.summary(), TextSummary { found: 3, failed: 1 });
+}
+
+#[test]
+fn msbuild_request_ids() {
+    let mut log = build_log(Some("/tmp"));
+    let input = r#"1>Project "C:\s\a.sln" (1:2) is building "C:\p\b.vcxproj" (2:2) on node 1 (default targets).
+2>src\b.c(3,1): error
+3>src\c.c(4,1): error
+"#;
+    let output = r#"1>Project "/mnt/c/s/a.sln" (1:2) is building "/mnt/c/p/b.vcxproj" (2:2) on node 1 (default targets).
+2>/mnt/c/p/src/b.c(3,1): error
+3>src\c.c(4,1): error
+"#;
+    assert_eq!(convert(&mut log, input), output);
+    assert_eq!(log.summary(), TextSummary { found: 4, failed: 1 });
+}
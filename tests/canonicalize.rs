@@ -0,0 +1,113 @@
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+use wslpath::convert::{Converter, wsl_to_win};
+use wslpath::convert::path_sep::WindowsPathSep;
+use wslpath::convert::wsl_to_win::canonicalize::{CacheLimits, CanonicalizeCache};
+
+/// A tree with nested directories, files, and absolute, relative, chained and looping symlinks,
+/// deleted when the [`TempDir`] is dropped, and its canonical root.
+fn tree() -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::create_dir_all(root.join("a/b/c")).unwrap();
+    fs::write(root.join("a/b/c/file"), "").unwrap();
+    fs::write(root.join("a/file"), "").unwrap();
+    symlink(root.join("a/b"), root.join("abs")).unwrap();
+    symlink("a/b/c", root.join("rel")).unwrap();
+    symlink("../file", root.join("a/b/up")).unwrap();
+    symlink("rel", root.join("chain")).unwrap();
+    symlink("loop2", root.join("loop1")).unwrap();
+    symlink("loop1", root.join("loop2")).unwrap();
+    symlink("missing", root.join("dangling")).unwrap();
+    (dir, root)
+}
+
+const PATHS: &[&str] = &[
+    "",
+    "a",
+    "a/",
+    "a/b/c/file",
+    "a/b/c/file/",
+    "a/b/c/file/x",
+    "a/b/../b/./c/file",
+    "a/b/c/../../file",
+    "abs/c/file",
+    "abs/../file",
+    "rel/file",
+    "rel/../../file",
+    "chain/file",
+    "a/b/up",
+    "loop1",
+    "loop1/x",
+    "dangling",
+    "missing",
+    "missing/x",
+];
+
+fn check(cache: &CanonicalizeCache, root: &Path) {
+    for path in PATHS {
+        let path = root.join(path);
+        let expected = path.canonicalize().map_err(|e| e.to_string());
+        let actual = cache.canonicalize(&path).map_err(|e| e.to_string());
+        assert_eq!(actual, expected, "{:?}", path);
+    }
+}
+
+#[test]
+fn same_as_canonicalize() {
+    let (_dir, root) = tree();
+    let cache = CanonicalizeCache::new(CacheLimits::default());
+    // twice, so the second time everything's cached
+    check(&cache, &root);
+    assert!(!cache.is_empty());
+    check(&cache, &root);
+}
+
+#[test]
+fn relative() {
+    let (_dir, root) = tree();
+    // relative paths are resolved against the base directory, not the process's current one
+    let converter = wsl_to_win::Converter::new(wsl_to_win::Options {
+        sep: WindowsPathSep::Slash,
+        canonicalize: true,
+        base_directory: Some(root.clone()),
+        distro: Some("test".into()),
+        keep_relative: false,
+        canonicalize_cache: Some(CacheLimits::default()),
+    }).unwrap();
+    let file = root.join("a/b/c/file");
+    assert_eq!(converter.convert_path(Path::new("rel/file")).unwrap(), converter.convert_path(&file).unwrap());
+    assert!(!converter.canonicalize_cache().unwrap().is_empty());
+}
+
+#[test]
+fn max_dirs() {
+    let (_dir, root) = tree();
+    let cache = CanonicalizeCache::new(CacheLimits {
+        max_dirs: Some(2),
+        ..Default::default()
+    });
+    check(&cache, &root);
+    assert!(cache.len() <= 2);
+}
+
+#[test]
+fn max_age() {
+    let (_dir, root) = tree();
+    let cache = CanonicalizeCache::new(CacheLimits {
+        max_age: Some(Default::default()),
+        ..Default::default()
+    });
+    let root = &root;
+    assert_eq!(cache.canonicalize(&root.join("abs/c/file")).unwrap(), root.join("a/b/c/file"));
+    // point abs somewhere else, which an expired cache notices
+    fs::remove_file(root.join("abs")).unwrap();
+    symlink(root.join("a"), root.join("abs")).unwrap();
+    fs::create_dir(root.join("a/c")).unwrap();
+    fs::write(root.join("a/c/file"), "").unwrap();
+    assert_eq!(cache.canonicalize(&root.join("abs/c/file")).unwrap(), root.join("a/c/file"));
+}
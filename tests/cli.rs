@@ -0,0 +1,129 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Run the extended CLI with [`stdin`].
+fn wslpath(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wslpath"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+const WSL: &[&str] = &["wsl", "--distro", "test", "--dont-convert-root-loop", "--quiet"];
+
+fn args<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    WSL.iter().chain(args).copied().collect()
+}
+
+#[test]
+fn failed_paths_are_empty_records() {
+    let output = wslpath(&args(&[r"C:\a", "bad", r"D:\b"]), b"");
+    assert_eq!(output.stdout, b"/mnt/c/a\n\n/mnt/d/b\n");
+    assert_eq!(output.status.code(), Some(2));
+    
+    let output = wslpath(&args(&["bad", "worse"]), b"");
+    assert_eq!(output.stdout, b"\n\n");
+    assert_eq!(output.status.code(), Some(3));
+    
+    // paths read from files line up the same way
+    let input = b"a\0C:\\x\0C:\\y\0z\0";
+    let output = wslpath(&args(&["--from-files", "--read-line-sep", "null", "--write-line-sep", "null", "/dev/stdin"]), input);
+    assert_eq!(output.stdout, b"\0/mnt/c/x\0/mnt/c/y\0\0");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn jsonl_records() {
+    let output = wslpath(&args(&["--format", "jsonl", r"C:\a", "bad"]), b"");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+{\"input\":\"C:\\\\a\",\"line\":1,\"output\":\"/mnt/c/a\",\"source\":null}
+{\"error\":{\"index\":null,\"kind\":\"Parse\",\"message\":\"parse error\"},\"input\":\"bad\",\"line\":2,\"source\":null}
+");
+    assert_eq!(output.status.code(), Some(2));
+    
+    let input = b"C:\\a\n\nbad\r\nD:\\b";
+    let output = wslpath(&args(&["--format", "jsonl", "--from-files", "--read-line-sep", "crlf"]), input);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+{\"input\":\"C:\\\\a\",\"line\":1,\"output\":\"/mnt/c/a\",\"source\":\"-\"}
+{\"error\":{\"index\":null,\"kind\":\"Parse\",\"message\":\"parse error\"},\"input\":\"bad\",\"line\":3,\"source\":\"-\"}
+{\"input\":\"D:\\\\b\",\"line\":4,\"output\":\"/mnt/d/b\",\"source\":\"-\"}
+");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn jsonl_lines_across_reads() {
+    // more than one read's worth, so paths and separators are split between reads
+    let mut input = Vec::new();
+    for i in 0..1000 {
+        input.extend_from_slice(format!("C:\\{}\r\n", i).as_bytes());
+    }
+    let output = wslpath(&args(&["--format", "jsonl", "--from-files", "--read-line-sep", "\\r\\n"]), &input);
+    let output = String::from_utf8(output.stdout).unwrap();
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1000);
+    for (i, line) in lines.iter().enumerate() {
+        let expected = format!("\"line\":{},\"output\":\"/mnt/c/{}\"", i + 1, i);
+        assert!(line.contains(&expected), "{}", line);
+    }
+}
+
+#[test]
+fn build_log_counts_paths() {
+    let args = ["filter", "wsl", "--build-log", "--base-dir", r"C:\p", "--distro", "test", "--quiet"];
+    let output = wslpath(&args, b"a.c:1: error\nC:\\a?b.c:2: error\n");
+    assert_eq!(output.stdout, b"/mnt/c/p/a.c:1: error\nC:\\a?b.c:2: error\n");
+    // one of the two paths failed, not the one file
+    assert_eq!(output.status.code(), Some(2));
+    
+    let output = wslpath(&args, b"C:\\a?b.c:2: error\n");
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn build_log_is_only_for_wsl() {
+    // a usage error, like clap's, not an environment one
+    let output = wslpath(&["filter", "win", "--build-log", "--distro", "test"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn filter_counts_paths() {
+    let args = ["filter", "wsl", "--distro", "test", "--quiet"];
+    let output = wslpath(&args, b"C:/src/b.c:3: error\nC:\\a?b.c:2: error\n");
+    assert_eq!(output.stdout, b"/mnt/c/src/b.c:3: error\nC:\\a?b.c:2: error\n");
+    assert_eq!(output.status.code(), Some(2));
+    
+    let output = wslpath(&args, b"no paths\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn list_entry_errors() {
+    let list = r"C:\a;C:\<;D:\b";
+    let output = wslpath(&["wsl", "--distro", "test", "--dont-convert-root-loop", "--list", "--failed-entries", "drop", list], b"");
+    assert_eq!(output.stdout, b"/mnt/c/a:/mnt/d/b\n");
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("warning: error converting entry 1 "));
+    assert_eq!(output.status.code(), Some(2));
+    
+    let output = wslpath(&args(&["--list", "--failed-entries", "drop", r"C:\a;D:\b"]), b"");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn exec_reserved_exit_codes() {
+    let exec = |args: &[&str]| {
+        let args = ["exec", "--distro", "test", "--quiet"].iter().chain(args).copied().collect::<Vec<_>>();
+        wslpath(&args, b"").status.code()
+    };
+    // a flag's path has to exist to be canonicalized
+    assert_eq!(exec(&["--canonicalize", "--flag", "-o", "--", "true", "-o", "/does/not/exist"]), Some(125));
+    assert_eq!(exec(&["--", "/does/not/exist"]), Some(127));
+    assert_eq!(exec(&["--", "sh", "-c", "exit 3"]), Some(3));
+}
@@ -0,0 +1,12 @@
+use wslpath::convert::{Converter, win_to_wsl};
+
+/// A converter for the `test` distro that never spawns `cmd.exe`, so it works without Windows.
+pub fn test_converter(keep_relative: bool) -> win_to_wsl::Converter {
+    let options = win_to_wsl::Options {
+        convert_root_loop: false,
+        distro: Some("test".into()),
+        keep_relative,
+        ..Default::default()
+    };
+    win_to_wsl::Converter::new(options).unwrap()
+}
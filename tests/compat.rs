@@ -0,0 +1,54 @@
+use std::process::{Command, Output};
+
+/// Run the binary as the system `wslpath` would be run.
+fn wslpath(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_wslpath"))
+        .arg("--compat")
+        .args(args)
+        .env("WSL_DISTRO_NAME", "test")
+        .current_dir("/")
+        .output()
+        .unwrap()
+}
+
+/// stdout and the exit code
+fn run(args: &[&str]) -> (String, i32) {
+    let output = wslpath(args);
+    (String::from_utf8(output.stdout).unwrap(), output.status.code().unwrap())
+}
+
+#[test]
+fn to_wsl() {
+    assert_eq!(run(&[r"C:\Users"]), ("/mnt/c/Users\n".into(), 0));
+    assert_eq!(run(&["-u", r"c:\users"]), ("/mnt/c/users\n".into(), 0));
+    assert_eq!(run(&["-u", "C:/Users"]), ("/mnt/c/Users\n".into(), 0));
+    // relative paths are kept relative unless -a is given
+    assert_eq!(run(&[r"a\b"]), ("a/b\n".into(), 0));
+    assert_eq!(run(&["-a", r"a\b"]), ("/a/b\n".into(), 0));
+    assert_eq!(run(&["-ua", r"a\b"]), ("/a/b\n".into(), 0));
+}
+
+#[test]
+fn to_win() {
+    assert_eq!(run(&["-w", "/tmp"]), ("\\\\wsl$\\test\\tmp\n".into(), 0));
+    assert_eq!(run(&["-m", "/tmp"]), ("//wsl$/test/tmp\n".into(), 0));
+    assert_eq!(run(&["-w", "/tmp/.."]), ("\\\\wsl$\\test\\\n".into(), 0));
+    assert_eq!(run(&["-wa", "tmp"]), ("\\\\wsl$\\test\\tmp\n".into(), 0));
+    // the last direction flag wins
+    assert_eq!(run(&["-u", "-m", "/tmp"]), ("//wsl$/test/tmp\n".into(), 0));
+}
+
+#[test]
+fn errors() {
+    let output = wslpath(&["-w", "/does/not/exist"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(output.stderr, b"wslpath: /does/not/exist: No such file or directory\n");
+    
+    for args in [&[][..], &["-x", "/tmp"], &["-w"], &["/a", "/b"]] {
+        let output = wslpath(args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(output.stdout.is_empty());
+        assert!(output.stderr.starts_with(b"wslpath: Invalid argument\nUsage:\n"));
+    }
+}
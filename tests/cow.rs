@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use wslpath::convert::{Converter, wsl_to_win};
+use wslpath::convert::path_sep::WindowsPathSep;
+
+mod common;
+
+use common::test_converter;
+
+fn wsl_to_win(sep: WindowsPathSep) -> wsl_to_win::Converter {
+    let options = wsl_to_win::Options {
+        sep,
+        canonicalize: false,
+        base_directory: Some(PathBuf::from("/")),
+        distro: Some("test".into()),
+        keep_relative: true,
+        canonicalize_cache: None,
+    };
+    wsl_to_win::Converter::new(options).unwrap()
+}
+
+/// Check that [`path`] is borrowed by every Cow API if and only if [`borrowed`],
+/// and that it's converted the same as by [`Converter::convert`] either way.
+fn check<C: Converter>(converter: &C, path: &str, borrowed: bool) {
+    let expected = converter.convert(path).unwrap();
+    assert_eq!(converter.is_unchanged(path.as_bytes()), borrowed, "{}", path);
+    
+    let cow = converter.convert_cow(path.as_bytes()).unwrap();
+    assert_eq!(matches!(cow, Cow::Borrowed(_)), borrowed, "{}", path);
+    assert_eq!(&*cow, expected.as_encoded_bytes());
+    
+    let cow = converter.convert_os_str(OsStr::new(path)).unwrap();
+    assert_eq!(matches!(cow, Cow::Borrowed(_)), borrowed, "{}", path);
+    assert_eq!(&*cow, expected.as_os_str());
+    
+    let cow = converter.convert_path(Path::new(path)).unwrap();
+    assert_eq!(matches!(cow, Cow::Borrowed(_)), borrowed, "{}", path);
+    assert_eq!(&*cow, Path::new(&expected));
+}
+
+#[test]
+fn win_to_wsl_borrows_unchanged() {
+    let converter = test_converter(true);
+    check(&converter, "a", true);
+    check(&converter, "a.txt", true);
+    check(&converter, r"a\b", false);
+    check(&converter, r"C:\a", false);
+    check(&converter, "C:", false);
+}
+
+#[test]
+fn wsl_to_win_borrows_unchanged() {
+    let converter = wsl_to_win(WindowsPathSep::Slash);
+    check(&converter, "a", true);
+    check(&converter, "a/b", true);
+    check(&converter, "/a", false);
+    check(&converter, "a:b", false);
+    
+    let converter = wsl_to_win(WindowsPathSep::BackSlash);
+    check(&converter, "a", true);
+    check(&converter, "a/b", false);
+}
+
+#[test]
+fn errors_arent_borrowed() {
+    let converter = test_converter(true);
+    assert!(!converter.is_unchanged(b"a|b"));
+    assert!(converter.convert_cow(b"a|b").is_err());
+    assert!(converter.convert_path(Path::new("a|b")).is_err());
+}
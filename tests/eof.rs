@@ -0,0 +1,115 @@
+use std::io;
+use std::io::Write;
+
+use tempfile::NamedTempFile;
+
+use wslpath::convert::{BufferSizeBlocks, Converter, PathSeparators};
+use wslpath::convert::eof::EofPolicy;
+use wslpath::convert::line_sep::LineSep;
+
+mod common;
+
+use common::test_converter;
+
+const SEPS: PathSeparators<LineSep, LineSep> = PathSeparators {
+    input: LineSep::LF,
+    output: LineSep::LF,
+};
+
+/// Convert all of [`input`] from a file, in chunks of at most [`max_blocks`] blocks.
+fn convert(input: &[u8], eof_policy: EofPolicy, max_blocks: u64) -> (Vec<u8>, Vec<io::Error>) {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(input).unwrap();
+    let converter = test_converter(false);
+    let blocks = BufferSizeBlocks {
+        min: 1,
+        max: max_blocks,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    let mut errors = Vec::new();
+    for converted in converter.convert_file(file.path(), &SEPS, blocks).unwrap().with_eof_policy(eof_policy) {
+        match converted {
+            Ok(converted) => {
+                assert!(converted.errors.is_empty(), "{:?}", converted.errors);
+                output.extend_from_slice(converted.paths.to_str().unwrap().as_bytes());
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    (output, errors)
+}
+
+#[test]
+fn trailing_separator() {
+    for &policy in &[EofPolicy::Convert, EofPolicy::Error, EofPolicy::Ignore] {
+        let (output, errors) = convert(b"C:\\a\nD:\\b\n", policy, u64::MAX);
+        assert_eq!(output, b"/mnt/c/a\n/mnt/d/b\n");
+        assert!(errors.is_empty());
+    }
+}
+
+#[test]
+fn no_trailing_separator_convert() {
+    let (output, errors) = convert(b"C:\\a\nD:\\b", EofPolicy::Convert, u64::MAX);
+    assert_eq!(output, b"/mnt/c/a\n/mnt/d/b\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn no_trailing_separator_error() {
+    let (output, errors) = convert(b"C:\\a\nD:\\b", EofPolicy::Error, u64::MAX);
+    assert_eq!(output, b"/mnt/c/a\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn no_trailing_separator_ignore() {
+    let (output, errors) = convert(b"C:\\a\nD:\\b", EofPolicy::Ignore, u64::MAX);
+    assert_eq!(output, b"/mnt/c/a\n");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn empty() {
+    let (output, errors) = convert(b"", EofPolicy::Error, u64::MAX);
+    assert!(output.is_empty());
+    assert!(errors.is_empty());
+}
+
+/// Many more paths than fit in one block, so the remainder is carried across reads.
+fn many_paths(n: usize, trailing_separator: bool) -> (Vec<u8>, Vec<u8>) {
+    let mut input = Vec::new();
+    let mut expected = Vec::new();
+    for i in 0..n {
+        input.extend_from_slice(format!("C:\\dir\\file{}\n", i).as_bytes());
+        expected.extend_from_slice(format!("/mnt/c/dir/file{}\n", i).as_bytes());
+    }
+    if !trailing_separator {
+        input.pop();
+    }
+    (input, expected)
+}
+
+#[test]
+fn larger_than_buffer() {
+    for &trailing_separator in &[true, false] {
+        let (input, expected) = many_paths(10_000, trailing_separator);
+        let (output, errors) = convert(&input, EofPolicy::Convert, 1);
+        assert!(errors.is_empty());
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn larger_than_buffer_ignore() {
+    let (input, expected) = many_paths(10_000, false);
+    let (output, errors) = convert(&input, EofPolicy::Ignore, 1);
+    assert!(errors.is_empty());
+    let last_line = expected[..expected.len() - 1]
+        .iter()
+        .rposition(|c| *c == b'\n')
+        .unwrap();
+    assert_eq!(output, &expected[..last_line + 1]);
+}
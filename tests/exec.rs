@@ -0,0 +1,107 @@
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::io;
+
+use wslpath::convert::{Converter, wsl_to_win};
+use wslpath::convert::path_sep::WindowsPathSep;
+use wslpath::exec::{ArgConverter, ArgRules, Command};
+use wslpath::exec::spawn::{DryRun, Spawner};
+
+/// Records what would've been run, since there's no Windows to run it on.
+#[derive(Default)]
+struct Recorder(Vec<Command>);
+
+impl Spawner for Recorder {
+    fn spawn(&mut self, command: &Command) -> io::Result<i32> {
+        self.0.push(command.clone());
+        Ok(0)
+    }
+}
+
+fn converter(rules: ArgRules) -> ArgConverter<wsl_to_win::Converter> {
+    let options = wsl_to_win::Options {
+        sep: WindowsPathSep::BackSlash,
+        canonicalize: false,
+        base_directory: None,
+        distro: Some("test".into()),
+        keep_relative: false,
+        canonicalize_cache: None,
+    };
+    ArgConverter::new(wsl_to_win::Converter::new(options).unwrap(), rules)
+}
+
+fn args(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+}
+
+/// [`path`] under the current directory as a `\\wsl$` path.
+fn unc(path: &str) -> String {
+    let dir = env::current_dir().unwrap();
+    let dir = dir.to_str().unwrap().replace('/', "\\");
+    match path {
+        "" => format!(r"\\wsl$\test{}", dir),
+        path => format!(r"\\wsl$\test{}\{}", dir, path),
+    }
+}
+
+#[test]
+fn existing() {
+    let converter = converter(ArgRules::existing());
+    let converted = converter.convert_args(args(&[".", "src/lib.rs", "--in=src", "-", "--out=missing", "missing", "/"])).unwrap();
+    assert_eq!(converted, args(&[
+        &unc(""),
+        &unc(r"src\lib.rs"),
+        &format!("--in={}", unc("src")),
+        "-",
+        "--out=missing",
+        "missing",
+        r"\\wsl$\test\",
+    ]));
+}
+
+#[test]
+fn prefixes_and_flags() {
+    let converter = converter(ArgRules {
+        prefixes: args(&["--out=", "/Fo", "/F"]),
+        flags: args(&["-o"]),
+        ..Default::default()
+    });
+    let converted = converter.convert_args(args(&["--out=./new", "/Fonew.obj", "-o", "a", "-o", "src", "--out="])).unwrap();
+    assert_eq!(converted, args(&[
+        &format!("--out={}", unc("new")),
+        &format!("/Fo{}", unc("new.obj")),
+        "-o",
+        &unc("a"),
+        "-o",
+        &unc("src"),
+        "--out=",
+    ]));
+}
+
+#[test]
+fn spawn() {
+    let converter = converter(ArgRules::existing());
+    let command = converter.command("notepad.exe".into(), args(&["src", "it's"])).unwrap();
+    let mut recorder = Recorder::default();
+    assert_eq!(recorder.spawn(&command).unwrap(), 0);
+    assert_eq!(recorder.0, vec![command.clone()]);
+    
+    let mut out = Vec::new();
+    DryRun(&mut out).spawn(&command).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), format!("notepad.exe '{}' 'it'\\''s'\n", unc("src")));
+}
+
+#[test]
+fn error_sources() {
+    let converter = converter(ArgRules {
+        prefixes: args(&["--out="]),
+        ..Default::default()
+    });
+    let error = converter.convert_args(args(&["x", "--out=a\0b"])).unwrap_err();
+    assert_eq!(error.arg_index(), 1);
+    // the message is just the argument, and what went wrong with it is the source
+    assert_eq!(error.to_string(), r#"error converting argument 1 "--out=a\0b""#);
+    let source = error.source().unwrap().to_string();
+    assert!(source.starts_with("path contains a null byte"), "{}", source);
+}
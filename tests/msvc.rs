@@ -0,0 +1,149 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use wslpath::convert::Converter;
+use wslpath::exec::{ArgGrammar, ArgKind};
+use wslpath::exec::msvc::{convert_output_line, Msvc, Tool};
+use wslpath::exec::response_file::{decode, encode, join, split, Encoding};
+
+mod common;
+
+use common::test_converter;
+
+fn args(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+}
+
+fn classify(tool: Tool, arg_list: &[&str]) -> Vec<ArgKind> {
+    Msvc { tool }.classify(&args(arg_list))
+}
+
+#[test]
+fn tool() {
+    assert_eq!(Tool::from_program("cl.exe".as_ref()), Some(Tool::Cl));
+    assert_eq!(Tool::from_program("/opt/msvc/bin/LINK.EXE".as_ref()), Some(Tool::Link));
+    assert_eq!(Tool::from_program("lib".as_ref()), Some(Tool::Lib));
+    assert_eq!(Tool::from_program("clang-cl.exe".as_ref()), None);
+}
+
+#[test]
+fn cl() {
+    use ArgKind::*;
+    assert_eq!(
+        classify(Tool::Cl, &["/nologo", "/Foout.obj", "/Fo:out/", "-Iinc", "/I", "inc", "/FA", "/Fa", "a.c", "/usr/a.c", "@a.rsp", "/DX=/y"]),
+        vec![Plain, Path(3), Path(4), Path(2), Plain, Path(0), Plain, Plain, Plain, Path(0), ResponseFile(1), Plain],
+    );
+    // the rest is for link
+    assert_eq!(
+        classify(Tool::Cl, &["/link", "/LIBPATH:lib", "/out:a.exe", "kernel32.lib", "src/lib.rs", "/Foa.obj"]),
+        vec![Plain, Path(9), Path(5), Plain, Path(0), Plain],
+    );
+    // only inputs that exist are paths, not anything with a `/` in it
+    assert_eq!(classify(Tool::Cl, &["/D", "X=a/b", "missing/a.c"]), vec![Plain, Plain, Plain]);
+}
+
+#[test]
+fn link_and_lib() {
+    use ArgKind::*;
+    assert_eq!(
+        classify(Tool::Link, &["/DEBUG", "/MAP", "/MAP:a.map", "-pdb:a.pdb", "/DEFAULTLIB:libcmt"]),
+        vec![Plain, Plain, Path(5), Path(5), Plain],
+    );
+    assert_eq!(classify(Tool::Lib, &["/OUT:a.lib", "/LIST:x", "/PDB:a.pdb"]), vec![Path(5), Path(6), Plain]);
+}
+
+#[test]
+fn windows_paths() {
+    use ArgKind::*;
+    // already Windows paths, so they're left as is
+    assert_eq!(
+        classify(Tool::Cl, &[r"/FoC:\out\", "/I", r"\\server\share\inc", r"/ID:/inc", "/Foout/"]),
+        vec![Plain, Plain, Plain, Plain, Path(3)],
+    );
+    assert_eq!(classify(Tool::Link, &[r"/LIBPATH:C:\sdk", r"/OUT:\\server\a.exe"]), vec![Plain, Plain]);
+}
+
+#[test]
+fn response_files() {
+    let text = b"/nologo  \"a b\\\\\" x\\y \"say \"\"hi\"\"\" \\\"q\r\n\"\"";
+    let expected = vec![&b"/nologo"[..], b"a b\\", b"x\\y", b"say \"hi\"", b"\"q", b""];
+    assert_eq!(split(text), expected);
+    assert_eq!(split(&join(expected.iter().copied())), expected);
+    for encoding in &[Encoding::Plain, Encoding::Utf8, Encoding::Utf16] {
+        let text = "/Fo\\\\wsl$\\d\\é.obj".as_bytes();
+        assert_eq!(decode(&encode(*encoding, text)), (*encoding, text.to_vec()));
+    }
+}
+
+#[test]
+fn output() {
+    let converter = test_converter(false);
+    let convert = |line: &str| String::from_utf8(convert_output_line(&converter, line.as_bytes()).into_owned()).unwrap();
+    assert_eq!(convert(r"C:\src\a b.c(12,5): error C2065: 'x': undeclared"), "/mnt/c/src/a b.c(12,5): error C2065: 'x': undeclared");
+    assert_eq!(convert(r"  D:\a.h(3) : warning C4101"), "  /mnt/d/a.h(3) : warning C4101");
+    assert_eq!(convert(r"Note: including file:   C:\inc\a.h"), "Note: including file:   /mnt/c/inc/a.h");
+    assert_eq!(
+        convert(r#"LINK : fatal error LNK1104: cannot open file 'C:\a.lib' or "C:\b.lib""#),
+        r#"LINK : fatal error LNK1104: cannot open file '/mnt/c/a.lib' or "/mnt/c/b.lib""#,
+    );
+    assert_eq!(convert(r"a.c"), "a.c");
+    assert_eq!(convert(r"C:\a.c: not a diagnostic"), r"C:\a.c: not a diagnostic");
+    // can't be converted, so it's left as is
+    assert_eq!(convert(r"'C:\<'"), r"'C:\<'");
+}
+
+#[test]
+fn rewrite_response_file() {
+    use wslpath::convert::path_sep::WindowsPathSep;
+    use wslpath::convert::wsl_to_win;
+    use wslpath::exec::ArgConverter;
+    
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    let rsp = dir.join("a.rsp");
+    fs::write(&rsp, encode(Encoding::Utf16, br#"/nologo /Fo/tmp/a.obj "/I/usr/my dir" /FoC:\out\ /link /LIBPATH:C:\sdk"#)).unwrap();
+    let converter = || {
+        let options = wsl_to_win::Options {
+            sep: WindowsPathSep::BackSlash,
+            canonicalize: false,
+            base_directory: None,
+            distro: Some("test".into()),
+            keep_relative: false,
+            canonicalize_cache: None,
+        };
+        ArgConverter::new(wsl_to_win::Converter::new(options).unwrap(), Msvc { tool: Tool::Cl })
+    };
+    let mut arg = OsString::from("@");
+    arg.push(&rsp);
+    let rewritten_path = |converted: &[OsString]| {
+        let rewritten = converted[0].to_str().unwrap().strip_prefix(r"@\\wsl$\test").unwrap().replace('\\', "/");
+        PathBuf::from(rewritten)
+    };
+    
+    let arg_converter = converter();
+    let converted = arg_converter.convert_args(vec![arg.clone()]).unwrap();
+    let rewritten = rewritten_path(&converted);
+    // it's a temp file, not next to the original
+    assert_ne!(rewritten.parent(), Some(dir));
+    assert!(rewritten.file_name().unwrap().to_str().unwrap().ends_with("a.rsp"));
+    let (encoding, text) = decode(&fs::read(&rewritten).unwrap());
+    assert_eq!(encoding, Encoding::Utf16);
+    assert_eq!(split(&text), vec![
+        &b"/nologo"[..],
+        br"/Fo\\wsl$\test\tmp\a.obj",
+        br"/I\\wsl$\test\usr\my dir",
+        // already Windows paths
+        br"/FoC:\out\",
+        b"/link",
+        br"/LIBPATH:C:\sdk",
+    ]);
+    // and it's removed once the command's done with it
+    drop(arg_converter);
+    assert!(!rewritten.exists());
+    
+    // a dry run doesn't write anything
+    let converted = converter().dry_run().convert_args(vec![arg]).unwrap();
+    assert!(!rewritten_path(&converted).exists());
+    assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "rayon")]
+
+use wslpath::convert::{Converter, PathSeparators, Position};
+use wslpath::convert::line_sep::LineSep;
+use wslpath::convert::parallel;
+
+mod common;
+
+use common::test_converter;
+
+const SEPS: PathSeparators<LineSep, LineSep> = PathSeparators {
+    input: LineSep::LF,
+    output: LineSep::LF,
+};
+
+/// Enough paths for several chunks, with a failing one and an empty line every so often,
+/// and an unterminated path at the end.
+fn input() -> Vec<u8> {
+    let mut input = Vec::new();
+    for i in 0..50_000 {
+        match i % 997 {
+            0 => input.extend_from_slice(format!("bad{}\n", i).as_bytes()),
+            1 => input.push(b'\n'),
+            _ => input.extend_from_slice(format!("C:\\dir\\file{}\n", i).as_bytes()),
+        }
+    }
+    input.extend_from_slice(b"C:\\unterminated");
+    input
+}
+
+#[test]
+fn same_as_sequential() {
+    let converter = test_converter(false);
+    let input = input();
+    let start = Position {
+        index: 3,
+        line: 7,
+        offset: 1000,
+    };
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let parallel = pool.install(|| parallel::convert_all_from(&converter, &input, &SEPS, start));
+    let sequential = converter.convert_all_from(&input, &SEPS, start);
+    // output is in input order
+    assert!(parallel.paths == sequential.paths);
+    assert_eq!(parallel.remainder_index, sequential.remainder_index);
+    assert_eq!(parallel.num_paths, sequential.num_paths);
+    assert_eq!(parallel.next, sequential.next);
+    // errors are in order too, with positions in the whole input, not just their chunk
+    let errors = |errors: &[_]| errors
+        .iter()
+        .map(|e: &wslpath::convert::OneConvertError<_>| (e.position(), e.path().to_owned()))
+        .collect::<Vec<_>>();
+    assert_eq!(errors(&parallel.errors), errors(&sequential.errors));
+    assert_eq!(parallel.errors.len(), 51);
+    assert_eq!(parallel.errors[1].path(), "bad997");
+    assert_eq!(parallel.errors[1].position().line, 7 + 997);
+}
@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+
+use wslpath::convert::{ConvertErrorInfo, Converter};
+use wslpath::convert::path_list::{convert_list, EmptyEntryPolicy, FailedEntryPolicy, ListOptions, ListSyntax, PathListConverter};
+
+mod common;
+
+use common::test_converter;
+
+fn options(empty: EmptyEntryPolicy, failed: FailedEntryPolicy) -> ListOptions {
+    ListOptions {
+        empty,
+        failed,
+        ..ListOptions::new(ListSyntax::Windows, ListSyntax::Unix)
+    }
+}
+
+#[test]
+fn split() {
+    fn split(syntax: ListSyntax, list: &str) -> Vec<Cow<'_, [u8]>> {
+        syntax.split(list.as_bytes())
+    }
+    
+    assert_eq!(split(ListSyntax::Unix, "/a::/b"), vec![&b"/a"[..], b"", b"/b"]);
+    assert_eq!(split(ListSyntax::Unix, ""), vec![&b""[..]]);
+    assert_eq!(split(ListSyntax::Windows, r#"C:\a;"C:\b;c";D:\"x""#), vec![&br"C:\a"[..], br"C:\b;c", br"D:\x"]);
+    assert!(matches!(split(ListSyntax::Windows, r"C:\a;C:\b")[1], Cow::Borrowed(_)));
+}
+
+#[test]
+fn policies() {
+    let converter = test_converter(false);
+    let list = br"C:\a;;C:\<;D:\b";
+    let convert = |empty, failed| convert_list(&converter, list, &options(empty, failed));
+    
+    let error = convert(EmptyEntryPolicy::Keep, FailedEntryPolicy::Error).unwrap_err();
+    assert_eq!(error.entry_index(), 2);
+    assert_eq!(error.entry(), r"C:\<");
+    
+    let converted = convert(EmptyEntryPolicy::Keep, FailedEntryPolicy::Drop).unwrap();
+    assert_eq!(converted.list, "/mnt/c/a::/mnt/d/b");
+    assert_eq!(converted.errors.len(), 1);
+    
+    let converted = convert(EmptyEntryPolicy::Drop, FailedEntryPolicy::Drop).unwrap();
+    assert_eq!(converted.list, "/mnt/c/a:/mnt/d/b");
+    
+    // `C:\<` has a `:` in it, so it can't be kept in a Unix list without splitting it
+    let error = convert(EmptyEntryPolicy::Keep, FailedEntryPolicy::Keep).unwrap_err();
+    assert_eq!((error.entry_index(), error.kind()), (2, "Unwritable"));
+    
+    let options = ListOptions {
+        failed: FailedEntryPolicy::Keep,
+        ..ListOptions::new(ListSyntax::Windows, ListSyntax::Windows)
+    };
+    let converted = convert_list(&converter, list, &options).unwrap();
+    assert_eq!(converted.list, r"/mnt/c/a;;C:\<;/mnt/d/b");
+    assert_eq!(converted.errors.len(), 1);
+}
+
+#[test]
+fn error_indices() {
+    let converter = test_converter(false);
+    let options = options(EmptyEntryPolicy::Keep, FailedEntryPolicy::Error);
+    let index = |list: &str| convert_list(&converter, list.as_bytes(), &options).unwrap_err().index();
+    assert_eq!(index(r"C:\a;C:\<"), Some(8));
+    // the quotes are in the list, but not the entry that's converted
+    assert_eq!(index(r#"C:\a;"C:\b;c";"C:\"<"#), Some(19));
+    assert_eq!(index(r#""C:\a";C"#), Some(7));
+}
+
+#[test]
+fn quoting() {
+    let converter = PathListConverter::from_converter(test_converter(false), ListOptions::new(ListSyntax::Windows, ListSyntax::Unix));
+    // a : can't be escaped in a Unix list
+    assert!(converter.convert_cow(br#"C:\a;"C:\b;c""#).is_ok());
+    assert!(converter.convert_cow(br#"C:\a;C:\b:c"#).is_err());
+}
+
+#[test]
+fn entry_errors() {
+    let converter = PathListConverter::from_converter(test_converter(false), options(EmptyEntryPolicy::Keep, FailedEntryPolicy::Drop));
+    assert_eq!(converter.convert_cow(br"C:\a;C:\<;D:\b;D:\?").unwrap(), &b"/mnt/c/a:/mnt/d/b"[..]);
+    assert_eq!(converter.convert_cow(br"C:\a").unwrap(), &b"/mnt/c/a"[..]);
+    let entry_errors = converter.take_entry_errors();
+    let indices = entry_errors
+        .iter()
+        .map(|errors| errors.iter().map(|e| e.entry_index()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(indices, [[1, 3]]);
+    assert!(converter.take_entry_errors().is_empty());
+}
@@ -0,0 +1,68 @@
+use wslpath::convert::{Converter, PathSeparators, Position};
+use wslpath::convert::line_sep::LineSep;
+
+mod common;
+
+use common::test_converter;
+
+/// The position of each error converting [`input`], reading [`buf_len`] bytes at a time.
+fn error_positions(input: &[u8], seps: &PathSeparators<LineSep, LineSep>, buf_len: usize) -> Vec<(Position, String)> {
+    let converter = test_converter(false);
+    let mut errors = Vec::new();
+    for converted in converter.convert_reader(input, seps, buf_len) {
+        let converted = converted.unwrap();
+        errors.extend(converted.errors.iter().map(|e| (e.position(), e.path().to_str().unwrap().to_owned())));
+    }
+    errors
+}
+
+fn position(index: usize, line: usize, offset: usize) -> Position {
+    Position { index, line, offset }
+}
+
+#[test]
+fn small_reads() {
+    let seps = PathSeparators {
+        input: LineSep::LF,
+        output: LineSep::LF,
+    };
+    let input = b"C:\\a\nbad\n\nC:\\bb\nworse\nC:\\c\nfoo\n";
+    let expected = vec![
+        (position(1, 2, 5), "bad".to_owned()),
+        // the empty line isn't a path, but it's still a line
+        (position(3, 5, 16), "worse".to_owned()),
+        (position(5, 7, 27), "foo".to_owned()),
+    ];
+    // positions are of the whole input, no matter how it's split into chunks
+    for buf_len in [1, 2, 3, 5, 7, 64] {
+        assert_eq!(error_positions(input, &seps, buf_len), expected, "buf_len {}", buf_len);
+    }
+}
+
+#[test]
+fn small_reads_crlf() {
+    let seps = PathSeparators {
+        input: LineSep::CRLF,
+        output: LineSep::LF,
+    };
+    let input = b"C:\\a\r\nbad\r\nC:\\b\r\nworse\r\n";
+    let expected = vec![
+        (position(1, 2, 6), "bad".to_owned()),
+        (position(3, 4, 17), "worse".to_owned()),
+    ];
+    for buf_len in [1, 2, 4, 6, 64] {
+        assert_eq!(error_positions(input, &seps, buf_len), expected, "buf_len {}", buf_len);
+    }
+}
+
+#[test]
+fn then() {
+    let start = position(10, 5, 100);
+    // a chunk's positions start at index 0, line 1, byte 0
+    assert_eq!(start.then(Position::default()), start);
+    assert_eq!(start.then(position(2, 1, 7)), position(12, 5, 107));
+    assert_eq!(start.then(position(3, 4, 20)), position(13, 8, 120));
+    // chaining is the same as adding up the chunks
+    let (a, b) = (position(1, 2, 3), position(4, 5, 6));
+    assert_eq!(start.then(a).then(b), start.then(Position::default().then(a).then(b)));
+}
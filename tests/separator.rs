@@ -0,0 +1,117 @@
+use wslpath::convert::{Converter, InputPathSeparator, OutputPathSeparator, PathSeparators};
+use wslpath::convert::line_sep::LineSep;
+use wslpath::convert::separator::{ByteSet, SepFn, SepStr, Separator};
+
+mod common;
+
+use common::test_converter;
+
+/// Convert all of [`input`], reading [`buf_len`] bytes at a time,
+/// returning the output and the line of each error.
+fn convert<I, O>(input: &[u8], seps: &PathSeparators<I, O>, buf_len: usize) -> (String, Vec<usize>)
+    where I: InputPathSeparator,
+          O: OutputPathSeparator {
+    let converter = test_converter(false);
+    let mut output = String::new();
+    let mut lines = Vec::new();
+    for converted in converter.convert_reader(input, seps, buf_len) {
+        let converted = converted.unwrap();
+        output.push_str(converted.paths.to_str().unwrap());
+        lines.extend(converted.errors.iter().map(|e| e.position().line));
+    }
+    (output, lines)
+}
+
+#[test]
+fn parse() {
+    let parse = |s: &str| s.parse::<Separator>();
+    assert_eq!(parse("null"), Ok(Separator::Line(LineSep::Null)));
+    assert_eq!(parse(r"\n"), Ok(Separator::Line(LineSep::LF)));
+    assert_eq!(parse("CRLF"), Ok(Separator::Line(LineSep::CRLF)));
+    assert_eq!(parse("whitespace"), Ok(Separator::Whitespace));
+    assert_eq!(parse(";"), Ok(Separator::Byte(b';')));
+    assert_eq!(parse(r"\t"), Ok(Separator::Byte(b'\t')));
+    assert_eq!(parse("0"), Ok(Separator::Byte(b'0')));
+    assert_eq!(parse(r"\0"), Ok(Separator::Line(LineSep::Null)));
+    assert_eq!(parse("::"), Ok(Separator::Str(SepStr::new("::").unwrap())));
+    assert_eq!(parse(r"\x00\\"), Ok(Separator::Str(SepStr::new(b"\0\\".to_vec()).unwrap())));
+    assert!(parse("").is_err());
+    assert!(parse(r"\x0").is_err());
+    assert!(parse(r"\q").is_err());
+    for s in &["null", "whitespace", ";", "0", "::", r"\x00\\"] {
+        assert_eq!(parse(&parse(s).unwrap().to_string()), parse(s));
+    }
+}
+
+#[test]
+fn multi_byte_across_reads() {
+    let seps = PathSeparators {
+        input: SepStr::new("::").unwrap(),
+        output: b';',
+    };
+    // a separator is split across reads for every odd buffer length
+    for buf_len in 1..8 {
+        let (output, errors) = convert(b"C:\\a::D:\\b:c::::C:\\c::", &seps, buf_len);
+        assert_eq!(output, "/mnt/c/a;;/mnt/c/c;");
+        assert_eq!(errors, vec![2]);
+    }
+}
+
+#[test]
+fn multi_byte_lines() {
+    // a whole separator ends one line, not each byte of it
+    let sep = SepStr::new("::").unwrap();
+    assert!(!sep.matches(b':'));
+    assert!(!sep.is_line_end(b':'));
+    assert_eq!(sep.lines_ended(b"::"), 1);
+    
+    // but with a `\n` in it, lines are the input's own
+    let seps = PathSeparators {
+        input: SepStr::new(";\n").unwrap(),
+        output: b'\n',
+    };
+    for buf_len in 1..8 {
+        let (output, errors) = convert(b"C:\\a;\nbad;\nC:\\b;\nworse;\n", &seps, buf_len);
+        assert_eq!(output, "/mnt/c/a\n\n/mnt/c/b\n\n");
+        assert_eq!(errors, vec![2, 4]);
+    }
+    let seps = PathSeparators {
+        input: SepStr::new("\n\n").unwrap(),
+        output: b'\n',
+    };
+    for buf_len in 1..8 {
+        let (output, errors) = convert(b"C:\\a\n\nbad\n\nC:\\b\n\nworse\n\n", &seps, buf_len);
+        assert_eq!(output, "/mnt/c/a\n\n/mnt/c/b\n\n");
+        assert_eq!(errors, vec![3, 7]);
+    }
+}
+
+#[test]
+fn whitespace_lines() {
+    let seps = PathSeparators {
+        input: Separator::Whitespace,
+        output: Separator::Byte(b' '),
+    };
+    let (output, errors) = convert(b"C:\\a \tC:\\b<\n\nC:\\c\r\nC:\\d>", &seps, 4);
+    assert_eq!(output, "/mnt/c/a  /mnt/c/c  ");
+    assert_eq!(errors, vec![1, 4]);
+}
+
+#[test]
+fn closures_and_byte_sets() {
+    let input = b"C:\\a;C:\\b,C:\\c";
+    let expected = "/mnt/c/a\r\n/mnt/c/b\r\n/mnt/c/c\r\n";
+    let converter = test_converter(false);
+    let seps = PathSeparators {
+        input: SepFn(|c| c == b';' || c == b','),
+        output: &b"\r\n"[..],
+    };
+    let converted = converter.convert_end_from(input, &seps, Default::default());
+    assert_eq!(converted.paths, expected);
+    let seps = PathSeparators {
+        input: ByteSet::new(b";,"),
+        output: LineSep::CRLF,
+    };
+    let converted = converter.convert_end_from(input, &seps, Default::default());
+    assert_eq!(converted.paths, expected);
+}
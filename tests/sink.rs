@@ -0,0 +1,78 @@
+use std::ops::Range;
+
+use wslpath::convert::{ConvertErrorInfo, Converter, win_to_wsl};
+use wslpath::convert::line_sep::LineSep;
+use wslpath::convert::separator::SepStr;
+use wslpath::convert::sink::SpanSink;
+
+mod common;
+
+use common::test_converter;
+
+#[test]
+fn each_span() {
+    let converter = test_converter(false);
+    let input = b"C:\\a\n\nbad\n\\\\wsl$\\test\\a|b\nD:\\x\nunterminated";
+    let mut results = Vec::new();
+    let mut sink = |span: Range<usize>, output: Result<&[u8], win_to_wsl::ConvertError>| {
+        let output = output
+            .map(|path| String::from_utf8(path.to_vec()).unwrap())
+            .map_err(|e| (e.kind(), e.index()));
+        results.push((span, output));
+    };
+    let remainder_index = converter.convert_each(input, LineSep::LF, &mut sink);
+    assert_eq!(remainder_index, 31);
+    assert_eq!(&input[remainder_index..], b"unterminated");
+    // empty paths are skipped, and each span is of the path without its separator
+    assert_eq!(results, vec![
+        (0..4, Ok("/mnt/c/a".into())),
+        (6..9, Err(("Parse", None))),
+        // the index is within the path, not the whole input
+        (10..25, Err(("IllegalFileNameChar", Some(13)))),
+        (26..30, Ok("/mnt/d/x".into())),
+    ]);
+}
+
+#[test]
+fn each_span_multi_byte_sep() {
+    let converter = test_converter(false);
+    let sep = SepStr::new("::").unwrap();
+    let input = b"C:\\a::::D:\\b:::C:\\c::";
+    let mut spans = Vec::new();
+    let remainder_index = converter.convert_each(input, &sep, &mut |span: Range<usize>, output: Result<&[u8], _>| {
+        spans.push((span, output.is_ok()));
+    });
+    assert_eq!(remainder_index, input.len());
+    // `:::` is a separator followed by `:`, which starts the next path
+    assert_eq!(spans, vec![(0..4, true), (8..12, true), (14..19, false)]);
+    assert_eq!(&input[14..19], b":C:\\c");
+    
+    // no complete path
+    assert_eq!(converter.convert_each(b"C:\\a:", &sep, &mut |_, _: Result<&[u8], _>| panic!()), 0);
+}
+
+#[test]
+fn span_sink() {
+    let converter = test_converter(false);
+    let mut sink = SpanSink::new();
+    assert!(sink.is_empty());
+    let input = b"C:\\a\nbad\nD:\\bb\n";
+    assert_eq!(converter.convert_each(input, LineSep::LF, &mut sink), input.len());
+    assert_eq!(sink.len(), 3);
+    assert_eq!(sink.buf, b"/mnt/c/a/mnt/d/bb");
+    let spans = sink.spans
+        .iter()
+        .map(|(input, output)| (input.clone(), output.as_ref().ok().cloned()))
+        .collect::<Vec<_>>();
+    assert_eq!(spans, vec![(0..4, Some(0..8)), (5..8, None), (9..14, Some(8..17))]);
+    assert_eq!(sink.get(0).unwrap().unwrap(), "/mnt/c/a");
+    assert_eq!(sink.get(1).unwrap().unwrap_err().kind(), "Parse");
+    assert_eq!(sink.get(2).unwrap().unwrap(), "/mnt/d/bb");
+    assert!(sink.get(3).is_none());
+    
+    // more paths are appended after the ones already collected
+    converter.convert_each(b"E:\\\n", LineSep::LF, &mut sink);
+    assert_eq!(sink.len(), 4);
+    assert_eq!(sink.spans[3].0, 0..3);
+    assert_eq!(sink.get(3).unwrap().unwrap(), "/mnt/e/");
+}
@@ -0,0 +1,101 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::net::UnixStream;
+
+use wslpath::convert::{BufferSizeBlocks, ConvertFileError, Converter, PathSeparators};
+use wslpath::convert::line_sep::LineSep;
+
+mod common;
+
+use common::test_converter;
+
+const SEPS: PathSeparators<LineSep, LineSep> = PathSeparators {
+    input: LineSep::LF,
+    output: LineSep::LF,
+};
+
+const INPUT: &[u8] = b"C:\\a\nbad\nD:\\b\\c\nworse\nE:\\\n";
+
+const OUTPUT: &[u8] = b"/mnt/c/a\n\n/mnt/d/b/c\n\n/mnt/e/\n";
+
+#[test]
+fn write_to() {
+    let converter = test_converter(false);
+    for buf_len in [1, 3, 64] {
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+        let num_paths = converter
+            .convert_reader(INPUT, &SEPS, buf_len)
+            .write_to(&mut output, |e| errors.push(e.position().line))
+            .unwrap();
+        assert_eq!(num_paths, 5);
+        assert_eq!(output, OUTPUT);
+        assert_eq!(errors, vec![2, 4]);
+    }
+}
+
+#[test]
+fn reader() {
+    let converter = test_converter(false);
+    for buf_len in [1, 3, 64] {
+        let mut errors = Vec::new();
+        let mut reader = converter
+            .convert_reader(INPUT, &SEPS, buf_len)
+            .into_reader(|e| errors.push(e.path().to_owned()));
+        // read less than a chunk at a time
+        let mut output = Vec::new();
+        let mut buf = [0; 2];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..n]);
+        }
+        drop(reader);
+        assert_eq!(output, OUTPUT);
+        assert_eq!(errors, vec!["bad", "worse"]);
+    }
+}
+
+#[test]
+fn fd() {
+    let converter = test_converter(false);
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(b"skipped\n").unwrap();
+    file.write_all(INPUT).unwrap();
+    file.seek(SeekFrom::Start(b"skipped\n".len() as u64)).unwrap();
+    // a small regular file is read all at once, starting from where it's at
+    let converted = converter.convert_fd(&file, &SEPS, BufferSizeBlocks::default()).unwrap();
+    assert!(converted.buf_len() >= INPUT.len());
+    let mut output = Vec::new();
+    converted.write_to(&mut output, |_| {}).unwrap();
+    assert_eq!(output, OUTPUT);
+    // the file's only borrowed, so it's still open
+    file.seek(SeekFrom::Start(0)).unwrap();
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).unwrap();
+    assert_eq!(&contents[b"skipped\n".len()..], INPUT);
+    
+    let dir = tempfile::tempdir().unwrap();
+    let result = converter.convert_fd(File::open(dir.path()).unwrap(), &SEPS, BufferSizeBlocks::default());
+    assert!(matches!(result, Err(ConvertFileError::IsADirectory)));
+}
+
+#[test]
+fn fd_socket() {
+    let converter = test_converter(false);
+    let (mut writer, reader) = UnixStream::pair().unwrap();
+    writer.write_all(INPUT).unwrap();
+    drop(writer);
+    let blocks = BufferSizeBlocks {
+        min: 1,
+        ..Default::default()
+    };
+    // anything but a regular file is read a block at a time
+    let converted = converter.convert_fd(reader, &SEPS, blocks).unwrap();
+    assert!(converted.buf_len() > 0 && converted.buf_len() < usize::MAX);
+    let mut output = Vec::new();
+    assert_eq!(converted.write_to(&mut output, |_| {}).unwrap(), 5);
+    assert_eq!(output, OUTPUT);
+}
@@ -0,0 +1,59 @@
+use wslpath::convert::text::{convert_text, find_paths, TextOptions, TextPaths, TextSummary};
+
+mod common;
+
+use common::test_converter;
+
+fn found<'a>(line: &'a str, options: &TextOptions) -> Vec<&'a str> {
+    find_paths(line.as_bytes(), options)
+        .into_iter()
+        .map(|range| &line[range])
+        .collect()
+}
+
+#[test]
+fn windows_paths() {
+    let options = TextOptions::new(TextPaths::Windows);
+    let find = |line| found(line, &options);
+    assert_eq!(find(r"C:\src\a.rs(12,5): error: x"), [r"C:\src\a.rs"]);
+    assert_eq!(find(r"C:\src\a.rs:12:5: error"), [r"C:\src\a.rs"]);
+    assert_eq!(find(r"(see C:\a.h). and [D:\b]"), [r"C:\a.h", r"D:\b"]);
+    assert_eq!(find(r#"File "C:\Program Files (x86)\a.py", line 3"#), [r"C:\Program Files (x86)\a.py"]);
+    assert_eq!(find(r"at \\wsl$\d\home\a.c(3) and \\?"), [r"\\wsl$\d\home\a.c"]);
+    assert_eq!(find(r"xC:\a ab\\c 1/2"), Vec::<&str>::new());
+    
+    let options = TextOptions {
+        unc: false,
+        quoted_spaces: false,
+        terminators: b",".to_vec(),
+        ..TextOptions::new(TextPaths::Windows)
+    };
+    assert_eq!(found(r#"C:\a,C:\b "C:\c d" \\wsl$\d\e"#, &options), [r"C:\a", r"C:\b", r"C:\c"]);
+}
+
+#[test]
+fn linux_paths() {
+    let options = TextOptions::new(TextPaths::Linux);
+    let find = |line| found(line, &options);
+    assert_eq!(find("/usr/lib/a.rs:12:5: error"), ["/usr/lib/a.rs"]);
+    assert_eq!(find("see (/usr/a.h). /nologo /DX=/y a/b https://x/y"), ["/usr/a.h"]);
+    assert_eq!(find("'/tmp/a b/c' /tmp/a b"), ["/tmp/a b/c", "/tmp/a"]);
+    
+    let options = TextOptions {
+        existing_root: false,
+        ..TextOptions::new(TextPaths::Linux)
+    };
+    assert_eq!(found("/nologo /DX=/y", &options), ["/nologo", "/DX=/y"]);
+}
+
+#[test]
+fn convert() {
+    let converter = test_converter(false);
+    // `C:\a?b` is found, but `?` can't be in a Windows path, so it's left as is,
+    // and `D:/x/y` is converted even though the converter expects `\`
+    let input = "C:\\src\\a.rs(12,5): error\r\nno paths\nC:\\a?b and 'D:/x/y'";
+    let mut output = Vec::new();
+    let summary = convert_text(&converter, &TextOptions::new(TextPaths::Windows), input.as_bytes(), &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "/mnt/c/src/a.rs(12,5): error\r\nno paths\nC:\\a?b and '/mnt/d/x/y'");
+    assert_eq!(summary, TextSummary { found: 3, failed: 1 });
+}
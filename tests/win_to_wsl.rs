@@ -0,0 +1,71 @@
+use wslpath::convert::{ConvertErrorInfo, Converter, win_to_wsl};
+
+mod common;
+
+use common::test_converter;
+
+fn convert(converter: &win_to_wsl::Converter, path: &str) -> Result<String, &'static str> {
+    converter
+        .convert_cow(path.as_bytes())
+        .map(|path| String::from_utf8(path.into_owned()).unwrap())
+        .map_err(|e| e.kind())
+}
+
+#[test]
+fn convert_paths() {
+    let converter = test_converter(false);
+    assert_eq!(convert(&converter, r"C:\Users\a"), Ok("/mnt/c/Users/a".into()));
+    assert_eq!(convert(&converter, "D:"), Ok("/mnt/d".into()));
+    assert_eq!(convert(&converter, r"\\?\C:\a"), Ok("/mnt/c/a".into()));
+    assert_eq!(convert(&converter, r"\\wsl$\test\home\a"), Ok("/home/a".into()));
+    assert_eq!(convert(&converter, r"\\wsl$\other\home"), Err("Parse"));
+    assert_eq!(convert(&converter, r"C:a"), Err("Parse"));
+}
+
+#[test]
+fn parse_errors_before_distro_lookup() {
+    // without a distro, looking it up might fail,
+    // but paths that can't be UNC paths shouldn't even try
+    let options = win_to_wsl::Options {
+        convert_root_loop: false,
+        ..Default::default()
+    };
+    let converter = win_to_wsl::Converter::new(options).unwrap();
+    assert_eq!(convert(&converter, "a"), Err("Parse"));
+    assert_eq!(convert(&converter, r"\a"), Err("Parse"));
+    assert_eq!(convert(&converter, ""), Err("Parse"));
+    assert_eq!(convert(&converter, r"C:\a"), Ok("/mnt/c/a".into()));
+}
+
+#[test]
+fn illegal_chars() {
+    let converter = test_converter(false);
+    let error = |path: &str| {
+        let e = converter.convert_cow(path.as_bytes()).unwrap_err();
+        (e.kind(), e.index())
+    };
+    // with `\` as the sep, `/` is illegal, wherever it is in the path
+    assert_eq!(error(r"C:\a/b"), ("IllegalFileNameChar", Some(4)));
+    assert_eq!(error(r"C:/a"), ("IllegalFileNameChar", Some(2)));
+    assert_eq!(error(r"\\?\C:\a/b"), ("IllegalFileNameChar", Some(8)));
+    assert_eq!(error(r"\\wsl$\test\a/b"), ("IllegalFileNameChar", Some(13)));
+    assert_eq!(error(r"\/wsl$\test\a"), ("IllegalFileNameChar", Some(1)));
+    assert_eq!(error("C/a"), ("IllegalFileNameChar", Some(1)));
+    // indices are into the whole path, not what's left after the prefix
+    assert_eq!(error(r"C:\a<b"), ("IllegalFileNameChar", Some(4)));
+    assert_eq!(error(r"\\wsl$\test\a|b"), ("IllegalFileNameChar", Some(13)));
+    assert!(!converter.is_unchanged(b"a/b"));
+}
+
+#[test]
+fn encoded_chars() {
+    let converter = test_converter(false);
+    let decode = |path: &[u8]| converter.convert_cow(path).unwrap().into_owned();
+    assert_eq!(decode("C:\\a\u{f02a}b".as_bytes()), b"/mnt/c/a*b");
+    // invalid or truncated encodings are just bytes, left as is
+    assert_eq!(decode(b"C:\\a\xEF\x80A"), b"/mnt/c/a\xEF\x80A");
+    assert_eq!(decode(b"C:\\a\xEF\x81!"), b"/mnt/c/a\xEF\x81!");
+    assert_eq!(decode(b"C:\\a\xEF\x80\xC0b"), b"/mnt/c/a\xEF\x80\xC0b");
+    assert_eq!(decode(b"C:\\a\xEF\x80"), b"/mnt/c/a\xEF\x80");
+    assert_eq!(decode(b"C:\\a\xEF"), b"/mnt/c/a\xEF");
+}
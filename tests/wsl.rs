@@ -0,0 +1,13 @@
+use wslpath::convert::wsl::{get_wsl_version, is_wsl, WslVersion};
+
+#[test]
+fn versions() {
+    assert_eq!(WslVersion::Wsl1.to_string(), "1");
+    assert_eq!(WslVersion::Wsl2.number(), 2);
+    assert!(WslVersion::Wsl1.has_root_loop());
+    assert!(!WslVersion::Wsl2.has_root_loop());
+    // only detected on WSL, but either way it doesn't fail
+    if !is_wsl() {
+        assert_eq!(get_wsl_version(), None);
+    }
+}
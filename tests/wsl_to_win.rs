@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use wslpath::convert::{ConvertErrorInfo, Converter, wsl_to_win};
+use wslpath::convert::path_sep::WindowsPathSep;
+
+fn converter(sep: WindowsPathSep, keep_relative: bool) -> wsl_to_win::Converter {
+    let options = wsl_to_win::Options {
+        sep,
+        canonicalize: false,
+        base_directory: Some(PathBuf::from("/home/me")),
+        distro: Some("test".into()),
+        keep_relative,
+        canonicalize_cache: None,
+    };
+    wsl_to_win::Converter::new(options).unwrap()
+}
+
+fn convert(converter: &wsl_to_win::Converter, path: &str) -> Result<String, &'static str> {
+    converter
+        .convert_cow(path.as_bytes())
+        .map(|path| String::from_utf8(path.into_owned()).unwrap())
+        .map_err(|e| e.kind())
+}
+
+#[test]
+fn unc_paths() {
+    let converter = converter(WindowsPathSep::BackSlash, false);
+    assert_eq!(convert(&converter, "/home/me/a"), Ok(r"\\wsl$\test\home\me\a".into()));
+    assert_eq!(convert(&converter, "/"), Ok(r"\\wsl$\test\".into()));
+    // relative paths are resolved against the base directory
+    assert_eq!(convert(&converter, "a/b"), Ok(r"\\wsl$\test\home\me\a\b".into()));
+    
+    let converter = self::converter(WindowsPathSep::Slash, false);
+    assert_eq!(convert(&converter, "/home/me/a"), Ok("//wsl$/test/home/me/a".into()));
+}
+
+#[test]
+fn keep_relative() {
+    let converter = converter(WindowsPathSep::BackSlash, true);
+    assert_eq!(convert(&converter, "a/b"), Ok(r"a\b".into()));
+    assert_eq!(convert(&converter, "../a"), Ok(r"..\a".into()));
+    assert_eq!(convert(&converter, "/a"), Ok(r"\\wsl$\test\a".into()));
+    assert!(!converter.is_unchanged(b"a/b"));
+    
+    let converter = self::converter(WindowsPathSep::Slash, true);
+    assert!(converter.is_unchanged(b"a/b"));
+    assert!(!converter.is_unchanged(b"/a"));
+    assert!(!converter.is_unchanged(b"a:b"));
+}
+
+#[test]
+fn encoding() {
+    let converter = converter(WindowsPathSep::BackSlash, true);
+    // chars that are illegal in Windows file names are mapped to U+F000 + c,
+    // the inverse of what the Windows to WSL converter decodes
+    assert_eq!(convert(&converter, "a:b"), Ok("a\u{f03a}b".into()));
+    assert_eq!(convert(&converter, r#"<>"|?*\"#), Ok("\u{f03c}\u{f03e}\u{f022}\u{f07c}\u{f03f}\u{f02a}\u{f05c}".into()));
+    assert_eq!(convert(&converter, "\x01/\x1f"), Ok("\u{f001}\\\u{f01f}".into()));
+    assert_eq!(convert(&converter, "/a:b"), Ok("\\\\wsl$\\test\\a\u{f03a}b".into()));
+}
+
+#[test]
+fn errors() {
+    let converter = converter(WindowsPathSep::BackSlash, true);
+    let e = converter.convert_cow(b"/a\0b").unwrap_err();
+    assert_eq!((e.kind(), e.index()), ("NullByte", Some(2)));
+    let e = converter.convert_cow(b"a\0b").unwrap_err();
+    assert_eq!((e.kind(), e.index()), ("NullByte", Some(1)));
+    // the index is into the original path, not the one made absolute
+    let converter = self::converter(WindowsPathSep::BackSlash, false);
+    let e = converter.convert_cow(b"a/\0b").unwrap_err();
+    assert_eq!((e.kind(), e.index()), ("NullByte", Some(2)));
+    
+    // without a base directory or canonicalizing, a relative path can't be made absolute
+    let options = wsl_to_win::Options {
+        sep: WindowsPathSep::BackSlash,
+        canonicalize: false,
+        base_directory: None,
+        distro: Some("test".into()),
+        keep_relative: false,
+        canonicalize_cache: None,
+    };
+    let converter = wsl_to_win::Converter::new(options).unwrap();
+    assert_eq!(convert(&converter, "a"), Err("NotAbsolute"));
+    assert_eq!(convert(&converter, "/a"), Ok(r"\\wsl$\test\a".into()));
+}
@@ -0,0 +1,73 @@
+use std::ffi::OsString;
+
+use wslpath::convert::path_list::FailedEntryPolicy;
+use wslpath::convert::wslenv::{translate_env, EnvOptions, Target, Translation, WslEnv, WslEnvError};
+
+mod common;
+
+use common::test_converter;
+
+fn env(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+    vars.iter().map(|(name, value)| (name.into(), value.into())).collect()
+}
+
+#[test]
+fn parse() {
+    let wslenv = WslEnv::parse(b"A:B/p::C/lu:D/wp:A/l").unwrap();
+    let vars = wslenv.vars();
+    assert_eq!(vars.len(), 5);
+    assert_eq!(vars[1].translation, Translation::Path);
+    assert!(vars[2].is_shared_to(Target::Wsl) && !vars[2].is_shared_to(Target::Win));
+    assert!(vars[3].is_shared_to(Target::Win) && !vars[3].is_shared_to(Target::Wsl));
+    assert_eq!(wslenv.get("A".as_ref()).unwrap().translation, Translation::List);
+    assert_eq!(WslEnv::parse(b"A/x"), Err(WslEnvError::UnknownFlag { name: "A".into(), flag: 'x' }));
+    assert_eq!(WslEnv::parse(b"A/pl"), Err(WslEnvError::PathAndList { name: "A".into() }));
+}
+
+#[test]
+fn translate() {
+    let wslenv = WslEnv::parse(b"P/p:L/l:U:W/w:E/p").unwrap();
+    let input = env(&[
+        ("WSLENV", "P/p:L/l:U:W/w:E/p"),
+        ("P", r"C:\a"),
+        ("L", r"C:\a;;rel"),
+        ("U", r"C:\unchanged"),
+        ("W", r"C:\win-only"),
+        ("E", ""),
+        ("OTHER", "x"),
+    ]);
+    let mut options = EnvOptions::new(Target::Wsl);
+    let translated = translate_env(&test_converter(true), &wslenv, input.clone(), &options).unwrap();
+    assert_eq!(translated.vars, env(&[
+        ("WSLENV", "P/p:L/l:U:W/w:E/p"),
+        ("P", "/mnt/c/a"),
+        ("L", "/mnt/c/a::rel"),
+        ("U", r"C:\unchanged"),
+        // only shared from WSL to Windows, so it's not passed to WSL
+        ("E", ""),
+        ("OTHER", "x"),
+    ]));
+    options.shared_only = true;
+    let translated = translate_env(&test_converter(true), &wslenv, input, &options).unwrap();
+    let names = translated.vars.iter().map(|(name, _)| name.to_str().unwrap()).collect::<Vec<_>>();
+    assert_eq!(names, ["WSLENV", "P", "L", "U", "E"]);
+}
+
+#[test]
+fn failed() {
+    let wslenv = WslEnv::parse(b"P/p:L/l").unwrap();
+    let input = env(&[("P", r"C:\<"), ("L", r"C:\a;C:\>")]);
+    let mut options = EnvOptions::new(Target::Wsl);
+    let error = translate_env(&test_converter(true), &wslenv, input.clone(), &options).unwrap_err();
+    assert_eq!(error.name(), "P");
+    
+    options.failed = FailedEntryPolicy::Keep;
+    let translated = translate_env(&test_converter(true), &wslenv, input.clone(), &options).unwrap();
+    // `C:\>` can't be kept in a Unix list, so the whole of `L` is kept unconverted instead
+    assert_eq!(translated.vars, env(&[("P", r"C:\<"), ("L", r"C:\a;C:\>")]));
+    assert_eq!(translated.errors.len(), 2);
+    
+    options.failed = FailedEntryPolicy::Drop;
+    let translated = translate_env(&test_converter(true), &wslenv, input, &options).unwrap();
+    assert_eq!(translated.vars, env(&[("L", "/mnt/c/a")]));
+}